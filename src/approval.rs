@@ -0,0 +1,121 @@
+// ABOUTME: Landing policy requiring queued items to be approved before they are tested.
+// ABOUTME: Approval comes from description trailers or a record written by `jjq approve`.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, JJQ_BOOKMARK};
+use crate::jj::Jj;
+use crate::lock::{hostname, unix_now};
+use crate::metadata;
+
+/// Who approved a commit with `jjq approve`, and when.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Approval {
+    pub by: String,
+    pub host: String,
+    /// Unix time (seconds) of the approval.
+    pub at: u64,
+}
+
+/// Whether queued items need approval, and which description trailers
+/// count as one.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    pub required: bool,
+    /// Trailer keys (e.g. "Reviewed-by") that approve a commit when present
+    /// with a value.
+    pub trailers: Vec<String>,
+}
+
+impl Policy {
+    pub fn from_config(jj: &Jj) -> Result<Policy> {
+        Ok(Policy {
+            required: config::get(jj, "require_approval")?.as_deref() == Some("true"),
+            trailers: config::get(jj, "approval_trailers")?
+                .map(|v| {
+                    v.split(',')
+                        .map(|k| k.trim().to_string())
+                        .filter(|k| !k.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Who approved the commit: the first configured trailer's
+    /// "<key>: <value>", or the `jjq approve` record. None if unapproved.
+    pub fn approved_by(
+        &self,
+        jj: &Jj,
+        commit_id: &str,
+        description: &str,
+    ) -> Result<Option<String>> {
+        if let Some(trailer) = find_trailer(description, &self.trailers) {
+            return Ok(Some(trailer));
+        }
+        Ok(get(jj, commit_id)?.map(|a| format!("{} on {} (jjq approve)", a.by, a.host)))
+    }
+}
+
+/// The first `<key>: <value>` line of `description` whose key is one of
+/// `keys` (case-insensitively) and whose value is non-empty.
+fn find_trailer(description: &str, keys: &[String]) -> Option<String> {
+    description.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        let value = value.trim();
+        (!value.is_empty() && keys.iter().any(|k| k.eq_ignore_ascii_case(key.trim())))
+            .then(|| format!("{}: {}", key.trim(), value))
+    })
+}
+
+fn record_path(commit_id: &str) -> String {
+    format!("approvals/{}", commit_id)
+}
+
+/// The `jjq approve` record for a commit, if any.
+pub fn get(jj: &Jj, commit_id: &str) -> Result<Option<Approval>> {
+    match jj.file_show(&record_path(commit_id), JJQ_BOOKMARK) {
+        Ok(contents) => Ok(serde_json::from_str(&contents).ok()),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Record approval of a commit by the current user. Approval is tied to the
+/// commit, so a re-pushed change with new content needs approving again.
+pub fn record(jj: &Jj, id: u32, commit_id: &str) -> Result<Approval> {
+    let approval = Approval {
+        by: jj.user_name(),
+        host: hostname(),
+        at: unix_now(),
+    };
+    metadata::write(
+        jj,
+        &[(&record_path(commit_id), &serde_json::to_string(&approval)?)],
+        &format!("approve {}", id),
+    )?;
+    Ok(approval)
+}
+
+/// Remove a commit's approval record once it is no longer needed.
+pub fn remove(jj: &Jj, commit_id: &str) -> Result<()> {
+    metadata::remove(jj, &[&record_path(commit_id)], "remove approval")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_trailer() {
+        let keys = vec!["Reviewed-by".to_string(), "Approved-by".to_string()];
+        let desc = "Add feature\n\nSome text: here\nreviewed-by: Alice <a@example.com>\n";
+        assert_eq!(
+            find_trailer(desc, &keys),
+            Some("reviewed-by: Alice <a@example.com>".to_string())
+        );
+        assert_eq!(find_trailer("Add feature\n\nReviewed-by:\n", &keys), None);
+        assert_eq!(find_trailer("Add feature", &keys), None);
+        assert_eq!(find_trailer("Reviewed-by: Bob", &[]), None);
+    }
+}
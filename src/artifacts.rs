@@ -0,0 +1,168 @@
+// ABOUTME: Collects files produced by check runs into per-item artifact directories.
+// ABOUTME: Artifacts live under .jj/jjq-artifacts/<id>/ and are pruned by delete and clean.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::jj::Jj;
+use crate::queue;
+
+/// Return the directory holding artifacts for all items.
+pub fn root(jj: &Jj) -> Result<PathBuf> {
+    let root = jj.root();
+    Ok(root.join(".jj").join("jjq-artifacts"))
+}
+
+/// Return the artifacts directory for one item.
+pub fn dir(jj: &Jj, id: u32) -> Result<PathBuf> {
+    Ok(root(jj)?.join(queue::format_seq_id(id)))
+}
+
+/// Copy files matching `patterns` (globs relative to the workspace root) from
+/// `workspace` into `dest`, usually an item's artifacts directory, replacing
+/// anything collected there before. Returns the copied paths, relative to the
+/// workspace root. Files reached through a symlink leading outside the
+/// workspace are skipped.
+pub fn collect(dest: &Path, workspace: &Path, patterns: &[String]) -> Result<Vec<String>> {
+    if dest.exists() {
+        fs::remove_dir_all(dest)?;
+    }
+    let real_workspace = workspace.canonicalize()?;
+
+    let base = glob::Pattern::escape(&workspace.to_string_lossy());
+    let mut copied = Vec::new();
+    for pattern in patterns {
+        let full = format!("{}/{}", base, pattern);
+        let paths =
+            glob::glob(&full).with_context(|| format!("invalid artifacts pattern: {}", pattern))?;
+        for path in paths.filter_map(|p| p.ok()) {
+            // A check could link to any file jjq can read
+            let Ok(real) = path.canonicalize() else {
+                continue;
+            };
+            if !real.starts_with(&real_workspace) || !real.is_file() {
+                continue;
+            }
+            let Ok(relative) = path.strip_prefix(workspace) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().into_owned();
+            if copied.contains(&relative) {
+                continue;
+            }
+            let target = dest.join(&relative);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(&real, &target)
+                .with_context(|| format!("failed to copy artifact {}", relative))?;
+            copied.push(relative);
+        }
+    }
+    copied.sort();
+    Ok(copied)
+}
+
+/// List the artifacts collected for an item, relative to its directory.
+pub fn list(jj: &Jj, id: u32) -> Result<Vec<String>> {
+    let dir = dir(jj, id)?;
+    let mut files = Vec::new();
+    if dir.is_dir() {
+        walk(&dir, &dir, &mut files)?;
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn walk(base: &Path, dir: &Path, files: &mut Vec<String>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(base, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(base) {
+            files.push(relative.to_string_lossy().into_owned());
+        }
+    }
+    Ok(())
+}
+
+/// Remove an item's artifacts. Returns whether there were any.
+pub fn remove(jj: &Jj, id: u32) -> Result<bool> {
+    let dir = dir(jj, id)?;
+    if !dir.exists() {
+        return Ok(false);
+    }
+    fs::remove_dir_all(&dir)?;
+    Ok(true)
+}
+
+/// IDs of the items that have artifacts.
+pub fn ids(jj: &Jj) -> Result<Vec<u32>> {
+    let root = root(jj)?;
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+    let mut ids: Vec<u32> = fs::read_dir(&root)?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.file_name().to_str()?.parse().ok())
+        .collect();
+    ids.sort();
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_copies_matching_files() {
+        let root = tempfile::TempDir::new().unwrap();
+        let workspace = tempfile::TempDir::new().unwrap();
+        let ws = workspace.path();
+        fs::create_dir_all(ws.join("target/cov")).unwrap();
+        fs::write(ws.join("target/cov/lcov.info"), "lcov").unwrap();
+        fs::write(ws.join("target/cov/index.html"), "html").unwrap();
+        fs::write(ws.join("report.xml"), "<testsuite/>").unwrap();
+        fs::write(ws.join("main.go"), "package main").unwrap();
+
+        let dest = root.path().join("000007");
+        let patterns = ["target/cov/**/*".to_string(), "*.xml".to_string()];
+        let copied = collect(&dest, ws, &patterns).unwrap();
+        assert_eq!(
+            copied,
+            [
+                "report.xml",
+                "target/cov/index.html",
+                "target/cov/lcov.info"
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(dest.join("target/cov/lcov.info")).unwrap(),
+            "lcov"
+        );
+
+        // Collecting again replaces the previous artifacts
+        fs::remove_file(ws.join("report.xml")).unwrap();
+        collect(&dest, ws, &["*.xml".to_string()]).unwrap();
+        assert!(!dest.join("target").exists());
+    }
+
+    #[test]
+    fn test_collect_skips_links_outside_workspace() {
+        let root = tempfile::TempDir::new().unwrap();
+        let outside = tempfile::TempDir::new().unwrap();
+        let workspace = tempfile::TempDir::new().unwrap();
+        let ws = workspace.path();
+        fs::write(outside.path().join("secret"), "secret").unwrap();
+        fs::write(ws.join("inside.log"), "log").unwrap();
+        std::os::unix::fs::symlink(outside.path().join("secret"), ws.join("secret.log")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), ws.join("linked")).unwrap();
+        std::os::unix::fs::symlink(ws.join("inside.log"), ws.join("alias.log")).unwrap();
+
+        let dest = root.path().join("000001");
+        let patterns = ["*.log".to_string(), "linked/*".to_string()];
+        let copied = collect(&dest, ws, &patterns).unwrap();
+        assert_eq!(copied, ["alias.log", "inside.log"]);
+    }
+}
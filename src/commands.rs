@@ -1,61 +1,154 @@
 // ABOUTME: Command implementations for jjq CLI.
 // ABOUTME: Each function implements one jjq subcommand per the specification.
 
-use anyhow::{Result, bail};
+use anyhow::Result;
 use std::collections::HashMap;
-use std::env;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use tempfile::TempDir;
 
 use serde::Serialize;
 
+use crate::approval;
 use crate::config::{self, Strategy};
-use crate::exit_codes::{self, ExitError};
-use crate::jj;
+use crate::error::Error;
+use crate::jj::{self, Jj};
+use crate::landed::{self, Landed};
 use crate::lock::{self, Lock};
+use crate::metadata;
+use crate::pause::{self, Pause};
+use crate::pool;
 use crate::queue;
-
-#[derive(Serialize)]
-struct StatusOutput {
-    running: bool,
-    queue: Vec<QueueItem>,
-    failed: Vec<FailedItem>,
+use crate::runlog::LogOptions;
+use crate::runner::CheckEnv;
+use crate::schedule::{LandingClosed, Schedule};
+use crate::state::{self, Phase, Progress};
+
+/// Snapshot of the queue, as printed by `jjq status --json`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StatusOutput {
+    /// Set while the queue is paused.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paused: Option<Pause>,
+    /// Set while the landing schedule doesn't allow landing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub landing_closed: Option<LandingClosed>,
+    /// Whether a runner holds the run lock.
+    pub running: bool,
+    /// The process holding the run lock, if it recorded itself.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runner: Option<lock::Holder>,
+    /// The item the runner is processing, if it published its state.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current: Option<CurrentItem>,
+    pub queue: Vec<QueueItem>,
+    pub failed: Vec<FailedItem>,
+    /// Recently landed items, most recent first.
     #[serde(skip_serializing_if = "Vec::is_empty")]
-    landed: Vec<LandedItem>,
+    pub landed: Vec<LandedItem>,
 }
 
-#[derive(Serialize)]
-struct QueueItem {
-    id: u32,
-    change_id: String,
-    commit_id: String,
-    description: String,
+/// The item a runner is processing, from its state file.
+#[derive(Debug, Clone, Serialize)]
+pub struct CurrentItem {
+    pub id: u32,
+    pub candidate_change_id: String,
+    /// "creating_workspace", "checking" or "landing".
+    pub phase: state::Phase,
+    pub pid: u32,
+    /// Seconds since the runner started on this item.
+    pub elapsed_secs: u64,
+    /// Seconds since the check command started.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check_elapsed_secs: Option<u64>,
+    /// Estimated seconds until the check finishes, from recent check
+    /// durations.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta_secs: Option<u64>,
+    /// Last line the check command wrote to the run log.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_log_line: Option<String>,
 }
 
-#[derive(Serialize)]
-struct FailedItem {
-    id: u32,
-    candidate_change_id: String,
-    candidate_commit_id: String,
-    description: String,
-    trunk_commit_id: String,
-    workspace_path: String,
-    failure_reason: String,
+/// An item waiting in the queue.
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueItem {
+    pub id: u32,
+    pub change_id: String,
+    pub commit_id: String,
+    pub description: String,
+    /// Why the runner skips this item: "needs approval".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocked: Option<String>,
+    /// What approved the item, when approval is required.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub approved_by: Option<String>,
+    /// Strategy given with `push --strategy`, overriding the configured one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<String>,
+}
+
+/// An item that failed its merge attempt, read from its failed bookmark.
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedItem {
+    pub id: u32,
+    pub candidate_change_id: String,
+    pub candidate_commit_id: String,
+    pub description: String,
+    pub trunk_commit_id: String,
+    pub workspace_path: String,
+    pub failure_reason: String,
+    /// Resource limits in force when the check failed, e.g. "cpu=60".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limits: Option<String>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
-    conflict_paths: Vec<String>,
+    pub conflict_paths: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failing_tests: Vec<String>,
+    /// Total failing tests in the report; `failing_tests` holds at most
+    /// `report::MAX_FAILING_TESTS` of them.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub failing_test_count: usize,
+    /// Run log lines matching `failure_patterns`, with context.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failure_excerpt: Vec<String>,
+    /// Files collected by the `artifacts` setting, relative to `artifacts_dir`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifacts_dir: Option<String>,
 }
 
+/// Artifacts kept for an item that is no longer queued or failed.
 #[derive(Serialize)]
-struct LandedItem {
+struct ArtifactsItem {
     id: u32,
-    change_id: String,
-    description: String,
+    artifacts_dir: String,
+    artifacts: Vec<String>,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// An item that landed on trunk.
+#[derive(Debug, Clone, Serialize)]
+pub struct LandedItem {
+    pub id: u32,
+    pub change_id: String,
+    pub description: String,
 }
 
 /// Check if a workspace name belongs to jjq (and should be cleaned up by doctor/clean).
 /// Covers all workspace naming patterns: jjq-run-*, jjq-config-*, jjq-meta-*,
 /// jjq-check-*, jjq-hint-*, and bare "jjq{PID}" from init/next_id.
+/// Warm pool workspaces (jjq-pool-*) and the metadata workspace are
+/// long-lived and excluded.
 fn is_jjq_workspace(name: &str) -> bool {
+    if name.starts_with(pool::WORKSPACE_PREFIX) || name == metadata::WORKSPACE_NAME {
+        return false;
+    }
     name.starts_with("jjq-") || (name.starts_with("jjq") && name.len() > 3)
 }
 
@@ -70,13 +163,9 @@ fn preferr(msg: &str) {
 }
 
 /// Require jjq to be initialized, or error with instructions.
-fn require_initialized() -> Result<()> {
-    if !config::is_initialized()? {
-        return Err(ExitError::new(
-            exit_codes::USAGE,
-            "jjq is not initialized. Run 'jjq init' first.",
-        )
-        .into());
+pub fn require_initialized(jj: &Jj) -> Result<()> {
+    if !config::is_initialized(jj)? {
+        return Err(Error::NotInitialized.into());
     }
     Ok(())
 }
@@ -104,12 +193,39 @@ fn extract_trailers(description: &str) -> HashMap<String, String> {
     trailers
 }
 
+/// Extract every value of a jjq-* trailer that may appear more than once, in
+/// order. Leading whitespace in values is preserved.
+fn extract_trailer_values(description: &str, key: &str) -> Vec<String> {
+    let prefix = format!("jjq-{}:", key);
+    description
+        .lines()
+        .filter_map(|line| line.strip_prefix(&prefix))
+        .map(|value| {
+            value
+                .strip_prefix(' ')
+                .unwrap_or(value)
+                .trim_end()
+                .to_string()
+        })
+        .collect()
+}
+
 /// Look up the filesystem path of a workspace from jjq metadata log history.
-fn lookup_workspace_path(id: u32) -> Option<String> {
-    // Try reading workspace path from metadata file first
+/// The recorded path is absolute, so it stays valid if `workspace_dir` changes.
+fn lookup_workspace_path(jj: &Jj, id: u32) -> Option<String> {
+    // A failed item's commit records its workspace in the jjq-workspace trailer
+    let failed_rev = format!("bookmarks(exact:{})", queue::failed_bookmark(id));
+    if let Ok(desc) = jj.get_description(&failed_rev)
+        && let Some(path) = extract_trailers(&desc).remove("workspace")
+        && !path.is_empty()
+    {
+        return Some(path);
+    }
+
+    // Otherwise read the workspace path from the metadata file
     let padded = queue::format_seq_id(id);
     let file_path = format!("workspace/{}", padded);
-    if let Ok(path) = jj::file_show(&file_path, config::JJQ_BOOKMARK) {
+    if let Ok(path) = jj.file_show(&file_path, config::JJQ_BOOKMARK) {
         let path = path.trim().to_string();
         if !path.is_empty() {
             return Some(path);
@@ -117,7 +233,7 @@ fn lookup_workspace_path(id: u32) -> Option<String> {
     }
 
     // Fall back to searching commit descriptions
-    let output = jj::run_ok(&[
+    let output = jj.run_ok(&[
         "log",
         "-r",
         "ancestors(bookmarks(exact:\"jjq/_/_\"), 100)",
@@ -144,47 +260,36 @@ fn lookup_workspace_path(id: u32) -> Option<String> {
 }
 
 /// Record workspace path in metadata for later recovery by delete/clean.
-fn record_workspace_metadata(id: u32, workspace_path: &str) -> Result<()> {
-    let temp_dir = tempfile::TempDir::new()?;
-    let workspace_name = format!("jjq-meta-{}", std::process::id());
-
-    jj::workspace_add(
-        temp_dir.path().to_str().unwrap(),
-        &workspace_name,
-        &[config::JJQ_BOOKMARK],
-    )?;
-
-    let orig_dir = env::current_dir()?;
-    env::set_current_dir(temp_dir.path())?;
-
-    fs::create_dir_all("workspace")?;
-    fs::write(
-        format!("workspace/{}", queue::format_seq_id(id)),
-        workspace_path,
-    )?;
-    jj::describe(
-        "@",
+fn record_workspace_metadata(jj: &Jj, id: u32, workspace_path: &str) -> Result<()> {
+    metadata::write(
+        jj,
+        &[(
+            &format!("workspace/{}", queue::format_seq_id(id)),
+            workspace_path,
+        )],
         &format!("Sequence-Id: {}\nWorkspace: {}", id, workspace_path),
-    )?;
-    jj::run_quiet(&["bookmark", "set", config::JJQ_BOOKMARK])?;
-
-    env::set_current_dir(&orig_dir)?;
-    jj::workspace_forget(&workspace_name)?;
+    )
+}
 
-    Ok(())
+/// Parse a strategy name given on the command line.
+fn parse_strategy(strategy: &str) -> Result<Strategy> {
+    Strategy::try_from(strategy).map_err(|_| {
+        Error::usage(format!(
+            "invalid strategy: {}\nvalid values: {}",
+            strategy,
+            config::VALID_STRATEGIES.join(", ")
+        ))
+        .into()
+    })
 }
 
 /// Initialize jjq in this repository.
-pub fn init(trunk: Option<&str>, check: Option<&str>, strategy: &str) -> Result<()> {
+pub fn init(jj: &Jj, trunk: Option<&str>, check: Option<&str>, strategy: &str) -> Result<()> {
     use std::io::{self, IsTerminal};
 
     // Refuse if already initialized
-    if config::is_initialized()? {
-        return Err(ExitError::new(
-            exit_codes::USAGE,
-            "jjq is already initialized. Use 'jjq config' to change settings.",
-        )
-        .into());
+    if config::is_initialized(jj)? {
+        return Err(Error::AlreadyInitialized.into());
     }
 
     println!("Initializing jjq in this repository.");
@@ -194,11 +299,9 @@ pub fn init(trunk: Option<&str>, check: Option<&str>, strategy: &str) -> Result<
 
     // Non-interactive mode requires both flags
     if !is_tty && (trunk.is_none() || check.is_none()) {
-        return Err(ExitError::new(
-            exit_codes::USAGE,
-            "--trunk and --check are required in non-interactive mode.",
-        )
-        .into());
+        return Err(
+            Error::usage("--trunk and --check are required in non-interactive mode.").into(),
+        );
     }
 
     // Determine trunk bookmark
@@ -206,7 +309,7 @@ pub fn init(trunk: Option<&str>, check: Option<&str>, strategy: &str) -> Result<
         t.to_string()
     } else {
         // Auto-detect default from existing bookmarks
-        let bookmarks = jj::list_bookmarks().unwrap_or_default();
+        let bookmarks = jj.list_bookmarks().unwrap_or_default();
         let default = if bookmarks.iter().any(|b| b == "main") {
             Some("main")
         } else if bookmarks.iter().any(|b| b == "master") {
@@ -218,13 +321,11 @@ pub fn init(trunk: Option<&str>, check: Option<&str>, strategy: &str) -> Result<
     };
 
     // Verify trunk bookmark exists
-    if !jj::bookmark_exists(&trunk_value)? {
+    if !jj.bookmark_exists(&trunk_value)? {
         if !is_tty {
-            return Err(ExitError::new(
-                exit_codes::USAGE,
-                format!("trunk bookmark '{}' does not exist.", trunk_value),
-            )
-            .into());
+            return Err(
+                Error::usage(format!("trunk bookmark '{}' does not exist.", trunk_value)).into(),
+            );
         }
         // Interactive mode: offer to create it
         println!("Bookmark '{}' does not exist.", trunk_value);
@@ -240,14 +341,14 @@ pub fn init(trunk: Option<&str>, check: Option<&str>, strategy: &str) -> Result<
                 Some("A revset is required (e.g., '@-', 'main', a change ID)."),
             )?,
             _ => {
-                return Err(ExitError::new(
-                    exit_codes::USAGE,
-                    format!("trunk bookmark '{}' does not exist.", trunk_value),
-                )
+                return Err(Error::usage(format!(
+                    "trunk bookmark '{}' does not exist.",
+                    trunk_value
+                ))
                 .into());
             }
         };
-        jj::bookmark_create(&trunk_value, &rev)?;
+        jj.bookmark_create(&trunk_value, &rev)?;
         println!("Created bookmark '{}' at '{}'.", trunk_value, rev);
     }
 
@@ -262,27 +363,19 @@ pub fn init(trunk: Option<&str>, check: Option<&str>, strategy: &str) -> Result<
         )?
     };
 
-    // Initialize metadata branch
-    config::initialize()?;
-
-    // Set config values
-    config::set("trunk_bookmark", &trunk_value)?;
-    config::set("check_command", &check_value)?;
-
-    // Validate and set strategy
-    let strategy_val = config::Strategy::try_from(strategy).ok().ok_or_else(|| {
-        ExitError::new(
-            exit_codes::USAGE,
-            format!(
-                "invalid strategy: {}\nvalid values: rebase, merge",
-                strategy
-            ),
-        )
-    })?;
-    config::set("strategy", strategy_val.as_str())?;
+    // Initialize the metadata branch with the settings in one commit
+    let strategy_val = parse_strategy(strategy)?;
+    config::initialize(
+        jj,
+        &[
+            ("trunk_bookmark", &trunk_value),
+            ("check_command", &check_value),
+            ("strategy", strategy_val.as_str()),
+        ],
+    )?;
 
     // Configure jj to hide jjq metadata from jj log
-    setup_log_filter()?;
+    setup_log_filter(jj)?;
 
     println!();
     println!("Initialized jjq:");
@@ -293,7 +386,7 @@ pub fn init(trunk: Option<&str>, check: Option<&str>, strategy: &str) -> Result<
 
     // Run doctor
     println!("Running doctor...");
-    doctor()?;
+    doctor(jj)?;
 
     println!();
     println!("Ready to go! Queue revisions with 'jjq push <revset>'.");
@@ -303,10 +396,10 @@ pub fn init(trunk: Option<&str>, check: Option<&str>, strategy: &str) -> Result<
 
 /// Configure jj's revsets.log to exclude jjq metadata from `jj log`.
 /// Composes with any existing filter value.
-fn setup_log_filter() -> Result<()> {
+fn setup_log_filter(jj: &Jj) -> Result<()> {
     let exclude = format!("~ ::{}", config::JJQ_BOOKMARK);
 
-    let value = if let Ok(Some(current)) = jj::config_get("revsets.log") {
+    let value = if let Ok(Some(current)) = jj.config_get("revsets.log") {
         if current.contains(config::JJQ_BOOKMARK) {
             return Ok(());
         }
@@ -315,7 +408,7 @@ fn setup_log_filter() -> Result<()> {
         exclude
     };
 
-    jj::config_set_repo("revsets.log", &value)?;
+    jj.config_set_repo("revsets.log", &value)?;
     prefout("configured jj to hide jjq metadata from 'jj log'");
     Ok(())
 }
@@ -365,67 +458,102 @@ fn prompt_choice(label: &str, max: u32) -> Result<u32> {
     }
 }
 
-/// Push a revision onto the merge queue.
-pub fn push(revset: &str) -> Result<()> {
+/// Push a revision onto the merge queue, returning its sequence ID. With
+/// `wait`, waits up to that long for the sequence ID lock.
+pub fn push(
+    jj: &Jj,
+    revset: &str,
+    wait: Option<Duration>,
+    force: bool,
+    strategy: Option<&str>,
+) -> Result<u32> {
+    let strategy = strategy.map(parse_strategy).transpose()?;
+
     // Resolve both change ID and commit ID
-    let (change_id, commit_id) = jj::resolve_revset_full(revset)
-        .map_err(|e| ExitError::new(exit_codes::USAGE, e.to_string()))?;
+    let (change_id, commit_id) = jj.resolve_revset_full(revset)?;
 
     // Get trunk bookmark
-    let trunk_bookmark = config::get_trunk_bookmark()?;
+    let trunk_bookmark = config::get_trunk_bookmark(jj)?;
 
     // Verify trunk bookmark exists
-    if !jj::bookmark_exists(&trunk_bookmark)? {
-        return Err(ExitError::new(
-            exit_codes::USAGE,
-            format!("trunk bookmark '{}' not found", trunk_bookmark),
-        )
+    if !jj.bookmark_exists(&trunk_bookmark)? {
+        return Err(Error::TrunkMissing {
+            bookmark: trunk_bookmark,
+        }
         .into());
     }
 
+    // Every commit that would land must satisfy the description policy
+    let rules = crate::lint::Rules::from_config(jj)?;
+    if !force && !rules.is_empty() {
+        let landing = format!("bookmarks(exact:{})..({})", trunk_bookmark, revset);
+        let problems: Vec<String> = jj
+            .descriptions(&landing)?
+            .iter()
+            .flat_map(|(change_id, description)| {
+                rules
+                    .problems(description)
+                    .into_iter()
+                    .map(move |problem| format!("{}: {}", change_id, problem))
+            })
+            .collect();
+        if !problems.is_empty() {
+            for problem in &problems {
+                preferr(problem);
+            }
+            return Err(Error::DescriptionRejected {
+                revset: revset.to_string(),
+                problems,
+            }
+            .into());
+        }
+    }
+
     // Idempotent push: clean up existing queue/failed entries for this change
 
     // Scan queue bookmarks (one subprocess per bookmark for both IDs)
-    let queue_bookmarks = jj::bookmark_list_glob("jjq/queue/??????")?;
+    let queue_bookmarks = jj.bookmark_list_glob("jjq/queue/??????")?;
     for bookmark in &queue_bookmarks {
         let revset = format!("bookmarks(exact:{})", bookmark);
-        let (entry_change_id, entry_commit_id) = jj::resolve_revset_full(&revset)?;
+        let (entry_change_id, entry_commit_id) = jj.resolve_revset_full(&revset)?;
         if entry_commit_id == commit_id {
             let entry_id = extract_id_from_bookmark(bookmark);
             preferr(&format!("revision already queued at {}", entry_id));
-            return Err(ExitError::new(exit_codes::USAGE, "revision already queued").into());
+            return Err(Error::AlreadyQueued { id: entry_id }.into());
         }
         if entry_change_id == change_id {
             let entry_id = extract_id_from_bookmark(bookmark);
-            jj::bookmark_delete(bookmark)?;
+            jj.bookmark_delete(bookmark)?;
+            queue::forget_item(jj, entry_id)?;
             prefout(&format!("replacing queued entry {}", entry_id));
         }
     }
 
     // Scan failed bookmarks: extract candidate change ID from jjq-candidate trailer
-    let failed_bookmarks = jj::bookmark_list_glob("jjq/failed/??????")?;
+    let failed_bookmarks = jj.bookmark_list_glob("jjq/failed/??????")?;
     for bookmark in &failed_bookmarks {
-        let desc = jj::get_description(&format!("bookmarks(exact:{})", bookmark))?;
+        let desc = jj.get_description(&format!("bookmarks(exact:{})", bookmark))?;
         let trailers = extract_trailers(&desc);
         if let Some(candidate_change_id) = trailers.get("candidate")
             && *candidate_change_id == change_id
         {
             let entry_id = extract_id_from_bookmark(bookmark);
-            jj::bookmark_delete(bookmark)?;
+            jj.bookmark_delete(bookmark)?;
+            queue::forget_item(jj, entry_id)?;
             prefout(&format!("clearing failed entry {}", entry_id));
         }
     }
 
     // Pre-flight conflict check using headless merge commit.
     // Ensure the temporary commit is always abandoned, even if has_conflicts errors.
-    let conflict_check_id = jj::new_rev(&[&trunk_bookmark, revset])?;
-    let has_conflicts = match jj::has_conflicts(&conflict_check_id) {
+    let conflict_check_id = jj.new_rev(&[&trunk_bookmark, revset])?;
+    let has_conflicts = match jj.has_conflicts(&conflict_check_id) {
         Ok(v) => {
-            jj::abandon(&conflict_check_id)?;
+            jj.abandon(&conflict_check_id)?;
             v
         }
         Err(e) => {
-            let _ = jj::abandon(&conflict_check_id);
+            let _ = jj.abandon(&conflict_check_id);
             return Err(e);
         }
     };
@@ -439,58 +567,85 @@ pub fn push(revset: &str) -> Result<()> {
             "rebase onto {} and resolve conflicts before pushing",
             trunk_bookmark
         ));
-        return Err(ExitError::new(exit_codes::CONFLICT, "revision conflicts with trunk").into());
+        return Err(Error::Conflict {
+            revset: revset.to_string(),
+        }
+        .into());
     }
 
-    require_initialized()?;
+    require_initialized(jj)?;
 
-    let id = queue::next_id()?;
+    // The item's pusher and strategy are recorded before the bookmark exists
+    // so a runner never sees the item without them
+    let id = queue::next_id(jj, wait, &jj.user_name(), strategy)?;
     let bookmark = queue::queue_bookmark(id);
+    jj.bookmark_create(&bookmark, revset)?;
 
-    jj::bookmark_create(&bookmark, revset)?;
-
-    let repo_path = jj::repo_root()?;
+    let repo_path = jj.root();
     prefout(&format!(
         "revision '{}' queued at {} (trunk: {} in {})",
         revset, id, trunk_bookmark, repo_path.display()
     ));
+    if let Some(pause) = pause::get(jj)? {
+        prefout(&format!(
+            "queue is {}; it will land after 'jjq resume'",
+            pause
+        ));
+    }
 
     // Show one-time hint about configuring jj log
-    config::maybe_show_log_hint()?;
+    config::maybe_show_log_hint(jj)?;
 
-    Ok(())
+    Ok(id)
 }
 
 /// Process queue items.
-pub fn run(all: bool, stop_on_failure: bool) -> Result<()> {
-    require_initialized()?;
+pub fn run(
+    jj: &Jj,
+    all: bool,
+    stop_on_failure: bool,
+    wait: Option<Duration>,
+    ignore_schedule: bool,
+) -> Result<()> {
+    require_initialized(jj)?;
 
     if all {
-        run_all(stop_on_failure)
+        run_all(jj, stop_on_failure, wait, ignore_schedule)
     } else {
-        match run_one()? {
+        match run_one(jj, wait, ignore_schedule)? {
             RunResult::Success => Ok(()),
             RunResult::Empty => Ok(()),
             RunResult::Skipped => Ok(()),
-            RunResult::Failure(code, msg) => Err(ExitError::new(code, msg).into()),
+            RunResult::Failure(err) => Err(err.into()),
         }
     }
 }
 
-enum RunResult {
+/// Outcome of processing one queue item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunResult {
+    /// The item landed on trunk.
     Success,
+    /// The queue was empty.
     Empty,
+    /// The item had no changes against trunk and was dropped.
     Skipped,
-    Failure(i32, String),
+    /// The item was not landed.
+    Failure(Error),
 }
 
-fn run_all(stop_on_failure: bool) -> Result<()> {
+fn run_all(
+    jj: &Jj,
+    stop_on_failure: bool,
+    wait: Option<Duration>,
+    ignore_schedule: bool,
+) -> Result<()> {
     let mut merged_count = 0u32;
     let mut failed_count = 0u32;
     let mut skipped_count = 0u32;
 
     loop {
-        match run_one()? {
+        match run_one(jj, wait, ignore_schedule)? {
             RunResult::Success => {
                 merged_count += 1;
             }
@@ -500,15 +655,23 @@ fn run_all(stop_on_failure: bool) -> Result<()> {
             RunResult::Skipped => {
                 skipped_count += 1;
             }
-            RunResult::Failure(_code, msg) => {
-                if stop_on_failure {
+            RunResult::Failure(err) => {
+                // Neither a paused or closed queue nor a cancelled item left
+                // in the queue would get anywhere on the next iteration
+                let halted = matches!(
+                    err,
+                    Error::Paused(_)
+                        | Error::LandingClosed(_)
+                        | Error::Cancelled { requeued: true, .. }
+                );
+                if stop_on_failure || halted {
                     if merged_count > 0 {
                         prefout(&format!(
                             "processed {} item(s) before failure",
                             merged_count
                         ));
                     }
-                    return Err(ExitError::new(exit_codes::CONFLICT, msg).into());
+                    return Err(err.into());
                 }
                 failed_count += 1;
             }
@@ -521,13 +684,10 @@ fn run_all(stop_on_failure: bool) -> Result<()> {
                 "processed {} item(s), {} failed",
                 merged_count, failed_count
             ));
-            return Err(ExitError::new(
-                exit_codes::PARTIAL,
-                format!(
-                    "processed {} item(s), {} failed",
-                    merged_count, failed_count
-                ),
-            )
+            return Err(Error::Partial {
+                merged: merged_count,
+                failed: failed_count,
+            }
             .into());
         }
         if skipped_count > 0 {
@@ -542,138 +702,186 @@ fn run_all(stop_on_failure: bool) -> Result<()> {
     Ok(())
 }
 
-fn run_one() -> Result<RunResult> {
-    let id = match queue::next_item()? {
-        Some(id) => id,
-        None => {
-            prefout("queue is empty");
-            return Ok(RunResult::Empty);
-        }
+/// Why items can't land now: the queue is paused, or (unless
+/// `ignore_schedule`) the landing schedule is closed.
+fn landing_blocked(jj: &Jj, ignore_schedule: bool) -> Result<Option<Error>> {
+    if let Some(pause) = pause::get(jj)? {
+        return Ok(Some(Error::Paused(pause)));
+    }
+    if !ignore_schedule && let Some(closed) = Schedule::from_config(jj)?.closed_now() {
+        preferr("use 'jjq run --ignore-schedule' to land anyway");
+        return Ok(Some(Error::LandingClosed(closed)));
+    }
+    Ok(None)
+}
+
+/// Process the next queue item. jjq must be initialized. With `wait`, waits
+/// up to that long for the config and run locks instead of failing at once.
+/// With `ignore_schedule`, lands even if the landing schedule is closed.
+pub fn run_one(jj: &Jj, wait: Option<Duration>, ignore_schedule: bool) -> Result<RunResult> {
+    if let Some(err) = landing_blocked(jj, ignore_schedule)? {
+        return Ok(RunResult::Failure(err));
+    }
+
+    let policy = approval::Policy::from_config(jj)?;
+    let Some(id) = pick_item(jj, &policy)? else {
+        return Ok(RunResult::Empty);
     };
 
     // Acquire config lock to read settings
-    let config_lock = Lock::acquire_or_fail("config", "config lock unavailable")?;
-    let trunk_bookmark = config::get_trunk_bookmark()?;
-    let check_command = match config::get_check_command()? {
+    let config_lock = Lock::acquire_or_fail(jj, "config", wait)?;
+    let trunk_bookmark = config::get_trunk_bookmark(jj)?;
+    let check_command = match config::get_check_command(jj)? {
         Some(cmd) => cmd,
         None => {
             drop(config_lock);
-            preferr("check_command not configured (use 'jjq config check_command <cmd>')");
-            return Ok(RunResult::Failure(
-                exit_codes::CONFLICT,
-                "check_command not configured".to_string(),
-            ));
+            return Ok(RunResult::Failure(Error::CheckNotConfigured));
         }
     };
-    let strategy = config::get_strategy()?;
+    let default_strategy = config::get_strategy(jj)?;
     drop(config_lock);
 
-    prefout(&format!(
-        "processing queue item {} ({} strategy)",
-        id,
-        strategy.as_str()
-    ));
-
     // Acquire run lock
-    let run_lock = match Lock::acquire("run")? {
+    let mut run_lock = match Lock::acquire_maybe_wait(jj, "run", wait)? {
         Some(lock) => lock,
         None => {
-            preferr("queue runner lock already held");
-            return Ok(RunResult::Failure(
-                exit_codes::CONFLICT,
-                "run lock unavailable".to_string(),
-            ));
+            let holder = lock::holder(jj, "run")?;
+            match &holder {
+                Some(holder) => preferr(&format!("queue runner lock already held by {}", holder)),
+                None => preferr("queue runner lock already held"),
+            }
+            return Ok(RunResult::Failure(Error::RunnerBusy { holder }));
         }
     };
 
+    // The runner we waited for may have landed or failed the item, and the
+    // queue may have been paused or closed meanwhile
+    let id = match wait {
+        Some(_) => {
+            if let Some(err) = landing_blocked(jj, ignore_schedule)? {
+                return Ok(RunResult::Failure(err));
+            }
+            match pick_item(jj, &policy)? {
+                Some(id) => id,
+                None => return Ok(RunResult::Empty),
+            }
+        }
+        None => id,
+    };
+
+    run_lock.set_item(id)?;
+
+    // An item pushed with --strategy overrides the configured strategy
+    let strategy = queue::item_strategy(jj, id)?.unwrap_or(default_strategy);
+
+    prefout(&format!(
+        "processing queue item {} ({} strategy)",
+        id,
+        strategy.as_str()
+    ));
+
     // Record trunk commit ID
-    let trunk_commit_id = jj::get_commit_id(&format!("bookmarks(exact:{})", trunk_bookmark))?;
+    let trunk_commit_id = jj.get_commit_id(&format!("bookmarks(exact:{})", trunk_bookmark))?;
 
     // Capture candidate change ID before creating workspace
     let queue_bookmark = queue::queue_bookmark(id);
     let (candidate_change_id, candidate_commit_id) =
-        jj::resolve_revset_full(&format!("bookmarks(exact:{})", queue_bookmark))?;
+        jj.resolve_revset_full(&format!("bookmarks(exact:{})", queue_bookmark))?;
+    let mut progress = Progress::start(jj, run_lock, id, &candidate_change_id)?;
 
-    // Resolve log path before cd-ing into workspace so it stays in the main repo .jj
-    let log_path = crate::runlog::log_path()?;
+    // The log lives in the main repo's .jj, not the runner workspace
+    let log_path = crate::runlog::log_path(jj)?;
 
     // Capture original description for rebase success path (before queue bookmark is deleted)
-    let candidate_description =
-        jj::get_description(&format!("bookmarks(exact:{})", queue_bookmark)).unwrap_or_default();
+    let candidate_description = jj
+        .get_description(&format!("bookmarks(exact:{})", queue_bookmark))
+        .unwrap_or_default();
 
     // Create workspace — strategy determines how
-    let runner_workspace = TempDir::new()?;
     let run_name = format!("jjq-run-{}", queue::format_seq_id(id));
 
-    // For rebase strategy, track all duplicate IDs so we can abandon them all
-    let mut rebase_duplicate_ids: Vec<String> = Vec::new();
+    // For rebase and squash, track all duplicate IDs so we can abandon them all
+    let (runner_workspace, duplicate_ids) = open_candidate_workspace(
+        jj,
+        &run_name,
+        strategy,
+        &format!("bookmarks(exact:{})", trunk_bookmark),
+        &format!("bookmarks(exact:{})", queue_bookmark),
+    )?;
 
-    match strategy {
-        config::Strategy::Merge => {
-            jj::workspace_add(
-                runner_workspace.path().to_str().unwrap(),
-                &run_name,
-                &[
-                    &format!("bookmarks(exact:{})", trunk_bookmark),
-                    &format!("bookmarks(exact:{})", queue_bookmark),
-                ],
-            )?;
-        }
-        config::Strategy::Rebase => {
-            // Duplicate candidate onto trunk (creates rebased copy without touching original)
-            rebase_duplicate_ids = jj::duplicate_onto(
-                &format!("bookmarks(exact:{})", queue_bookmark),
-                &format!("bookmarks(exact:{})", trunk_bookmark),
-            )?;
-            // Create workspace on the tip duplicate (last in the list)
-            let duplicate_tip = rebase_duplicate_ids.last().unwrap();
-            jj::workspace_add(
-                runner_workspace.path().to_str().unwrap(),
-                &run_name,
-                &[duplicate_tip.as_str()],
-            )?;
-        }
+    // Record the workspace path in metadata for later recovery by delete/clean
+    if let Some(path) = runner_workspace.kept_path() {
+        record_workspace_metadata(jj, id, path.to_str().unwrap())?;
     }
 
-    // Record the workspace path in metadata for later recovery by delete/clean
-    record_workspace_metadata(id, runner_workspace.path().to_str().unwrap())?;
+    let workspace_rev = format!("{}@", runner_workspace.name());
+    let attempt = FailedAttempt {
+        id,
+        candidate_change_id: &candidate_change_id,
+        candidate_commit_id: &candidate_commit_id,
+        trunk_commit_id: &trunk_commit_id,
+        workspace_path: runner_workspace.kept_path().map(Path::to_path_buf),
+        strategy,
+    };
 
-    let orig_dir = env::current_dir()?;
-    env::set_current_dir(runner_workspace.path())?;
+    // Fast-forward lands the candidate unchanged, so it must already be
+    // based on trunk
+    if strategy == Strategy::FastForward
+        && !jj.is_ancestor(
+            &format!("bookmarks(exact:{})", trunk_bookmark),
+            &candidate_commit_id,
+        )?
+    {
+        jj.bookmark_delete(&queue_bookmark)?;
+        jj.bookmark_create(&queue::failed_bookmark(id), &workspace_rev)?;
+        jj::describe_in(
+            runner_workspace.path(),
+            &workspace_rev,
+            &attempt.description("not-fast-forward", &[]),
+        )?;
 
-    // For rebase strategy, edit the duplicate directly so check artifacts
-    // are snapshotted into it (workspace add created an empty commit on top)
-    if strategy == config::Strategy::Rebase {
-        let parent_rev = jj::resolve_revset(&format!("{}@-", run_name))?;
-        jj::edit(&parent_rev)?;
+        runner_workspace.keep();
+        drop(progress);
+
+        preferr(&format!(
+            "merge {} is not a fast-forward of {}, marked as failed",
+            id, trunk_bookmark
+        ));
+        preferr(&format!("  candidate: {}", candidate_change_id));
+        attempt.print_workspace_note();
+        preferr("");
+        preferr("To resolve:");
+        preferr(&format!(
+            "  jj rebase -r {} -d {}",
+            candidate_change_id, trunk_bookmark
+        ));
+        preferr(&format!("  # resolve conflicts in {}", candidate_change_id));
+        preferr(&format!("  jjq push {}", candidate_change_id));
+        return Ok(RunResult::Failure(Error::NotFastForward { id: Some(id) }));
     }
 
     // Check for conflicts
-    let workspace_rev = format!("{}@", run_name);
-    if jj::has_conflicts(&workspace_rev)? {
-        let conflicts = jj::conflict_paths(&workspace_rev)?;
-        jj::bookmark_delete(&queue_bookmark)?;
-        jj::bookmark_create(&queue::failed_bookmark(id), &workspace_rev)?;
-        jj::describe(
+    if jj.has_conflicts(&workspace_rev)? {
+        let conflicts = jj.conflict_paths(&workspace_rev)?;
+        let conflict_trailers: Vec<(&str, String)> = if conflicts.is_empty() {
+            Vec::new()
+        } else {
+            vec![("conflicts", conflicts.join(","))]
+        };
+        jj.bookmark_delete(&queue_bookmark)?;
+        jj.bookmark_create(&queue::failed_bookmark(id), &workspace_rev)?;
+        jj::describe_in(
+            runner_workspace.path(),
             &workspace_rev,
-            &failure_description(
-                id,
-                "conflicts",
-                &candidate_change_id,
-                &candidate_commit_id,
-                &trunk_commit_id,
-                runner_workspace.path(),
-                &strategy,
-                &conflicts,
-            ),
+            &attempt.description("conflicts", &conflict_trailers),
         )?;
 
-        env::set_current_dir(&orig_dir)?;
-        let _ = runner_workspace.keep();
-        drop(run_lock);
+        runner_workspace.keep();
+        drop(progress);
 
         preferr(&format!("merge {} has conflicts, marked as failed", id));
         preferr(&format!("  candidate: {}", candidate_change_id));
+        attempt.print_workspace_note();
         if !conflicts.is_empty() {
             preferr(&format!("  conflicting files: {}", conflicts.join(", ")));
         }
@@ -688,32 +896,25 @@ fn run_one() -> Result<RunResult> {
             candidate_change_id
         ));
         preferr(&format!("  jjq push {}", candidate_change_id));
-        return Ok(RunResult::Failure(
-            exit_codes::CONFLICT,
-            format!("merge {} has conflicts", id),
-        ));
+        return Ok(RunResult::Failure(Error::MergeConflict { id }));
     }
 
     // Check for empty commit (no changes vs trunk).
     // Compare the workspace tree against trunk: if they match, the candidate
     // adds nothing new and can be skipped.
-    let is_empty = jj::trees_match(
+    let is_empty = jj.trees_match(
         &format!("bookmarks(exact:{})", trunk_bookmark),
         &workspace_rev,
     )?;
     if is_empty {
-        jj::bookmark_delete(&queue_bookmark)?;
+        jj.bookmark_delete(&queue_bookmark)?;
+        queue::forget_item(jj, id)?;
 
-        // For rebase, abandon all duplicates we created
-        if strategy == config::Strategy::Rebase {
-            for dup_id in &rebase_duplicate_ids {
-                let _ = jj::abandon(dup_id);
-            }
-        }
+        // Abandon all duplicates we created (rebase and squash)
+        abandon_all(jj, &duplicate_ids);
 
-        env::set_current_dir(&orig_dir)?;
-        jj::workspace_forget(&run_name)?;
-        drop(run_lock);
+        runner_workspace.discard(jj)?;
+        drop(progress);
 
         preferr(&format!(
             "queue item {} is empty (no changes vs {}), skipping",
@@ -722,74 +923,188 @@ fn run_one() -> Result<RunResult> {
         return Ok(RunResult::Skipped);
     }
 
-    jj::describe(&workspace_rev, &format!("WIP: attempting merge {}", id))?;
+    jj::describe_in(
+        runner_workspace.path(),
+        &workspace_rev,
+        &format!("WIP: attempting merge {}", id),
+    )?;
+
+    // Run check command
+    progress.set_phase(Phase::Checking)?;
+    let check_started = Instant::now();
+    let check_env = CheckEnv::from_config(
+        jj,
+        runner_workspace.path(),
+        &[
+            ("JJQ_ID", id.to_string()),
+            ("JJQ_TRUNK", trunk_bookmark.clone()),
+            ("JJQ_STRATEGY", strategy.as_str().to_string()),
+        ],
+    )?;
+    remove_stale_report(jj, runner_workspace.path())?;
+    let check_status = crate::runner::run_check_command(
+        &check_command,
+        &check_env,
+        &LogOptions::from_config(jj)?,
+        &log_path,
+        &|| state::cancel_request(jj, id).is_some(),
+    )?;
+
+    // A cancel only applies while the check runs; one that arrives after
+    // the check finished is ignored
+    if check_status.cancelled {
+        if state::cancel_request(jj, id).is_some_and(|request| request.requeue) {
+            // Leave the queue bookmark in place; nothing made for the run
+            // is kept, including the workspace commit
+            abandon_all(jj, &duplicate_ids);
+            runner_workspace.discard(jj)?;
+            drop(progress);
+
+            preferr(&format!("merge {} cancelled, left in queue", id));
+            return Ok(RunResult::Failure(Error::Cancelled { id, requeued: true }));
+        }
 
-    // Run check command (log_path resolved before cd to workspace)
-    let check_status = crate::runner::run_check_command(&check_command, &log_path)?;
+        // The cancelled check's workspace isn't worth inspecting, so it is
+        // discarded along with the run's commits. An empty commit on trunk
+        // records the attempt as the failed commit.
+        abandon_all(jj, &duplicate_ids);
+        runner_workspace.discard(jj)?;
+        let attempt = FailedAttempt {
+            workspace_path: None,
+            ..attempt
+        };
+        let failed_rev = jj.new_rev(&[&trunk_commit_id])?;
+        jj.describe(&failed_rev, &attempt.description("cancelled", &[]))?;
+        jj.bookmark_delete(&queue_bookmark)?;
+        jj.bookmark_create(&queue::failed_bookmark(id), &failed_rev)?;
+        drop(progress);
+
+        preferr(&format!("merge {} cancelled, marked as failed", id));
+        preferr(&format!("  candidate: {}", candidate_change_id));
+        return Ok(RunResult::Failure(Error::Cancelled {
+            id,
+            requeued: false,
+        }));
+    }
+    let check_duration = check_started.elapsed();
+    state::record_check_duration(jj, check_duration)?;
+
+    // Keep build outputs from the workspace before it is cleaned up. This
+    // is best-effort: a copy error mustn't keep the item from landing or
+    // being marked failed.
+    let artifact_patterns = config::get_list(jj, "artifacts")?;
+    if !artifact_patterns.is_empty() {
+        let artifacts_dir = crate::artifacts::dir(jj, id)?;
+        match crate::artifacts::collect(&artifacts_dir, runner_workspace.path(), &artifact_patterns)
+        {
+            Ok(copied) if !copied.is_empty() => prefout(&format!(
+                "saved {} artifact(s) to {}",
+                copied.len(),
+                artifacts_dir.display()
+            )),
+            Ok(_) => {}
+            Err(e) => preferr(&format!("warning: could not collect artifacts: {:#}", e)),
+        }
+    }
 
     if !check_status.success() {
         // Print log output (skipping sentinel lines)
-        if let Ok(log_contents) = fs::read_to_string(&log_path) {
-            for line in log_contents.lines() {
-                if !line.starts_with(crate::runlog::SENTINEL_PREFIX) {
-                    eprintln!("{}", line);
-                }
+        let log_contents = fs::read_to_string(&log_path).unwrap_or_default();
+        for line in log_contents.lines() {
+            if !line.starts_with(crate::runlog::SENTINEL_PREFIX) {
+                eprintln!("{}", line);
             }
         }
 
-        jj::bookmark_delete(&queue_bookmark)?;
-        jj::bookmark_create(&queue::failed_bookmark(id), &workspace_rev)?;
-        jj::describe(
+        // Running into a resource limit is recorded as its own failure reason
+        let limit_violation = check_env.limits.violation(&check_status, &log_contents);
+
+        // Failing test names from the check's test report, if configured
+        let failing_tests = read_failing_tests(jj, runner_workspace.path())?;
+        let mut report_trailers: Vec<(&str, String)> = failing_tests
+            .iter()
+            .take(crate::report::MAX_FAILING_TESTS)
+            .map(|name| ("failing-test", name.clone()))
+            .collect();
+        if !failing_tests.is_empty() {
+            report_trailers.push(("failing-test-count", failing_tests.len().to_string()));
+        }
+
+        // Log lines matching the configured failure patterns
+        let failure_patterns = config::get_failure_patterns(jj)?;
+        report_trailers.extend(
+            crate::runlog::failure_excerpt(&log_contents, &failure_patterns)
+                .into_iter()
+                .map(|line| ("excerpt", line)),
+        );
+        // The limits in force, for failures they caused but that weren't
+        // recognized as such
+        if let Some(limits) = check_env.limits.describe() {
+            report_trailers.push(("limits", limits));
+        }
+
+        jj.bookmark_delete(&queue_bookmark)?;
+        jj.bookmark_create(&queue::failed_bookmark(id), &workspace_rev)?;
+        jj::describe_in(
+            runner_workspace.path(),
             &workspace_rev,
-            &failure_description(
-                id,
-                "check",
-                &candidate_change_id,
-                &candidate_commit_id,
-                &trunk_commit_id,
-                runner_workspace.path(),
-                &strategy,
-                &[],
-            ),
+            &attempt.description(limit_violation.unwrap_or("check"), &report_trailers),
         )?;
 
-        env::set_current_dir(&orig_dir)?;
-        let _ = runner_workspace.keep();
-        drop(run_lock);
+        runner_workspace.keep();
+        drop(progress);
 
-        preferr(&format!("merge {} failed check, marked as failed", id));
+        match limit_violation {
+            Some(reason) => preferr(&format!(
+                "merge {} failed check ({}), marked as failed",
+                id, reason
+            )),
+            None => preferr(&format!("merge {} failed check, marked as failed", id)),
+        }
         preferr(&format!("  candidate: {}", candidate_change_id));
+        attempt.print_workspace_note();
+        if !failing_tests.is_empty() {
+            preferr(&format!("  failing tests: {}", failing_tests.join(", ")));
+        }
         preferr("");
         preferr("To resolve:");
         preferr(&format!("  # fix the issue in {}", candidate_change_id));
         preferr(&format!("  jjq push {}", candidate_change_id));
-        return Ok(RunResult::Failure(
-            exit_codes::CONFLICT,
-            format!("merge {} check failed", id),
-        ));
+        return Ok(RunResult::Failure(Error::CheckFailed {
+            id: Some(id),
+            reason: limit_violation.unwrap_or("check").to_string(),
+        }));
     }
 
     // Verify trunk hasn't moved
     let current_trunk_commit_id =
-        jj::get_commit_id(&format!("bookmarks(exact:{})", trunk_bookmark))?;
+        jj.get_commit_id(&format!("bookmarks(exact:{})", trunk_bookmark))?;
     if trunk_commit_id != current_trunk_commit_id {
-        // For rebase, abandon all duplicates we created
-        if strategy == config::Strategy::Rebase {
-            for dup_id in &rebase_duplicate_ids {
-                let _ = jj::abandon(dup_id);
-            }
-        }
-        env::set_current_dir(&orig_dir)?;
-        jj::workspace_forget(&run_name)?;
-        drop(run_lock);
+        // Abandon all duplicates we created (rebase and squash)
+        abandon_all(jj, &duplicate_ids);
+        runner_workspace.discard(jj)?;
+        drop(progress);
 
         preferr("trunk bookmark moved during run; queue item left in place, re-run to retry");
-        return Ok(RunResult::Failure(
-            exit_codes::CONFLICT,
-            "trunk moved during run".to_string(),
-        ));
+        return Ok(RunResult::Failure(Error::TrunkMoved));
     }
 
+    progress.set_phase(Phase::Landing)?;
+
+    let landed_message = config::get_landed_message(jj)?;
+    let landed_trailers = config::get_landed_trailers(jj)?;
+    let trunk_before = jj.resolve_revset(&trunk_commit_id)?;
+    let pusher = queue::item_pusher(jj, id)?.unwrap_or_default();
+    let landing = |trunk_after| Landed {
+        id,
+        strategy,
+        description: &candidate_description,
+        check_duration,
+        trunk_before: &trunk_before,
+        trunk_after,
+        pusher: &pusher,
+    };
+
     // Success path — order matters for crash safety:
     // 1. Move trunk first (most critical)
     // 2. Delete queue bookmark
@@ -797,189 +1112,630 @@ fn run_one() -> Result<RunResult> {
     // For rebase: we tested against a duplicate, now rebase the original to
     // preserve change ID, then move trunk to the rebased original.
 
-    match strategy {
+    let landed_change_id = match strategy {
         config::Strategy::Merge => {
-            let landed_change_id = jj::resolve_revset("@")?;
-            jj::bookmark_move(&trunk_bookmark, &trunk_commit_id, "@")?;
-            jj::bookmark_delete(&queue_bookmark)?;
-            jj::describe("@", &format!("Success: merge {}", id))?;
+            let landed_change_id = jj.resolve_revset(&workspace_rev)?;
+            jj.bookmark_move(&trunk_bookmark, &trunk_commit_id, &workspace_rev)?;
+            jj.bookmark_delete(&queue_bookmark)?;
+            // Describe from the runner workspace so files the check wrote
+            // are snapshotted into the landed commit as before
+            jj::describe_in(
+                runner_workspace.path(),
+                &workspace_rev,
+                &landing(&landed_change_id).description(landed_message.as_deref(), landed_trailers),
+            )?;
 
-            env::set_current_dir(&orig_dir)?;
-            jj::workspace_forget(&run_name)?;
-            drop(run_lock);
+            runner_workspace.release(jj)?;
+            drop(progress);
 
             prefout(&format!(
                 "merged {} to {} (now at {})",
                 id, trunk_bookmark, landed_change_id
             ));
+            landed_change_id
         }
         config::Strategy::Rebase => {
             // The duplicate passed checks. Now rebase the ORIGINAL candidate
             // onto trunk to preserve its change ID.
 
-            // Return to original directory for rebase operations
-            env::set_current_dir(&orig_dir)?;
-
             // Rebase original candidate (and its descendants) onto trunk
-            jj::rebase_branch_onto(
+            jj.rebase_branch_onto(
                 &candidate_change_id,
                 &format!("bookmarks(exact:{})", trunk_bookmark),
             )?;
 
             // Move trunk to the rebased original (not the duplicate)
             // The candidate_change_id is now rebased onto trunk
-            jj::bookmark_move(&trunk_bookmark, &trunk_commit_id, &candidate_change_id)?;
-            jj::bookmark_delete(&queue_bookmark)?;
+            jj.bookmark_move(&trunk_bookmark, &trunk_commit_id, &candidate_change_id)?;
+            jj.bookmark_delete(&queue_bookmark)?;
 
-            // Describe the landed commit with trailers
-            let desc = format!(
-                "{}\n\njjq-sequence: {}\njjq-strategy: rebase",
-                candidate_description.trim(),
-                id,
-            );
-            jj::describe(&candidate_change_id, &desc)?;
+            // Describe the landed commit, with trailers unless disabled
+            jj.describe(
+                &candidate_change_id,
+                &landing(&candidate_change_id)
+                    .description(landed_message.as_deref(), landed_trailers),
+            )?;
 
             // Abandon all duplicates (they were only used for testing)
-            for dup_id in &rebase_duplicate_ids {
-                jj::abandon(dup_id)?;
+            for dup_id in &duplicate_ids {
+                jj.abandon(dup_id)?;
             }
 
-            jj::workspace_forget(&run_name)?;
-            drop(run_lock);
+            runner_workspace.release(jj)?;
+            drop(progress);
 
             prefout(&format!(
                 "rebased {} to {} (now at {})",
                 id, trunk_bookmark, candidate_change_id
             ));
+            candidate_change_id
+        }
+        config::Strategy::Squash => {
+            // The workspace commit already holds the whole chain's changes
+            // on trunk; the originals are left alone unless configured
+            let landed_change_id = jj.resolve_revset(&workspace_rev)?;
+            let mut originals = jj.descriptions(&format!(
+                "bookmarks(exact:{})..{}",
+                trunk_bookmark, candidate_change_id
+            ))?;
+            originals.reverse();
+
+            jj.bookmark_move(&trunk_bookmark, &trunk_commit_id, &workspace_rev)?;
+            jj.bookmark_delete(&queue_bookmark)?;
+            jj::describe_in(
+                runner_workspace.path(),
+                &workspace_rev,
+                &squash_description(
+                    &config::get_squash_message(jj)?,
+                    &originals,
+                    &landing(&landed_change_id),
+                    landed_trailers,
+                ),
+            )?;
+
+            if config::get_squash_abandon_originals(jj)? {
+                for (change_id, _) in &originals {
+                    jj.abandon(change_id)?;
+                }
+            }
+
+            runner_workspace.release(jj)?;
+            drop(progress);
+
+            prefout(&format!(
+                "squashed {} to {} (now at {})",
+                id, trunk_bookmark, landed_change_id
+            ));
+            landed_change_id
+        }
+        config::Strategy::FastForward => {
+            // The check ran in a child of the candidate; trunk moves to the
+            // candidate itself, which is left as it was pushed
+            jj.bookmark_move(&trunk_bookmark, &trunk_commit_id, &candidate_commit_id)?;
+            jj.bookmark_delete(&queue_bookmark)?;
+
+            runner_workspace.discard(jj)?;
+            drop(progress);
+
+            prefout(&format!(
+                "fast-forwarded {} to {} (now at {})",
+                id, trunk_bookmark, candidate_change_id
+            ));
+            candidate_change_id
         }
+    };
+
+    // Status finds landed items by their trailers; without them, or for a
+    // fast-forward that keeps the pushed description, keep a record instead
+    if !landed_trailers || strategy == config::Strategy::FastForward {
+        landed::record(jj, &landed_change_id, id)?;
     }
 
+    // A `jjq approve` record is no use once its commit has landed
+    approval::remove(jj, &candidate_commit_id)?;
+    queue::forget_item(jj, id)?;
+
     Ok(RunResult::Success)
 }
 
-fn failure_description(
-    id: u32,
-    reason: &str,
-    candidate_change_id: &str,
-    candidate_commit_id: &str,
-    trunk_commit_id: &str,
-    workspace_path: &std::path::Path,
-    strategy: &Strategy,
-    conflict_paths: &[String],
+/// The workspace a check runs in: a fresh temporary workspace, or a warm
+/// pool slot that keeps ignored build outputs between runs.
+enum RunnerWorkspace {
+    Temp { dir: TempDir, name: String },
+    Pool(pool::Slot),
+}
+
+impl RunnerWorkspace {
+    /// Create a workspace whose working-copy commit is a new commit on
+    /// `parents`, using a free pool slot if the pool is enabled. `name` is
+    /// used for a temporary workspace.
+    fn create(jj: &Jj, name: &str, parents: &[&str]) -> Result<RunnerWorkspace> {
+        if let Some(slot) = pool::claim(jj)? {
+            slot.checkout(jj, parents)?;
+            return Ok(RunnerWorkspace::Pool(slot));
+        }
+        let dir = config::workspace_tempdir(jj, name)?;
+        jj.workspace_add(dir.path().to_str().unwrap(), name, parents)?;
+        Ok(RunnerWorkspace::Temp {
+            dir,
+            name: name.to_string(),
+        })
+    }
+
+    fn path(&self) -> &Path {
+        match self {
+            RunnerWorkspace::Temp { dir, .. } => dir.path(),
+            RunnerWorkspace::Pool(slot) => &slot.path,
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            RunnerWorkspace::Temp { name, .. } => name,
+            RunnerWorkspace::Pool(slot) => &slot.name,
+        }
+    }
+
+    /// Where a failed run's workspace stays for inspection. None for a pool
+    /// slot, which the next run reuses.
+    fn kept_path(&self) -> Option<&Path> {
+        match self {
+            RunnerWorkspace::Temp { dir, .. } => Some(dir.path()),
+            RunnerWorkspace::Pool(_) => None,
+        }
+    }
+
+    /// Leave a failed run's workspace on disk for inspection. A pool slot is
+    /// released and will be reused by the next run.
+    fn keep(self) {
+        if let RunnerWorkspace::Temp { dir, .. } = self {
+            let _ = dir.keep();
+        }
+    }
+
+    /// Finish with a workspace whose working-copy commit has landed.
+    fn release(self, jj: &Jj) -> Result<()> {
+        match self {
+            RunnerWorkspace::Temp { name, .. } => jj.workspace_forget(&name),
+            RunnerWorkspace::Pool(_) => Ok(()),
+        }
+    }
+
+    /// Finish with a workspace whose working-copy commit is not needed. The
+    /// commit is abandoned so it doesn't linger once the workspace is
+    /// forgotten or the pool slot moves on.
+    fn discard(self, jj: &Jj) -> Result<()> {
+        match self {
+            RunnerWorkspace::Temp { name, .. } => {
+                let working_copy = jj.resolve_revset(&format!("{}@", name))?;
+                jj.workspace_forget(&name)?;
+                jj.abandon(&working_copy)
+            }
+            RunnerWorkspace::Pool(slot) => jj.abandon(&format!("{}@", slot.name)),
+        }
+    }
+}
+
+/// Create a workspace containing `candidate_rev` combined with `trunk_rev`
+/// the way the given strategy would land it: a two-parent merge for merge, a
+/// duplicate of the candidate chain onto trunk for rebase, one new commit on
+/// trunk with the chain's combined changes for squash, or the candidate as it
+/// is for fast-forward. Returns the
+/// workspace and the change IDs of any duplicates created (last is the tip),
+/// which the caller is responsible for abandoning.
+fn open_candidate_workspace(
+    jj: &Jj,
+    name: &str,
+    strategy: Strategy,
+    trunk_rev: &str,
+    candidate_rev: &str,
+) -> Result<(RunnerWorkspace, Vec<String>)> {
+    match strategy {
+        Strategy::Merge => {
+            let workspace = RunnerWorkspace::create(jj, name, &[trunk_rev, candidate_rev])?;
+            Ok((workspace, Vec::new()))
+        }
+        Strategy::Rebase => {
+            // Duplicate candidate onto trunk (creates rebased copy without touching original)
+            let duplicate_ids = jj.duplicate_onto(candidate_rev, trunk_rev)?;
+            // Create workspace on the tip duplicate (last in the list)
+            let duplicate_tip = duplicate_ids.last().unwrap();
+            let workspace = RunnerWorkspace::create(jj, name, &[duplicate_tip.as_str()])?;
+            // Edit the duplicate directly so check artifacts are snapshotted
+            // into it (creating the workspace put an empty commit on top)
+            jj::edit_in(workspace.path(), duplicate_tip)?;
+            Ok((workspace, duplicate_ids))
+        }
+        Strategy::Squash => {
+            // The rebased chain's tip has the combined changes (and any
+            // conflicts); copy its tree into a single commit on trunk
+            let duplicate_ids = jj.duplicate_onto(candidate_rev, trunk_rev)?;
+            let duplicate_tip = duplicate_ids.last().unwrap();
+            let workspace = RunnerWorkspace::create(jj, name, &[trunk_rev])?;
+            let restored = jj::restore_from_in(workspace.path(), duplicate_tip);
+            abandon_all(jj, &duplicate_ids);
+            restored?;
+            Ok((workspace, Vec::new()))
+        }
+        Strategy::FastForward => {
+            // Whether the candidate descends from trunk is the caller's check
+            let workspace = RunnerWorkspace::create(jj, name, &[candidate_rev])?;
+            Ok((workspace, Vec::new()))
+        }
+    }
+}
+
+/// Abandon revisions created for a run, ignoring failures.
+fn abandon_all(jj: &Jj, change_ids: &[String]) {
+    for change_id in change_ids {
+        let _ = jj.abandon(change_id);
+    }
+}
+
+/// Description of a squash-landed commit: the `squash_message` template
+/// filled from `originals` (change ID and description, oldest first) and
+/// `landed`, followed, unless `trailers` is off, by jjq trailers recording
+/// the original change IDs.
+fn squash_description(
+    template: &str,
+    originals: &[(String, String)],
+    landed: &Landed,
+    trailers: bool,
 ) -> String {
-    let mut desc = format!(
-        "Failed: merge {} ({})\n\njjq-candidate: {}\njjq-candidate-commit: {}\njjq-trunk: {}\njjq-workspace: {}\njjq-failure: {}\njjq-strategy: {}",
-        id,
-        reason,
-        candidate_change_id,
-        candidate_commit_id,
-        trunk_commit_id,
-        workspace_path.display(),
-        reason,
-        strategy.as_str()
+    let descriptions: Vec<&str> = originals
+        .iter()
+        .map(|(_, desc)| desc.trim())
+        .filter(|desc| !desc.is_empty())
+        .collect();
+    let titles: Vec<String> = descriptions
+        .iter()
+        .map(|desc| format!("- {}", desc.lines().next().unwrap_or("")))
+        .collect();
+    let message = landed.fill_with(
+        template,
+        &[
+            ("titles", &titles.join("\n")),
+            ("descriptions", &descriptions.join("\n\n")),
+        ],
     );
-    if !conflict_paths.is_empty() {
-        desc.push_str(&format!("\njjq-conflicts: {}", conflict_paths.join(",")));
+    if !trailers {
+        return message.trim().to_string();
+    }
+    let mut desc = format!("{}\n\n{}", message.trim(), landed.trailers());
+    for (change_id, _) in originals {
+        desc.push_str(&format!("\njjq-squashed: {}", change_id));
     }
     desc
 }
 
+/// Remove the test report a previous check left in the workspace, so a pool
+/// slot can't supply another item's results.
+fn remove_stale_report(jj: &Jj, workspace: &Path) -> Result<()> {
+    let Some(report) = config::get_check_report(jj)? else {
+        return Ok(());
+    };
+    let path = workspace.join(&report);
+    if path.exists() {
+        fs::remove_file(&path)?;
+    }
+    Ok(())
+}
+
+/// Read failing test names from the configured check report in `workspace`.
+/// A missing report yields no names; an unparseable one is reported as a
+/// warning rather than failing the run.
+fn read_failing_tests(jj: &Jj, workspace: &Path) -> Result<Vec<String>> {
+    let Some(report) = config::get_check_report(jj)? else {
+        return Ok(Vec::new());
+    };
+    let path = workspace.join(&report);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    match crate::report::failing_tests(&path) {
+        Ok(names) => Ok(names),
+        Err(e) => {
+            preferr(&format!("could not parse test report {}: {:#}", report, e));
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// What a failed item's commit records about the attempt.
+struct FailedAttempt<'a> {
+    id: u32,
+    candidate_change_id: &'a str,
+    candidate_commit_id: &'a str,
+    trunk_commit_id: &'a str,
+    /// None when the run used a pool slot, which isn't kept.
+    workspace_path: Option<PathBuf>,
+    strategy: Strategy,
+}
+
+impl FailedAttempt<'_> {
+    /// Build the description for the failed commit. `trailers` are extra
+    /// `jjq-<key>: <value>` lines appended after the standard ones; a key
+    /// may repeat.
+    fn description(&self, reason: &str, trailers: &[(&str, String)]) -> String {
+        let mut desc = format!(
+            "Failed: merge {} ({})\n\njjq-candidate: {}\njjq-candidate-commit: {}\njjq-trunk: {}\njjq-workspace: {}\njjq-failure: {}\njjq-strategy: {}",
+            self.id,
+            reason,
+            self.candidate_change_id,
+            self.candidate_commit_id,
+            self.trunk_commit_id,
+            self.workspace_path
+                .as_deref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default(),
+            reason,
+            self.strategy.as_str()
+        );
+        for (key, value) in trailers {
+            desc.push_str(&format!("\njjq-{}: {}", key, value));
+        }
+        desc
+    }
+
+    /// Tell the user when the failed run's workspace wasn't kept.
+    fn print_workspace_note(&self) {
+        if self.workspace_path.is_none() {
+            preferr("  workspace: not kept (pool slot is reused by the next run)");
+        }
+    }
+}
+
 /// Run check command against a revision in a temporary workspace.
-pub fn check(revset: &str, verbose: bool) -> Result<()> {
+///
+/// Unless `isolated` is set, the revision is combined with trunk exactly as
+/// `run` would combine it for the configured strategy, including conflict and
+/// empty-change detection. No queue bookmarks are touched either way.
+pub fn check(jj: &Jj, revset: &str, verbose: bool, isolated: bool) -> Result<()> {
     // Resolve the revision
-    let change_id =
-        jj::resolve_revset(revset).map_err(|e| ExitError::new(exit_codes::USAGE, e.to_string()))?;
+    let change_id = jj.resolve_revset(revset)?;
 
     // Read check command
-    let check_command = match config::get_check_command()? {
+    let check_command = match config::get_check_command(jj)? {
         Some(cmd) => cmd,
         None => {
-            return Err(ExitError::new(
-                exit_codes::USAGE,
-                "check_command not configured (use 'jjq config check_command <cmd>')",
-            )
+            return Err(Error::CheckNotConfigured.into());
+        }
+    };
+
+    let log_path = crate::runlog::log_path(jj)?;
+
+    // Create temporary (or pooled) workspace
+    let workspace_name = format!("jjq-check-{}", std::process::id());
+
+    // Duplicates created for the rebase strategy, abandoned on cleanup
+    let mut duplicate_ids: Vec<String> = Vec::new();
+    let mut trunk = None;
+
+    let workspace = if isolated {
+        prefout(&format!(
+            "checking revision {} with: {}",
+            change_id, check_command
+        ));
+        RunnerWorkspace::create(jj, &workspace_name, &[revset])?
+    } else {
+        let trunk_bookmark = config::get_trunk_bookmark(jj)?;
+        if !jj.bookmark_exists(&trunk_bookmark)? {
+            return Err(Error::TrunkMissing {
+                bookmark: trunk_bookmark,
+            }
             .into());
         }
+        let strategy = config::get_strategy(jj)?;
+        prefout(&format!(
+            "checking revision {} against {} ({} strategy) with: {}",
+            change_id,
+            trunk_bookmark,
+            strategy.as_str(),
+            check_command
+        ));
+        let (workspace, ids) = open_candidate_workspace(
+            jj,
+            &workspace_name,
+            strategy,
+            &format!("bookmarks(exact:{})", trunk_bookmark),
+            revset,
+        )?;
+        duplicate_ids = ids;
+        trunk = Some((trunk_bookmark, strategy));
+        workspace
     };
+    let workspace_path = workspace.path().to_path_buf();
 
-    prefout(&format!(
-        "checking revision {} with: {}",
-        change_id, check_command
-    ));
+    let cleanup = |workspace: RunnerWorkspace, duplicate_ids: &[String]| -> Result<()> {
+        workspace.discard(jj)?;
+        for dup_id in duplicate_ids {
+            let _ = jj.abandon(dup_id);
+        }
+        Ok(())
+    };
 
-    // Resolve log path before changing to workspace directory.
-    let log_path = crate::runlog::log_path()?;
+    if let Some((trunk_bookmark, strategy)) = &trunk {
+        let workspace_rev = format!("{}@", workspace.name());
+        if *strategy == Strategy::FastForward
+            && !jj.is_ancestor(&format!("bookmarks(exact:{})", trunk_bookmark), revset)?
+        {
+            cleanup(workspace, &duplicate_ids)?;
 
-    // Create temporary workspace
-    let workspace_dir = TempDir::new()?;
-    let workspace_name = format!("jjq-check-{}", std::process::id());
+            preferr(&format!(
+                "revision {} is not a fast-forward of {}",
+                change_id, trunk_bookmark
+            ));
+            preferr("");
+            preferr("To resolve:");
+            preferr(&format!(
+                "  jj rebase -r {} -d {}",
+                change_id, trunk_bookmark
+            ));
+            preferr(&format!("  # resolve conflicts in {}", change_id));
+            return Err(Error::NotFastForward { id: None }.into());
+        }
 
-    jj::workspace_add(
-        workspace_dir.path().to_str().unwrap(),
-        &workspace_name,
-        &[revset],
-    )?;
+        if jj.has_conflicts(&workspace_rev)? {
+            let conflicts = jj.conflict_paths(&workspace_rev)?;
+            cleanup(workspace, &duplicate_ids)?;
 
-    let orig_dir = env::current_dir()?;
-    env::set_current_dir(workspace_dir.path())?;
+            preferr(&format!(
+                "revision {} conflicts with {}",
+                change_id, trunk_bookmark
+            ));
+            if !conflicts.is_empty() {
+                preferr(&format!("  conflicting files: {}", conflicts.join(", ")));
+            }
+            preferr("");
+            preferr("To resolve:");
+            preferr(&format!(
+                "  jj rebase -r {} -d {}",
+                change_id, trunk_bookmark
+            ));
+            preferr(&format!("  # resolve conflicts in {}", change_id));
+            return Err(Error::CheckFailed {
+                id: None,
+                reason: "conflicts".to_string(),
+            }
+            .into());
+        }
+
+        if jj.trees_match(
+            &format!("bookmarks(exact:{})", trunk_bookmark),
+            &workspace_rev,
+        )? {
+            cleanup(workspace, &duplicate_ids)?;
+            prefout(&format!(
+                "revision {} is empty (no changes vs {}), run would skip it",
+                change_id, trunk_bookmark
+            ));
+            return Ok(());
+        }
+    }
+
+    let mut jjq_vars = vec![("JJQ_TRUNK", config::get_trunk_bookmark(jj)?)];
+    if let Some((_, strategy)) = &trunk {
+        jjq_vars.push(("JJQ_STRATEGY", strategy.as_str().to_string()));
+    }
+    let check_env = match CheckEnv::from_config(jj, &workspace_path, &jjq_vars) {
+        Ok(check_env) => check_env,
+        Err(e) => {
+            cleanup(workspace, &duplicate_ids)?;
+            return Err(e);
+        }
+    };
 
     if verbose {
-        prefout(&format!("workspace: {}", workspace_dir.path().display()));
-        prefout("shell: /bin/sh");
+        prefout(&format!("workspace: {}", workspace_path.display()));
+        prefout(&format!("directory: {}", check_env.dir.display()));
+        prefout(&format!("shell: {}", check_env.shell));
         prefout("env:");
-        let mut vars: Vec<(String, String)> = env::vars().collect();
-        vars.sort();
-        for (key, value) in &vars {
-            prefout(&format!("  {}={}", key, value));
+        for (key, value) in &check_env.vars {
+            prefout(&format!(
+                "  {}={}",
+                key.to_string_lossy(),
+                value.to_string_lossy()
+            ));
         }
     }
 
     // Run check command
-    let check_status = crate::runner::run_check_command(&check_command, &log_path)?;
+    remove_stale_report(jj, &workspace_path)?;
+    let check_status = crate::runner::run_check_command(
+        &check_command,
+        &check_env,
+        &LogOptions::from_config(jj)?,
+        &log_path,
+        &|| false,
+    )?;
 
     // Print log output (skipping sentinel lines)
-    if let Ok(log_contents) = fs::read_to_string(&log_path) {
-        for line in log_contents.lines() {
-            if !line.starts_with(crate::runlog::SENTINEL_PREFIX) {
-                println!("{}", line);
-            }
+    let log_contents = fs::read_to_string(&log_path).unwrap_or_default();
+    for line in log_contents.lines() {
+        if !line.starts_with(crate::runlog::SENTINEL_PREFIX) {
+            println!("{}", line);
         }
     }
 
     let success = check_status.success();
+    let failing_tests = if success {
+        Vec::new()
+    } else {
+        read_failing_tests(jj, &workspace_path)?
+    };
 
     // Always clean up
-    env::set_current_dir(&orig_dir)?;
-    jj::workspace_forget(&workspace_name)?;
+    cleanup(workspace, &duplicate_ids)?;
 
     if success {
         prefout("check passed");
         Ok(())
     } else {
-        Err(ExitError::new(exit_codes::CONFLICT, "check failed").into())
+        if !failing_tests.is_empty() {
+            preferr(&format!("failing tests: {}", failing_tests.join(", ")));
+        }
+        let reason = check_env.limits.violation(&check_status, &log_contents);
+        Err(Error::CheckFailed {
+            id: None,
+            reason: reason.unwrap_or("check").to_string(),
+        }
+        .into())
     }
 }
 
 /// Build a QueueItem by resolving data from the bookmark target.
-fn build_queue_item(id: u32) -> Result<QueueItem> {
+fn build_queue_item(jj: &Jj, id: u32, policy: &approval::Policy) -> Result<QueueItem> {
     let bookmark = queue::queue_bookmark(id);
     let revset = format!("bookmarks(exact:{})", bookmark);
-    let (change_id, commit_id) = jj::resolve_revset_full(&revset)?;
-    let description = jj::get_description(&revset)?;
-    let description = description.lines().next().unwrap_or("").to_string();
+    let (change_id, commit_id) = jj.resolve_revset_full(&revset)?;
+    let strategy = queue::item_strategy(jj, id)?.map(|s| s.as_str().to_string());
+    let full_description = jj.get_description(&revset)?;
+    let description = full_description.lines().next().unwrap_or("").to_string();
+    let approved_by = match policy.required {
+        true => policy.approved_by(jj, &commit_id, &full_description)?,
+        false => None,
+    };
+    let blocked = (policy.required && approved_by.is_none()).then(|| "needs approval".to_string());
     Ok(QueueItem {
         id,
         change_id,
         commit_id,
         description,
+        blocked,
+        approved_by,
+        strategy,
     })
 }
 
+/// The first queued item the runner may process, skipping items blocked on
+/// approval. Prints why if there is none.
+fn pick_item(jj: &Jj, policy: &approval::Policy) -> Result<Option<u32>> {
+    let queue = queue::get_queue(jj)?;
+    if queue.is_empty() {
+        prefout("queue is empty");
+        return Ok(None);
+    }
+    if !policy.required {
+        return Ok(queue.first().copied());
+    }
+    for &id in &queue {
+        if build_queue_item(jj, id, policy)?.blocked.is_none() {
+            return Ok(Some(id));
+        }
+        preferr(&format!("skipping queue item {}: needs approval", id));
+    }
+    prefout(&format!(
+        "no approved items in queue ({} blocked: needs approval)",
+        queue.len()
+    ));
+    Ok(None)
+}
+
 /// Build a FailedItem by parsing trailers from the bookmark target description.
-fn build_failed_item(id: u32) -> Result<FailedItem> {
+fn build_failed_item(jj: &Jj, id: u32) -> Result<FailedItem> {
     let bookmark = queue::failed_bookmark(id);
     let revset = format!("bookmarks(exact:{})", bookmark);
-    let desc = jj::get_description(&revset)?;
+    let desc = jj.get_description(&revset)?;
     let trailers = extract_trailers(&desc);
 
     let candidate_change_id = trailers.get("candidate").cloned().unwrap_or_default();
@@ -990,14 +1746,27 @@ fn build_failed_item(id: u32) -> Result<FailedItem> {
     let trunk_commit_id = trailers.get("trunk").cloned().unwrap_or_default();
     let workspace_path = trailers.get("workspace").cloned().unwrap_or_default();
     let failure_reason = trailers.get("failure").cloned().unwrap_or_default();
+    let limits = trailers.get("limits").cloned();
     let conflict_paths = trailers
         .get("conflicts")
         .map(|s| s.split(',').map(|p| p.to_string()).collect())
         .unwrap_or_default();
+    let failing_tests = extract_trailer_values(&desc, "failing-test");
+    let failing_test_count = trailers
+        .get("failing-test-count")
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(failing_tests.len());
+    let failure_excerpt = extract_trailer_values(&desc, "excerpt");
+    let artifacts = crate::artifacts::list(jj, id)?;
+    let artifacts_dir = if artifacts.is_empty() {
+        None
+    } else {
+        Some(crate::artifacts::dir(jj, id)?.display().to_string())
+    };
 
     // Resolve original candidate description from the candidate change ID
     let description = if !candidate_change_id.is_empty() {
-        jj::get_description(&candidate_change_id)
+        jj.get_description(&candidate_change_id)
             .map(|d| d.lines().next().unwrap_or("").to_string())
             .unwrap_or_default()
     } else {
@@ -1012,23 +1781,33 @@ fn build_failed_item(id: u32) -> Result<FailedItem> {
         trunk_commit_id,
         workspace_path,
         failure_reason,
+        limits,
         conflict_paths,
+        failing_tests,
+        failing_test_count,
+        failure_excerpt,
+        artifacts,
+        artifacts_dir,
     })
 }
 
 /// Find recently landed items by scanning trunk ancestors for jjq trailers.
 /// Returns up to `limit` items, most recent first.
-fn get_recently_landed(trunk_bookmark: &str, limit: usize) -> Result<Vec<LandedItem>> {
+fn get_recently_landed(jj: &Jj, trunk_bookmark: &str, limit: usize) -> Result<Vec<LandedItem>> {
     // Scan recent trunk ancestors for commits landed by jjq. We look for
-    // jjq-sequence trailers (rebase strategy) or "Success: merge" descriptions
-    // (merge strategy). Scan more than limit since not every ancestor is jjq-landed.
+    // jjq-sequence trailers (rebase and squash strategies) or "Success: merge" descriptions
+    // (merge strategy), then for landings recorded in metadata because their
+    // commits have no trailers (fast-forward, or landed_trailers off).
+    // Scan more than limit since not every ancestor is jjq-landed.
     let scan_count = (limit * 10).max(50);
     let revset = format!(
         "ancestors(bookmarks(exact:\"{}\"), {})",
         trunk_bookmark, scan_count
     );
-    let template = "change_id.short() ++ \"\\t\" ++ description ++ \"\\x00\"";
-    let output = jj::run_ok(&["log", "-r", &revset, "--no-graph", "-T", template])?;
+    let template =
+        "change_id.short() ++ \"\\t\" ++ change_id ++ \"\\t\" ++ description ++ \"\\x00\"";
+    let output = jj.run_ok(&["log", "-r", &revset, "--no-graph", "-T", template])?;
+    let records = landed::records(jj)?;
 
     let mut items = Vec::new();
     for block in output.split('\x00') {
@@ -1036,15 +1815,17 @@ fn get_recently_landed(trunk_bookmark: &str, limit: usize) -> Result<Vec<LandedI
         if block.is_empty() {
             continue;
         }
-        let (change_id, description) = match block.split_once('\t') {
-            Some(pair) => pair,
-            None => continue,
+        let mut fields = block.splitn(3, '\t');
+        let (Some(change_id), Some(full_change_id), Some(description)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
         };
 
         let trailers = extract_trailers(description);
 
         if let Some(seq_str) = trailers.get("sequence") {
-            // Rebase strategy: description has the original text + trailers
+            // Rebase or squash strategy: description text + trailers
             if let Ok(id) = seq_str.parse::<u32>() {
                 let desc_line = description
                     .lines()
@@ -1057,7 +1838,10 @@ fn get_recently_landed(trunk_bookmark: &str, limit: usize) -> Result<Vec<LandedI
                     description: desc_line,
                 });
             }
-        } else if let Some(rest) = description.trim().strip_prefix("Success: merge ") {
+        } else if let Some(rest) = description
+            .trim()
+            .strip_prefix(landed::MERGE_MESSAGE_PREFIX)
+        {
             // Merge strategy: description is "Success: merge {id}"
             if let Ok(id) = rest.trim().parse::<u32>() {
                 items.push(LandedItem {
@@ -1066,6 +1850,17 @@ fn get_recently_landed(trunk_bookmark: &str, limit: usize) -> Result<Vec<LandedI
                     description: description.lines().next().unwrap_or("").to_string(),
                 });
             }
+        } else if let Some((_, id)) = records
+            .iter()
+            .rev()
+            .find(|(recorded, _)| recorded == full_change_id)
+        {
+            // No trailers: recorded when it landed
+            items.push(LandedItem {
+                id: *id,
+                change_id: change_id.to_string(),
+                description: description.lines().next().unwrap_or("").to_string(),
+            });
         }
 
         if items.len() >= limit {
@@ -1076,57 +1871,170 @@ fn get_recently_landed(trunk_bookmark: &str, limit: usize) -> Result<Vec<LandedI
     Ok(items)
 }
 
-/// Display queue status.
-pub fn status(id: Option<&str>, json: bool, resolve: Option<&str>) -> Result<()> {
-    // Single-item modes
-    if id.is_some() || resolve.is_some() {
-        return status_single(id, json, resolve);
-    }
-
-    if !config::is_initialized()? {
-        if json {
-            let output = StatusOutput {
-                running: false,
-                queue: vec![],
-                failed: vec![],
-                landed: vec![],
-            };
-            println!("{}", serde_json::to_string_pretty(&output)?);
-        } else {
-            prefout("jjq not initialized. Run 'jjq init' first.");
-        }
-        return Ok(());
+/// Return the queue's current state; empty if jjq is not initialized.
+pub fn status_output(jj: &Jj) -> Result<StatusOutput> {
+    if !config::is_initialized(jj)? {
+        return Ok(StatusOutput::default());
     }
+    collect_status(jj)
+}
 
-    let running = lock::is_held("run")?;
-
-    let queue_ids = queue::get_queue()?;
-    let failed_ids = queue::get_failed()?;
+/// Read queued, failed and recently landed items.
+fn collect_status(jj: &Jj) -> Result<StatusOutput> {
+    let (running, runner) = match lock::lock_state(jj, "run")? {
+        lock::LockState::Held(holder) => (true, holder),
+        lock::LockState::Free => (false, None),
+    };
+    // A state file without the lock is left over from a killed runner
+    let current = if running { current_item(jj)? } else { None };
 
-    let queue_items: Vec<QueueItem> = queue_ids
+    let policy = approval::Policy::from_config(jj)?;
+    let queue = queue::get_queue(jj)?
         .iter()
-        .map(|&id| build_queue_item(id))
+        .map(|&id| build_queue_item(jj, id, &policy))
         .collect::<Result<_>>()?;
 
-    let failed_items: Vec<FailedItem> = failed_ids
+    let failed = queue::get_failed(jj)?
         .iter()
-        .map(|&id| build_failed_item(id))
+        .map(|&id| build_failed_item(jj, id))
         .collect::<Result<_>>()?;
 
-    let trunk_bookmark = config::get_trunk_bookmark()?;
-    let landed_items = get_recently_landed(&trunk_bookmark, 5).unwrap_or_default();
+    let trunk_bookmark = config::get_trunk_bookmark(jj)?;
+    let landed = get_recently_landed(jj, &trunk_bookmark, 5).unwrap_or_default();
+
+    Ok(StatusOutput {
+        paused: pause::get(jj)?,
+        landing_closed: Schedule::from_config(jj)?.closed_now(),
+        running,
+        runner,
+        current,
+        queue,
+        failed,
+        landed,
+    })
+}
+
+/// Read the runner's published state, with timings and an ETA.
+fn current_item(jj: &Jj) -> Result<Option<CurrentItem>> {
+    let Some(runner) = state::read(jj)? else {
+        return Ok(None);
+    };
+    let check_elapsed_secs = match runner.phase {
+        Phase::Checking => runner.check_started.map(state::secs_since),
+        _ => None,
+    };
+    let eta_secs = match (check_elapsed_secs, state::typical_check_duration(jj)?) {
+        (Some(elapsed), Some(typical)) => Some(typical.as_secs().saturating_sub(elapsed)),
+        _ => None,
+    };
+    let last_log_line = match check_elapsed_secs {
+        Some(_) => crate::runlog::last_line(&crate::runlog::log_path(jj)?),
+        None => None,
+    };
+    Ok(Some(CurrentItem {
+        id: runner.id,
+        candidate_change_id: runner.candidate_change_id,
+        phase: runner.phase,
+        pid: runner.pid,
+        elapsed_secs: state::secs_since(runner.started),
+        check_elapsed_secs,
+        eta_secs,
+        last_log_line,
+    }))
+}
+
+/// "item 42, pid 1234, started 3m ago" for the status overview.
+fn describe_runner(runner: &lock::Holder) -> String {
+    let mut parts = Vec::new();
+    if let Some(id) = runner.item {
+        parts.push(format!("item {}", id));
+    }
+    parts.push(format!("pid {}", runner.pid));
+    parts.push(format!(
+        "started {} ago",
+        crate::runner::format_duration(runner.elapsed())
+    ));
+    let mut text = parts.join(", ");
+    if runner.is_stale() {
+        text.push_str(" (process no longer exists)");
+    }
+    text
+}
+
+/// Print the runner's current item under "Run in progress".
+fn print_current(current: &CurrentItem) {
+    let mut phase = format!(
+        "  {}: {} {}",
+        current.id,
+        current.candidate_change_id,
+        current.phase.as_str()
+    );
+    if let Some(elapsed) = current.check_elapsed_secs {
+        phase.push_str(&format!(
+            " for {}",
+            crate::runner::format_duration(Duration::from_secs(elapsed))
+        ));
+        if let Some(eta) = current.eta_secs {
+            phase.push_str(&format!(
+                ", about {} left",
+                crate::runner::format_duration(Duration::from_secs(eta))
+            ));
+        }
+    }
+    println!("{}", phase);
+    if let Some(line) = &current.last_log_line {
+        println!("      {}", line.trim());
+    }
+}
+
+/// Display queue status.
+pub fn status(jj: &Jj, id: Option<&str>, json: bool, resolve: Option<&str>) -> Result<()> {
+    // Single-item modes
+    if id.is_some() || resolve.is_some() {
+        return status_single(jj, id, json, resolve);
+    }
+
+    let initialized = config::is_initialized(jj)?;
+    if !initialized && !json {
+        prefout("jjq not initialized. Run 'jjq init' first.");
+        return Ok(());
+    }
+    let output = if initialized {
+        collect_status(jj)?
+    } else {
+        StatusOutput::default()
+    };
 
     if json {
-        let output = StatusOutput {
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else {
+        let StatusOutput {
+            paused,
+            landing_closed,
             running,
+            runner,
+            current,
             queue: queue_items,
             failed: failed_items,
             landed: landed_items,
-        };
-        println!("{}", serde_json::to_string_pretty(&output)?);
-    } else {
+        } = output;
+        if let Some(pause) = &paused {
+            prefout(&format!("QUEUE PAUSED: {}", pause));
+            prefout("  runs are refused until 'jjq resume'");
+            println!();
+        }
+        if let Some(closed) = &landing_closed {
+            prefout(&format!("Landing closed: {}", closed));
+            println!();
+        }
         if running {
-            prefout("Run in progress");
+            match runner {
+                Some(runner) => prefout(&format!("Run in progress: {}", describe_runner(&runner))),
+                None => prefout("Run in progress"),
+            }
+            if let Some(current) = &current {
+                print_current(current);
+            }
             println!();
         }
 
@@ -1138,7 +2046,23 @@ pub fn status(id: Option<&str>, json: bool, resolve: Option<&str>) -> Result<()>
         if !queue_items.is_empty() {
             prefout("Queued:");
             for item in &queue_items {
-                println!("  {}: {} {}", item.id, item.change_id, item.description);
+                let mut notes = Vec::new();
+                if let Some(strategy) = &item.strategy {
+                    notes.push(format!("strategy: {}", strategy));
+                }
+                if let Some(blocked) = &item.blocked {
+                    notes.push(format!("blocked: {}", blocked));
+                }
+                match notes.is_empty() {
+                    true => println!("  {}: {} {}", item.id, item.change_id, item.description),
+                    false => println!(
+                        "  {}: {} {} ({})",
+                        item.id,
+                        item.change_id,
+                        item.description,
+                        notes.join(", ")
+                    ),
+                }
             }
         }
 
@@ -1162,6 +2086,9 @@ pub fn status(id: Option<&str>, json: bool, resolve: Option<&str>) -> Result<()>
                         item.conflict_paths.join(", ")
                     );
                 }
+                if let Some(line) = item.failure_excerpt.first() {
+                    println!("      {}", line.trim());
+                }
             }
         }
 
@@ -1179,24 +2106,40 @@ pub fn status(id: Option<&str>, json: bool, resolve: Option<&str>) -> Result<()>
     Ok(())
 }
 
-fn status_single(id: Option<&str>, json: bool, resolve: Option<&str>) -> Result<()> {
+fn status_single(jj: &Jj, id: Option<&str>, json: bool, resolve: Option<&str>) -> Result<()> {
     let (item_id, is_queued) = if let Some(id_str) = id {
         let id = queue::parse_seq_id(id_str)?;
-        if queue::queue_item_exists(id)? {
+        if queue::queue_item_exists(jj, id)? {
             (id, true)
-        } else if queue::failed_item_exists(id)? {
+        } else if queue::failed_item_exists(jj, id)? {
             (id, false)
         } else {
-            bail!("item {} not found in queue or failed", id)
+            // Landed or deleted items may still have artifacts
+            let artifacts = crate::artifacts::list(jj, id)?;
+            if artifacts.is_empty() {
+                return Err(Error::ItemNotFound { id }.into());
+            }
+            let item = ArtifactsItem {
+                id,
+                artifacts_dir: crate::artifacts::dir(jj, id)?.display().to_string(),
+                artifacts,
+            };
+            if json {
+                println!("{}", serde_json::to_string_pretty(&item)?);
+            } else {
+                println!("Item {}", item.id);
+                print_artifacts(&item.artifacts_dir, &item.artifacts);
+            }
+            return Ok(());
         }
     } else if let Some(change_id) = resolve {
-        find_by_change_id(change_id)?
+        find_by_change_id(jj, change_id)?
     } else {
         unreachable!()
     };
 
     if is_queued {
-        let item = build_queue_item(item_id)?;
+        let item = build_queue_item(jj, item_id, &approval::Policy::from_config(jj)?)?;
         if json {
             println!("{}", serde_json::to_string_pretty(&item)?);
         } else {
@@ -1204,9 +2147,18 @@ fn status_single(id: Option<&str>, json: bool, resolve: Option<&str>) -> Result<
             println!("  Change ID:   {}", item.change_id);
             println!("  Commit ID:   {}", item.commit_id);
             println!("  Description: {}", item.description);
+            if let Some(strategy) = &item.strategy {
+                println!("  Strategy:    {}", strategy);
+            }
+            if let Some(approved_by) = &item.approved_by {
+                println!("  Approved by: {}", approved_by);
+            }
+            if let Some(blocked) = &item.blocked {
+                println!("  Blocked:     {}", blocked);
+            }
         }
     } else {
-        let item = build_failed_item(item_id)?;
+        let item = build_failed_item(jj, item_id)?;
         if json {
             println!("{}", serde_json::to_string_pretty(&item)?);
         } else {
@@ -1217,12 +2169,36 @@ fn status_single(id: Option<&str>, json: bool, resolve: Option<&str>) -> Result<
             );
             println!("  Description: {}", item.description);
             println!("  Failure:     {}", item.failure_reason);
+            if let Some(limits) = &item.limits {
+                println!("  Limits:      {}", limits);
+            }
             if !item.conflict_paths.is_empty() {
                 println!("  Conflicts:   {}", item.conflict_paths.join(", "));
             }
+            if !item.failing_tests.is_empty() {
+                println!("  Failing tests ({}):", item.failing_test_count);
+                for name in &item.failing_tests {
+                    println!("    {}", name);
+                }
+                let omitted = item
+                    .failing_test_count
+                    .saturating_sub(item.failing_tests.len());
+                if omitted > 0 {
+                    println!("    ... and {} more", omitted);
+                }
+            }
+            if !item.failure_excerpt.is_empty() {
+                println!("  Excerpt:");
+                for line in &item.failure_excerpt {
+                    println!("    {}", line);
+                }
+            }
+            if let Some(dir) = &item.artifacts_dir {
+                print_artifacts(dir, &item.artifacts);
+            }
             println!("  Trunk:       {}", item.trunk_commit_id);
             println!();
-            let trunk_bookmark = config::get_trunk_bookmark().unwrap_or_else(|_| "main".into());
+            let trunk_bookmark = config::get_trunk_bookmark(jj).unwrap_or_else(|_| "main".into());
             if item.failure_reason == "conflicts" {
                 println!("To resolve:");
                 println!(
@@ -1242,15 +2218,23 @@ fn status_single(id: Option<&str>, json: bool, resolve: Option<&str>) -> Result<
     Ok(())
 }
 
+/// Print the artifacts section of `status <id>`.
+fn print_artifacts(dir: &str, artifacts: &[String]) {
+    println!("  Artifacts:   {}", dir);
+    for path in artifacts {
+        println!("    {}", path);
+    }
+}
+
 /// Find a queue or failed item by candidate change ID.
 /// Returns (sequence_id, is_queued).
-fn find_by_change_id(change_id: &str) -> Result<(u32, bool)> {
+fn find_by_change_id(jj: &Jj, change_id: &str) -> Result<(u32, bool)> {
     // Search queue items
-    let queue_ids = queue::get_queue()?;
+    let queue_ids = queue::get_queue(jj)?;
     for id in &queue_ids {
         let bookmark = queue::queue_bookmark(*id);
         let revset = format!("bookmarks(exact:{})", bookmark);
-        if let Ok(item_change_id) = jj::resolve_revset(&revset)
+        if let Ok(item_change_id) = jj.resolve_revset(&revset)
             && item_change_id == change_id
         {
             return Ok((*id, true));
@@ -1258,11 +2242,11 @@ fn find_by_change_id(change_id: &str) -> Result<(u32, bool)> {
     }
 
     // Search failed items
-    let failed_ids = queue::get_failed()?;
+    let failed_ids = queue::get_failed(jj)?;
     for id in &failed_ids {
         let bookmark = queue::failed_bookmark(*id);
         let revset = format!("bookmarks(exact:{})", bookmark);
-        if let Ok(desc) = jj::get_description(&revset) {
+        if let Ok(desc) = jj.get_description(&revset) {
             let trailers = extract_trailers(&desc);
             if trailers.get("candidate").map(|s| s.as_str()) == Some(change_id) {
                 return Ok((*id, false));
@@ -1270,62 +2254,57 @@ fn find_by_change_id(change_id: &str) -> Result<(u32, bool)> {
         }
     }
 
-    bail!("no item found with candidate change ID '{}'", change_id)
+    Err(Error::CandidateNotFound {
+        change_id: change_id.to_string(),
+    }
+    .into())
 }
 
 /// Re-push a failed item back onto the queue.
-pub fn requeue(id_str: &str) -> Result<()> {
+pub fn requeue(jj: &Jj, id_str: &str) -> Result<()> {
     let id = queue::parse_seq_id(id_str)?;
 
-    require_initialized()?;
+    require_initialized(jj)?;
 
-    if !queue::failed_item_exists(id)? {
-        if queue::queue_item_exists(id)? {
-            return Err(
-                ExitError::new(exit_codes::USAGE, format!("item {} is already queued", id)).into(),
-            );
+    if !queue::failed_item_exists(jj, id)? {
+        if queue::queue_item_exists(jj, id)? {
+            return Err(Error::ItemQueued { id }.into());
         }
-        return Err(
-            ExitError::new(exit_codes::USAGE, format!("failed item {} not found", id)).into(),
-        );
+        return Err(Error::FailedItemNotFound { id }.into());
     }
 
     // Build the failed item to get candidate info
-    let item = build_failed_item(id)?;
+    let item = build_failed_item(jj, id)?;
     let candidate_change_id = &item.candidate_change_id;
 
     if candidate_change_id.is_empty() {
-        return Err(ExitError::new(
-            exit_codes::USAGE,
-            format!("failed item {} has no candidate change ID", id),
-        )
-        .into());
+        return Err(Error::usage(format!("failed item {} has no candidate change ID", id)).into());
     }
 
     // Resolve the candidate — it may have been rebased since the failure
     let revset = candidate_change_id.as_str();
-    let (_change_id, _commit_id) = jj::resolve_revset_full(revset)
-        .map_err(|e| ExitError::new(exit_codes::USAGE, format!("candidate not found: {}", e)))?;
+    let (_change_id, commit_id) = jj
+        .resolve_revset_full(revset)
+        .map_err(|e| Error::usage(format!("candidate not found: {}", e)))?;
 
     // Get trunk bookmark for conflict check
-    let trunk_bookmark = config::get_trunk_bookmark()?;
-    if !jj::bookmark_exists(&trunk_bookmark)? {
-        return Err(ExitError::new(
-            exit_codes::USAGE,
-            format!("trunk bookmark '{}' not found", trunk_bookmark),
-        )
+    let trunk_bookmark = config::get_trunk_bookmark(jj)?;
+    if !jj.bookmark_exists(&trunk_bookmark)? {
+        return Err(Error::TrunkMissing {
+            bookmark: trunk_bookmark,
+        }
         .into());
     }
 
     // Pre-flight conflict check against current trunk
-    let conflict_check_id = jj::new_rev(&[&trunk_bookmark, revset])?;
-    let has_conflicts = match jj::has_conflicts(&conflict_check_id) {
+    let conflict_check_id = jj.new_rev(&[&trunk_bookmark, revset])?;
+    let has_conflicts = match jj.has_conflicts(&conflict_check_id) {
         Ok(v) => {
-            jj::abandon(&conflict_check_id)?;
+            jj.abandon(&conflict_check_id)?;
             v
         }
         Err(e) => {
-            let _ = jj::abandon(&conflict_check_id);
+            let _ = jj.abandon(&conflict_check_id);
             return Err(e);
         }
     };
@@ -1339,23 +2318,35 @@ pub fn requeue(id_str: &str) -> Result<()> {
             "rebase onto {} and resolve conflicts before requeuing",
             trunk_bookmark
         ));
-        return Err(
-            ExitError::new(exit_codes::CONFLICT, "revision conflicts with trunk").into(),
-        );
+        return Err(Error::Conflict {
+            revset: revset.to_string(),
+        }
+        .into());
     }
 
     // Allocate new queue ID and create queue bookmark
-    let new_id = queue::next_id()?;
+    let new_id = queue::next_id(jj, None, &jj.user_name(), queue::item_strategy(jj, id)?)?;
     let new_bookmark = queue::queue_bookmark(new_id);
-    jj::bookmark_create(&new_bookmark, revset)?;
+    jj.bookmark_create(&new_bookmark, revset)?;
 
     // Clean up the failed entry and its workspace
     let padded = queue::format_seq_id(id);
     let run_name = format!("jjq-run-{}", padded);
-    let workspace_path = lookup_workspace_path(id);
+    let workspace_path = lookup_workspace_path(jj, id);
+
+    jj.bookmark_delete(&queue::failed_bookmark(id))?;
+    queue::forget_item(jj, id)?;
+    let _ = jj.workspace_forget(&run_name);
 
-    jj::bookmark_delete(&queue::failed_bookmark(id))?;
-    let _ = jj::workspace_forget(&run_name);
+    // An approval of the failed commit doesn't carry over to a rewritten one
+    if !item.candidate_commit_id.is_empty() && item.candidate_commit_id != commit_id {
+        approval::remove(jj, &item.candidate_commit_id)?;
+    }
+
+    // The failed run's artifacts don't belong to the new item
+    if crate::artifacts::remove(jj, id)? {
+        prefout(&format!("removed artifacts for item {}", id));
+    }
 
     if let Some(ref path) = workspace_path {
         let p = std::path::Path::new(path);
@@ -1364,7 +2355,7 @@ pub fn requeue(id_str: &str) -> Result<()> {
         }
     }
 
-    let repo_path = jj::repo_root()?;
+    let repo_path = jj.root();
     prefout(&format!(
         "requeued failed item {} as {} (trunk: {} in {})",
         id, new_id, trunk_bookmark, repo_path.display()
@@ -1374,36 +2365,52 @@ pub fn requeue(id_str: &str) -> Result<()> {
 }
 
 /// Delete an item from queue or failed list.
-pub fn delete(id_str: &str) -> Result<()> {
+pub fn delete(jj: &Jj, id_str: &str) -> Result<()> {
     let id = queue::parse_seq_id(id_str)?;
 
-    require_initialized()?;
+    require_initialized(jj)?;
 
     // Check queue first
-    if queue::queue_item_exists(id)? {
-        jj::bookmark_delete(&queue::queue_bookmark(id))?;
+    if queue::queue_item_exists(jj, id)? {
+        let queue_bookmark = queue::queue_bookmark(id);
+        let commit_id = jj.get_commit_id(&format!("bookmarks(exact:{})", queue_bookmark))?;
+        jj.bookmark_delete(&queue_bookmark)?;
+        queue::forget_item(jj, id)?;
+        approval::remove(jj, &commit_id)?;
         prefout(&format!("deleted queued item {}", id));
         return Ok(());
     }
 
     // Check failed
-    if queue::failed_item_exists(id)? {
+    if queue::failed_item_exists(jj, id)? {
         let padded = queue::format_seq_id(id);
         let run_name = format!("jjq-run-{}", padded);
 
-        // Look up workspace path before deleting
-        let workspace_path = lookup_workspace_path(id);
-
-        jj::bookmark_delete(&queue::failed_bookmark(id))?;
+        // Look up workspace path and candidate before deleting
+        let workspace_path = lookup_workspace_path(jj, id);
+        let failed_rev = format!("bookmarks(exact:{})", queue::failed_bookmark(id));
+        let candidate_commit_id = extract_trailers(&jj.get_description(&failed_rev)?)
+            .remove("candidate-commit")
+            .filter(|commit_id| !commit_id.is_empty());
+
+        jj.bookmark_delete(&queue::failed_bookmark(id))?;
+        queue::forget_item(jj, id)?;
+        if let Some(commit_id) = candidate_commit_id {
+            approval::remove(jj, &commit_id)?;
+        }
         prefout(&format!("deleted failed item {}", id));
 
         // Try to forget the workspace (silently ignore if not found)
-        let _ = jj::workspace_forget(&run_name);
+        let _ = jj.workspace_forget(&run_name);
 
-        // Remove directory if found and still exists
+        if crate::artifacts::remove(jj, id)? {
+            prefout(&format!("removed artifacts for item {}", id));
+        }
+
+        // Remove directory if found and still exists (pool workspaces are reused)
         if let Some(ref path) = workspace_path {
             let p = std::path::Path::new(path);
-            if p.is_dir() {
+            if p.is_dir() && !pool::owns(jj, p) {
                 let _ = std::fs::remove_dir_all(p);
                 prefout(&format!("removed workspace {}", path));
             }
@@ -1412,75 +2419,155 @@ pub fn delete(id_str: &str) -> Result<()> {
         return Ok(());
     }
 
-    bail!("item {} not found in queue or failed", id)
+    Err(Error::ItemNotFound { id }.into())
+}
+
+/// Approve a queued item so it may land when approval is required.
+pub fn approve(jj: &Jj, id_str: &str) -> Result<()> {
+    let id = queue::parse_seq_id(id_str)?;
+
+    require_initialized(jj)?;
+
+    if !queue::queue_item_exists(jj, id)? {
+        return Err(Error::usage(format!("item {} is not in the queue", id)).into());
+    }
+    let commit_id = jj.get_commit_id(&format!("bookmarks(exact:{})", queue::queue_bookmark(id)))?;
+    let approval = approval::record(jj, id, &commit_id)?;
+    prefout(&format!("approved queue item {} as {}", id, approval.by));
+    if !approval::Policy::from_config(jj)?.required {
+        prefout("note: approval is not required (jjq config require_approval true)");
+    }
+    Ok(())
+}
+
+/// Pause the queue: runs refuse to process items until `resume`.
+pub fn pause(jj: &Jj, reason: Option<&str>) -> Result<()> {
+    require_initialized(jj)?;
+
+    let pause = pause::set(jj, reason)?;
+    prefout(&format!("queue {}", pause));
+    if lock::is_held(jj, "run")? {
+        prefout("the item being processed will finish; later items wait for 'jjq resume'");
+    }
+    Ok(())
+}
+
+/// Resume a paused queue.
+pub fn resume(jj: &Jj) -> Result<()> {
+    require_initialized(jj)?;
+
+    if pause::get(jj)?.is_none() {
+        prefout("queue is not paused");
+        return Ok(());
+    }
+    pause::clear(jj)?;
+    prefout("queue resumed");
+    Ok(())
+}
+
+/// Cancel the item the runner is processing: its check is stopped and the
+/// item marked failed with reason `cancelled`, or left in the queue with
+/// `requeue`. With `id`, only cancels if that item is the one running.
+pub fn cancel(jj: &Jj, id_str: Option<&str>, requeue: bool) -> Result<()> {
+    let id = id_str.map(queue::parse_seq_id).transpose()?;
+
+    require_initialized(jj)?;
+
+    // A state file without the lock is left over from a killed runner
+    let running = match lock::is_held(jj, "run")? {
+        true => state::read(jj)?,
+        false => None,
+    };
+    let Some(runner) = running.filter(|runner| id.is_none_or(|id| id == runner.id)) else {
+        return Err(Error::NotRunning { id }.into());
+    };
+    if runner.phase == Phase::Landing {
+        return Err(Error::usage(format!(
+            "item {} is already landing and can't be cancelled",
+            runner.id
+        ))
+        .into());
+    }
+
+    state::request_cancel(jj, runner.id, requeue)?;
+    prefout(&format!(
+        "cancelling item {} (runner pid {})",
+        runner.id, runner.pid
+    ));
+    Ok(())
 }
 
 /// Get or set configuration.
-pub fn config(key: Option<&str>, value: Option<&str>) -> Result<()> {
+pub fn config(jj: &Jj, key: Option<&str>, value: Option<&str>) -> Result<()> {
     match (key, value) {
         (None, None) => {
             // Show all config
-            require_initialized()?;
-            let _config_lock = Lock::acquire_or_fail("config", "config lock unavailable")?;
-            let trunk = config::get_trunk_bookmark()?;
-            let check = config::get_check_command()?;
+            require_initialized(jj)?;
+            let _config_lock = Lock::acquire_or_fail(jj, "config", None)?;
+            let trunk = config::get_trunk_bookmark(jj)?;
+            let check = config::get_check_command(jj)?;
 
             println!("trunk_bookmark = {}", trunk);
             println!(
                 "check_command = {}",
                 check.unwrap_or_else(|| "(not set)".to_string())
             );
-            let strategy = config::get_strategy()?;
+            let strategy = config::get_strategy(jj)?;
             println!("strategy = {}", strategy.as_str());
+            // Optional keys are only shown once set
+            for key in config::VALID_KEYS {
+                if config::CORE_KEYS.contains(key) {
+                    continue;
+                }
+                if let Some(value) = config::get(jj, key)? {
+                    println!("{} = {}", key, value);
+                }
+            }
             Ok(())
         }
         (Some(k), None) => {
             // Get single value
             if !config::VALID_KEYS.contains(&k) {
-                bail!(
-                    "unknown config key: {}\nvalid keys: {}",
-                    k,
-                    config::VALID_KEYS.join(", ")
-                );
+                return Err(config::unknown_key(k).into());
             }
 
             // If not initialized, show defaults
-            if !config::is_initialized()? {
+            if !config::is_initialized(jj)? {
                 let value = match k {
                     "trunk_bookmark" => config::DEFAULT_TRUNK_BOOKMARK.to_string(),
                     "check_command" => String::new(),
                     "strategy" => config::DEFAULT_STRATEGY.as_str().to_string(),
-                    _ => unreachable!(),
+                    "check_shell" => config::DEFAULT_CHECK_SHELL.to_string(),
+                    _ => String::new(),
                 };
                 println!("{}", value);
                 return Ok(());
             }
 
-            let _config_lock = Lock::acquire_or_fail("config", "config lock unavailable")?;
+            let _config_lock = Lock::acquire_or_fail(jj, "config", None)?;
             let value = match k {
-                "trunk_bookmark" => config::get_trunk_bookmark()?,
-                "check_command" => config::get_check_command()?.unwrap_or_default(),
-                "strategy" => config::get_strategy()?.as_str().to_string(),
-                _ => unreachable!(),
+                "trunk_bookmark" => config::get_trunk_bookmark(jj)?,
+                "check_command" => config::get_check_command(jj)?.unwrap_or_default(),
+                "strategy" => config::get_strategy(jj)?.as_str().to_string(),
+                "check_shell" => config::get_check_shell(jj)?,
+                _ => config::get(jj, k)?.unwrap_or_default(),
             };
             println!("{}", value);
             Ok(())
         }
         (Some(k), Some(v)) => {
             // Set value
-            let _config_lock = Lock::acquire_or_fail("config", "config lock unavailable")?;
-            config::set(k, v)?;
+            let _config_lock = Lock::acquire_or_fail(jj, "config", None)?;
+            config::set(jj, k, v)?;
             prefout(&format!("{} = {}", k, v));
             Ok(())
         }
-        (None, Some(_)) => {
-            bail!("cannot set value without key")
-        }
+        (None, Some(_)) => Err(Error::usage("cannot set value without key").into()),
     }
 }
 
 /// Validate configuration and environment.
-pub fn doctor() -> Result<()> {
+pub fn doctor(jj: &Jj) -> Result<()> {
     println!("jjq doctor:");
 
     let mut fails = 0u32;
@@ -1490,7 +2577,7 @@ pub fn doctor() -> Result<()> {
     print_check("ok", "jj repository");
 
     // 2. jjq initialized
-    let initialized = config::is_initialized()?;
+    let initialized = config::is_initialized(jj)?;
     if initialized {
         print_check("ok", "jjq initialized");
     } else {
@@ -1499,8 +2586,8 @@ pub fn doctor() -> Result<()> {
     }
 
     // 3. trunk bookmark exists
-    let trunk_bookmark = config::get_trunk_bookmark()?;
-    if jj::bookmark_exists(&trunk_bookmark)? {
+    let trunk_bookmark = config::get_trunk_bookmark(jj)?;
+    if jj.bookmark_exists(&trunk_bookmark)? {
         print_check("ok", &format!("trunk bookmark '{}' exists", trunk_bookmark));
     } else {
         print_check(
@@ -1512,7 +2599,7 @@ pub fn doctor() -> Result<()> {
 
     // 4. check command configured
     let check_configured = if initialized {
-        config::get_check_command()?.is_some()
+        config::get_check_command(jj)?.is_some()
     } else {
         false
     };
@@ -1526,7 +2613,7 @@ pub fn doctor() -> Result<()> {
 
     // 5. strategy valid
     if initialized {
-        match config::get_strategy() {
+        match config::get_strategy(jj) {
             Ok(s) => print_check("ok", &format!("strategy: {}", s.as_str())),
             Err(e) => {
                 print_check("FAIL", &format!("invalid strategy: {}", e));
@@ -1535,8 +2622,20 @@ pub fn doctor() -> Result<()> {
         }
     }
 
-    // 6. jj log filter hides jjq metadata
-    if let Ok(Some(current_log)) = jj::config_get("revsets.log")
+    // 6. queue not paused
+    if initialized {
+        match pause::get(jj)? {
+            None => print_check("ok", "queue is not paused"),
+            Some(pause) => {
+                print_check("WARN", &format!("queue {}", pause));
+                print_hint("to resume landing: jjq resume");
+                warns += 1;
+            }
+        }
+    }
+
+    // 7. jj log filter hides jjq metadata
+    if let Ok(Some(current_log)) = jj.config_get("revsets.log")
         && current_log.contains(config::JJQ_BOOKMARK)
     {
         print_check("ok", "jj log hides jjq metadata");
@@ -1549,26 +2648,37 @@ pub fn doctor() -> Result<()> {
         warns += 1;
     }
 
-    // 7. locks
-    match lock::lock_state("run")? {
-        lock::LockState::Free => print_check("ok", "run lock is free"),
-        lock::LockState::Held => {
-            print_check("WARN", "run lock held by another process");
-            warns += 1;
-        }
-    }
-
-    // (id lock)
-    match lock::lock_state("id")? {
-        lock::LockState::Free => print_check("ok", "id lock is free"),
-        lock::LockState::Held => {
-            print_check("WARN", "id lock held by another process");
-            warns += 1;
+    // 8. locks
+    for name in ["run", "id"] {
+        match lock::lock_state(jj, name)? {
+            lock::LockState::Free => print_check("ok", &format!("{} lock is free", name)),
+            lock::LockState::Held(Some(holder)) if holder.is_stale() => {
+                print_check(
+                    "FAIL",
+                    &format!(
+                        "{} lock held by pid {}, which no longer exists on {}",
+                        name, holder.pid, holder.host
+                    ),
+                );
+                print_hint(&format!(
+                    "a child of that process may still hold it; or remove .jj/jjq-locks/{}.lock",
+                    name
+                ));
+                fails += 1;
+            }
+            lock::LockState::Held(Some(holder)) => {
+                print_check("WARN", &format!("{} lock held by {}", name, holder));
+                warns += 1;
+            }
+            lock::LockState::Held(None) => {
+                print_check("WARN", &format!("{} lock held by another process", name));
+                warns += 1;
+            }
         }
     }
 
-    // 8. orphaned workspaces
-    let ws_output = jj::workspace_list()?;
+    // 9. orphaned workspaces
+    let ws_output = jj.workspace_list()?;
     let orphaned: usize = ws_output
         .lines()
         .filter_map(|line| {
@@ -1600,7 +2710,7 @@ pub fn doctor() -> Result<()> {
     }
 
     if fails > 0 {
-        Err(ExitError::new(exit_codes::CONFLICT, "doctor found issues").into())
+        Err(Error::DoctorFailed.into())
     } else {
         Ok(())
     }
@@ -1619,12 +2729,15 @@ fn print_hint(msg: &str) {
     println!("       {}", msg);
 }
 
-/// Remove all jjq workspaces and their directories.
-pub fn clean() -> Result<()> {
-    let ws_output = jj::workspace_list()?;
+/// Remove all jjq workspaces and their directories, and with `reset_pool`
+/// the warm runner workspaces too.
+pub fn clean(jj: &Jj, reset_pool: bool) -> Result<()> {
+    let ws_output = jj.workspace_list()?;
 
     let mut removed = 0u32;
     let mut details = Vec::new();
+    // Items whose run workspace is removed lose their artifacts too
+    let mut cleaned_ids = Vec::new();
 
     for line in ws_output.lines() {
         let ws_name = line.split_whitespace().next().unwrap_or("");
@@ -1637,18 +2750,19 @@ pub fn clean() -> Result<()> {
         // Config/meta workspaces don't correspond to queue items.
         let (label, workspace_path) = if let Some(ws_id_str) = ws_name.strip_prefix("jjq-run-") {
             let plain_id: u32 = ws_id_str.parse().unwrap_or(0);
-            let label = if queue::failed_item_exists(plain_id)? {
+            cleaned_ids.push(plain_id);
+            let label = if queue::failed_item_exists(jj, plain_id)? {
                 format!("failed item {}", plain_id)
             } else {
                 "orphaned".to_string()
             };
-            (label, lookup_workspace_path(plain_id))
+            (label, lookup_workspace_path(jj, plain_id))
         } else {
             ("orphaned".to_string(), None)
         };
 
         // Forget the workspace
-        let _ = jj::workspace_forget(ws_name);
+        let _ = jj.workspace_forget(ws_name);
 
         // Remove directory if found
         if let Some(ref path) = workspace_path {
@@ -1672,5 +2786,35 @@ pub fn clean() -> Result<()> {
         prefout(&format!("removed {} workspace(s)\n{}", removed, detail_str));
     }
 
+    // Artifacts of landed and deleted items are left over too; those of
+    // other queued and failed items are kept
+    let mut artifact_items = 0;
+    for id in crate::artifacts::ids(jj)? {
+        let leftover = cleaned_ids.contains(&id)
+            || !(queue::queue_item_exists(jj, id)? || queue::failed_item_exists(jj, id)?);
+        if leftover && crate::artifacts::remove(jj, id)? {
+            artifact_items += 1;
+        }
+    }
+    if artifact_items > 0 {
+        prefout(&format!("removed artifacts for {} item(s)", artifact_items));
+    }
+
+    if reset_pool {
+        let (removed, busy) = pool::reset(jj)?;
+        if removed.is_empty() && busy.is_empty() {
+            prefout("no pool workspaces to reset");
+        } else if !removed.is_empty() {
+            prefout(&format!(
+                "reset {} pool workspace(s): {}",
+                removed.len(),
+                removed.join(", ")
+            ));
+        }
+        for name in busy {
+            preferr(&format!("pool workspace {} is in use, skipped", name));
+        }
+    }
+
     Ok(())
 }
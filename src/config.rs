@@ -1,13 +1,17 @@
 // ABOUTME: Configuration management for jjq stored on the metadata branch.
 // ABOUTME: Handles reading/writing config values from config/ directory.
 
-use anyhow::{Result, bail};
+use anyhow::Result;
+use regex::Regex;
 use std::env;
 use std::fs;
 use std::io::IsTerminal;
+use std::path::PathBuf;
 use tempfile::TempDir;
 
-use crate::jj;
+use crate::error::Error;
+use crate::jj::Jj;
+use crate::metadata;
 
 /// The jjq metadata bookmark name.
 pub const JJQ_BOOKMARK: &str = "jjq/_/_";
@@ -20,6 +24,10 @@ pub const DEFAULT_TRUNK_BOOKMARK: &str = "main";
 pub enum Strategy {
     Merge,
     Rebase,
+    /// Collapse the candidate chain into one new commit on trunk.
+    Squash,
+    /// Land the candidate itself, which must already descend from trunk.
+    FastForward,
 }
 
 impl Strategy {
@@ -27,16 +35,23 @@ impl Strategy {
         match self {
             Strategy::Merge => "merge",
             Strategy::Rebase => "rebase",
+            Strategy::Squash => "squash",
+            Strategy::FastForward => "fast-forward",
         }
     }
 }
 
+/// Strategy names accepted by `init --strategy` and `config strategy`.
+pub const VALID_STRATEGIES: &[&str] = &["rebase", "merge", "squash", "fast-forward"];
+
 impl TryFrom<&str> for Strategy {
     type Error = &'static str;
     fn try_from(s: &str) -> Result<Self, Self::Error> {
         match s {
             "merge" => Ok(Strategy::Merge),
             "rebase" => Ok(Strategy::Rebase),
+            "squash" => Ok(Strategy::Squash),
+            "fast-forward" => Ok(Strategy::FastForward),
             _ => Err("unknown strategy"),
         }
     }
@@ -45,132 +60,380 @@ impl TryFrom<&str> for Strategy {
 /// Default strategy for existing repos (backward compat).
 pub const DEFAULT_STRATEGY: Strategy = Strategy::Merge;
 
+/// Default template for the squash strategy's landed description: every
+/// constituent description, oldest first.
+pub const DEFAULT_SQUASH_MESSAGE: &str = "{descriptions}";
+
+/// Default shell used to run the check command.
+pub const DEFAULT_CHECK_SHELL: &str = "/bin/sh";
+
 /// Valid configuration keys.
-pub const VALID_KEYS: &[&str] = &["trunk_bookmark", "check_command", "strategy"];
+pub const VALID_KEYS: &[&str] = &[
+    "trunk_bookmark",
+    "check_command",
+    "strategy",
+    "check_shell",
+    "check_workdir",
+    "check_env_allow",
+    "check_env_deny",
+    "check_limit_memory",
+    "check_limit_cpu",
+    "check_limit_files",
+    "check_limit_procs",
+    "log_max_bytes",
+    "log_strip_ansi",
+    "check_report",
+    "failure_patterns",
+    "artifacts",
+    "pool_dir",
+    "pool_size",
+    "workspace_dir",
+    "freeze_windows",
+    "landing_windows",
+    "require_approval",
+    "approval_trailers",
+    "description_required",
+    "title_max_length",
+    "description_deny",
+    "description_require",
+    "squash_message",
+    "squash_originals",
+    "landed_message",
+    "landed_trailers",
+];
+
+/// Keys always shown by `jjq config`; other keys are shown only when set.
+pub const CORE_KEYS: &[&str] = &["trunk_bookmark", "check_command", "strategy"];
 
 /// Check if jjq is initialized (metadata bookmark exists).
-pub fn is_initialized() -> Result<bool> {
-    jj::bookmark_exists(JJQ_BOOKMARK)
+pub fn is_initialized(jj: &Jj) -> Result<bool> {
+    jj.bookmark_exists(JJQ_BOOKMARK)
 }
 
-/// Create the jjq metadata branch. Errors if already initialized.
-pub fn initialize() -> Result<()> {
-    // Create new revision parented to root()
-    let change_id = jj::new_rev(&["root()"])?;
-    jj::run_quiet(&["bookmark", "create", "-r", &change_id, JJQ_BOOKMARK])?;
-
-    // Create workspace to set up initial state
-    let temp_dir = TempDir::new()?;
-    let workspace_name = format!("jjq{}", std::process::id());
-
-    jj::workspace_add(
-        temp_dir.path().to_str().unwrap(),
-        &workspace_name,
-        &[JJQ_BOOKMARK],
-    )?;
-
-    // Change to workspace and create last_id file
-    let orig_dir = env::current_dir()?;
-    env::set_current_dir(temp_dir.path())?;
-
-    fs::write("last_id", "0")?;
-    jj::describe("@", "init jjq")?;
-    jj::run_quiet(&["squash"])?;
-
-    env::set_current_dir(&orig_dir)?;
-    jj::workspace_forget(&workspace_name)?;
-
-    Ok(())
+/// Create the jjq metadata branch with the given (key, value) config
+/// settings, in one commit. Errors if already initialized.
+pub fn initialize(jj: &Jj, settings: &[(&str, &str)]) -> Result<()> {
+    let paths: Vec<String> = settings
+        .iter()
+        .map(|(key, _)| format!("config/{}", key))
+        .collect();
+    let mut files = vec![("last_id", "0")];
+    for (path, (_, value)) in paths.iter().zip(settings) {
+        files.push((path, value));
+    }
+    metadata::create(jj, &files, "init jjq")
 }
 
 /// Ensure jjq is initialized, creating metadata branch if needed.
-pub fn ensure_initialized() -> Result<()> {
-    if is_initialized()? {
+pub fn ensure_initialized(jj: &Jj) -> Result<()> {
+    if is_initialized(jj)? {
         return Ok(());
     }
-    initialize()
+    initialize(jj, &[])
 }
 
 /// Get a config value from the metadata branch.
-pub fn get(key: &str) -> Result<Option<String>> {
+pub fn get(jj: &Jj, key: &str) -> Result<Option<String>> {
     let path = format!("config/{}", key);
-    match jj::file_show(&path, JJQ_BOOKMARK) {
+    match jj.file_show(&path, JJQ_BOOKMARK) {
         Ok(value) => Ok(Some(value.trim().to_string())),
         Err(_) => Ok(None),
     }
 }
 
 /// Get a config value with a default.
-pub fn get_or_default(key: &str, default: &str) -> Result<String> {
-    Ok(get(key)?.unwrap_or_else(|| default.to_string()))
+pub fn get_or_default(jj: &Jj, key: &str, default: &str) -> Result<String> {
+    Ok(get(jj, key)?.unwrap_or_else(|| default.to_string()))
 }
 
 /// Get the trunk bookmark name.
-pub fn get_trunk_bookmark() -> Result<String> {
-    get_or_default("trunk_bookmark", DEFAULT_TRUNK_BOOKMARK)
+pub fn get_trunk_bookmark(jj: &Jj) -> Result<String> {
+    get_or_default(jj, "trunk_bookmark", DEFAULT_TRUNK_BOOKMARK)
 }
 
 /// Get the check command (None if not configured).
-pub fn get_check_command() -> Result<Option<String>> {
-    get("check_command")
+pub fn get_check_command(jj: &Jj) -> Result<Option<String>> {
+    get(jj, "check_command")
+}
+
+/// Get the squash strategy's landed description template.
+pub fn get_squash_message(jj: &Jj) -> Result<String> {
+    Ok(get(jj, "squash_message")?
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| DEFAULT_SQUASH_MESSAGE.to_string()))
+}
+
+/// Whether the squash strategy abandons the original commits once landed.
+pub fn get_squash_abandon_originals(jj: &Jj) -> Result<bool> {
+    Ok(get(jj, "squash_originals")?.as_deref() == Some("abandon"))
+}
+
+/// Get the landed description template for the merge and rebase
+/// strategies, if one is configured.
+pub fn get_landed_message(jj: &Jj) -> Result<Option<String>> {
+    Ok(get(jj, "landed_message")?.filter(|v| !v.is_empty()))
+}
+
+/// Whether landed commits get jjq trailers (on unless set to false).
+pub fn get_landed_trailers(jj: &Jj) -> Result<bool> {
+    Ok(get(jj, "landed_trailers")?.as_deref() != Some("false"))
 }
 
 /// Get the merge strategy.
-pub fn get_strategy() -> Result<Strategy> {
-    match get("strategy")? {
+pub fn get_strategy(jj: &Jj) -> Result<Strategy> {
+    match get(jj, "strategy")? {
         Some(value) => Strategy::try_from(value.as_str())
             .map_err(|_| anyhow::anyhow!("invalid strategy value: {}", value)),
         None => Ok(DEFAULT_STRATEGY),
     }
 }
 
+/// Get the shell used to run the check command.
+pub fn get_check_shell(jj: &Jj) -> Result<String> {
+    get_or_default(jj, "check_shell", DEFAULT_CHECK_SHELL)
+}
+
+/// Get the check working directory, relative to the workspace root.
+pub fn get_check_workdir(jj: &Jj) -> Result<Option<String>> {
+    Ok(get(jj, "check_workdir")?.filter(|v| !v.is_empty()))
+}
+
+/// Get the test report path written by the check, relative to the workspace root.
+pub fn get_check_report(jj: &Jj) -> Result<Option<String>> {
+    Ok(get(jj, "check_report")?.filter(|v| !v.is_empty()))
+}
+
+/// Get the regexes identifying failure lines in the run log (one per line).
+pub fn get_failure_patterns(jj: &Jj) -> Result<Vec<Regex>> {
+    let Some(value) = get(jj, "failure_patterns")? else {
+        return Ok(Vec::new());
+    };
+    parse_patterns(&value).map_err(|e| anyhow::anyhow!("invalid failure_patterns value: {}", e))
+}
+
+/// Parse newline-separated regexes, skipping blank lines.
+pub fn parse_patterns(value: &str) -> Result<Vec<Regex>, regex::Error> {
+    value
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(Regex::new)
+        .collect()
+}
+
+/// Get the directory temporary workspaces are created in (None for the
+/// system temp directory). A relative path is resolved against the
+/// repository root.
+pub fn get_workspace_dir(jj: &Jj) -> Result<Option<PathBuf>> {
+    match get(jj, "workspace_dir")?.filter(|v| !v.is_empty()) {
+        Some(dir) => Ok(Some(jj.root().join(dir))),
+        None => Ok(None),
+    }
+}
+
+/// Create the directory for a temporary workspace named `name`, under
+/// `workspace_dir` if configured. The directory name starts with the
+/// workspace name so leftovers are easy to identify.
+pub fn workspace_tempdir(jj: &Jj, name: &str) -> Result<TempDir> {
+    let prefix = format!("{}-", name);
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(&prefix);
+    match get_workspace_dir(jj)? {
+        Some(dir) => {
+            fs::create_dir_all(&dir)?;
+            Ok(builder.tempdir_in(&dir)?)
+        }
+        None => Ok(builder.tempdir()?),
+    }
+}
+
+/// Get the directory holding warm runner workspaces (None if the pool is disabled).
+pub fn get_pool_dir(jj: &Jj) -> Result<Option<String>> {
+    Ok(get(jj, "pool_dir")?.filter(|v| !v.is_empty()))
+}
+
+/// Get the number of warm runner workspaces.
+pub fn get_pool_size(jj: &Jj) -> Result<u64> {
+    Ok(get_limit(jj, "pool_size", |v| v.parse().ok().filter(|n| *n > 0))?.unwrap_or(1))
+}
+
+/// Get a list-valued config key (comma or whitespace separated).
+pub fn get_list(jj: &Jj, key: &str) -> Result<Vec<String>> {
+    Ok(get(jj, key)?.map(|v| parse_list(&v)).unwrap_or_default())
+}
+
+/// Split a list value on commas and whitespace, dropping empty entries.
+pub fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Parse a byte size with an optional K, M or G suffix (powers of 1024).
+pub fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let (digits, multiplier) = match value.char_indices().last()? {
+        (i, 'K' | 'k') => (&value[..i], 1u64 << 10),
+        (i, 'M' | 'm') => (&value[..i], 1u64 << 20),
+        (i, 'G' | 'g') => (&value[..i], 1u64 << 30),
+        _ => (value, 1),
+    };
+    digits.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Get a numeric limit, parsed with `parse`, or None if unset.
+fn get_limit(jj: &Jj, key: &str, parse: fn(&str) -> Option<u64>) -> Result<Option<u64>> {
+    match get(jj, key)? {
+        Some(value) if !value.is_empty() => parse(&value)
+            .map(Some)
+            .ok_or_else(|| anyhow::anyhow!("invalid {} value: {}", key, value)),
+        _ => Ok(None),
+    }
+}
+
+/// Get the check address space limit in bytes.
+pub fn get_check_limit_memory(jj: &Jj) -> Result<Option<u64>> {
+    get_limit(jj, "check_limit_memory", parse_size)
+}
+
+/// Get the check CPU time limit in seconds.
+pub fn get_check_limit_cpu(jj: &Jj) -> Result<Option<u64>> {
+    get_limit(jj, "check_limit_cpu", |v| v.parse().ok())
+}
+
+/// Get the check open file descriptor limit.
+pub fn get_check_limit_files(jj: &Jj) -> Result<Option<u64>> {
+    get_limit(jj, "check_limit_files", |v| v.parse().ok())
+}
+
+/// Get the check process count limit.
+pub fn get_check_limit_procs(jj: &Jj) -> Result<Option<u64>> {
+    get_limit(jj, "check_limit_procs", |v| v.parse().ok())
+}
+
+/// Get the run log size cap in bytes.
+pub fn get_log_max_bytes(jj: &Jj) -> Result<Option<u64>> {
+    get_limit(jj, "log_max_bytes", parse_size)
+}
+
+/// Get whether ANSI escapes are stripped from the persisted run log.
+pub fn get_log_strip_ansi(jj: &Jj) -> Result<bool> {
+    Ok(get(jj, "log_strip_ansi")?.as_deref() == Some("true"))
+}
+
+/// The error for a config key jjq doesn't know.
+pub fn unknown_key(key: &str) -> Error {
+    Error::usage(format!(
+        "unknown config key: {}\nvalid keys: {}",
+        key,
+        VALID_KEYS.join(", ")
+    ))
+}
+
 /// Set a config value on the metadata branch.
-pub fn set(key: &str, value: &str) -> Result<()> {
+pub fn set(jj: &Jj, key: &str, value: &str) -> Result<()> {
     // Validate key
     if !VALID_KEYS.contains(&key) {
-        bail!(
-            "unknown config key: {}\nvalid keys: {}",
-            key,
-            VALID_KEYS.join(", ")
-        );
+        return Err(unknown_key(key).into());
     }
+    let invalid = |reason: &str| -> anyhow::Error {
+        Error::InvalidConfig {
+            key: key.to_string(),
+            value: value.to_string(),
+            reason: reason.to_string(),
+        }
+        .into()
+    };
 
     // Validate strategy values
     if key == "strategy" && Strategy::try_from(value).is_err() {
-        bail!(
-            "invalid value for strategy: {}\nvalid values: rebase, merge",
-            value
-        );
+        return Err(invalid(&format!(
+            "valid values: {}",
+            VALID_STRATEGIES.join(", ")
+        )));
+    }
+
+    if key == "squash_originals" && value != "keep" && value != "abandon" {
+        return Err(invalid("valid values: keep, abandon"));
+    }
+
+    // The check working directory and report path must stay inside the workspace
+    if (key == "check_workdir" || key == "check_report")
+        && (value.starts_with('/') || value.split('/').any(|c| c == ".."))
+    {
+        return Err(invalid("must be a relative path inside the workspace"));
     }
 
-    ensure_initialized()?;
+    // Resource limits must be numeric (sizes also accept K/M/G suffixes);
+    // an empty value clears the limit
+    if (key.starts_with("check_limit_") || key == "log_max_bytes") && !value.is_empty() {
+        let is_size = key == "check_limit_memory" || key == "log_max_bytes";
+        let valid = if is_size {
+            parse_size(value).is_some()
+        } else {
+            value.parse::<u64>().is_ok()
+        };
+        if !valid {
+            return Err(invalid(match is_size {
+                true => "must be a number with optional K, M or G suffix",
+                false => "must be a number",
+            }));
+        }
+    }
 
-    let temp_dir = TempDir::new()?;
-    let workspace_name = format!("jjq-config-{}", std::process::id());
+    // Artifact globs are matched relative to the workspace root
+    if key == "artifacts"
+        && parse_list(value)
+            .iter()
+            .any(|p| p.starts_with('/') || p.split('/').any(|c| c == ".."))
+    {
+        return Err(invalid(
+            "patterns must be relative paths inside the workspace",
+        ));
+    }
 
-    jj::workspace_add(
-        temp_dir.path().to_str().unwrap(),
-        &workspace_name,
-        &[JJQ_BOOKMARK],
-    )?;
+    if (key == "failure_patterns" || key == "description_deny" || key == "description_require")
+        && let Err(e) = parse_patterns(value)
+    {
+        return Err(invalid(&e.to_string()));
+    }
 
-    let orig_dir = env::current_dir()?;
-    env::set_current_dir(temp_dir.path())?;
+    if (key == "pool_size" || key == "title_max_length")
+        && !value.is_empty()
+        && !value.parse::<u64>().is_ok_and(|n| n > 0)
+    {
+        return Err(invalid("must be a positive number"));
+    }
 
-    fs::create_dir_all("config")?;
-    fs::write(format!("config/{}", key), value)?;
-    jj::describe("@", &format!("config: set {}", key))?;
-    jj::run_quiet(&["bookmark", "set", JJQ_BOOKMARK])?;
+    if (key == "freeze_windows" || key == "landing_windows")
+        && let Err(e) = crate::schedule::validate(value)
+    {
+        return Err(invalid(&e.to_string()));
+    }
 
-    env::set_current_dir(&orig_dir)?;
-    jj::workspace_forget(&workspace_name)?;
+    if (key == "log_strip_ansi"
+        || key == "require_approval"
+        || key == "description_required"
+        || key == "landed_trailers")
+        && value != "true"
+        && value != "false"
+    {
+        return Err(invalid("valid values: true, false"));
+    }
 
-    Ok(())
+    ensure_initialized(jj)?;
+
+    metadata::write(
+        jj,
+        &[(&format!("config/{}", key), value)],
+        &format!("config: set {}", key),
+    )
 }
 
 /// Show a one-time hint about configuring jj log filter.
 /// Only shows if: stdout is a TTY, log filter not already configured, hint not shown before.
-pub fn maybe_show_log_hint() -> Result<()> {
+pub fn maybe_show_log_hint(jj: &Jj) -> Result<()> {
     // Skip if not a terminal (unless JJQTEST_FORCE_HINT is set for testing)
     let force_hint = env::var("JJQTEST_FORCE_HINT").is_ok();
     if !force_hint && !std::io::stdout().is_terminal() {
@@ -178,14 +441,14 @@ pub fn maybe_show_log_hint() -> Result<()> {
     }
 
     // Skip if log filter already configured to hide jjq metadata
-    if let Ok(Some(current_log)) = jj::config_get("revsets.log")
+    if let Ok(Some(current_log)) = jj.config_get("revsets.log")
         && current_log.contains(JJQ_BOOKMARK)
     {
         return Ok(());
     }
 
     // Skip if hint already shown (check metadata)
-    if hint_already_shown()? {
+    if hint_already_shown(jj)? {
         return Ok(());
     }
 
@@ -196,46 +459,60 @@ pub fn maybe_show_log_hint() -> Result<()> {
     eprintln!();
 
     // Record hint shown
-    record_hint_shown()?;
+    record_hint_shown(jj)?;
 
     Ok(())
 }
 
 /// Check if the log hint has already been shown.
-fn hint_already_shown() -> Result<bool> {
-    if !is_initialized()? {
+fn hint_already_shown(jj: &Jj) -> Result<bool> {
+    if !is_initialized(jj)? {
         return Ok(false);
     }
-    match jj::file_show("log_hint_shown", JJQ_BOOKMARK) {
+    match jj.file_show("log_hint_shown", JJQ_BOOKMARK) {
         Ok(_) => Ok(true),   // File exists = hint was shown
         Err(_) => Ok(false), // File doesn't exist = hint not shown
     }
 }
 
 /// Record that the log hint has been shown.
-fn record_hint_shown() -> Result<()> {
-    if !is_initialized()? {
+fn record_hint_shown(jj: &Jj) -> Result<()> {
+    if !is_initialized(jj)? {
         return Ok(());
     }
 
-    let temp_dir = TempDir::new()?;
-    let workspace_name = format!("jjq-hint-{}", std::process::id());
-
-    jj::workspace_add(
-        temp_dir.path().to_str().unwrap(),
-        &workspace_name,
-        &[JJQ_BOOKMARK],
-    )?;
-
-    let orig_dir = env::current_dir()?;
-    env::set_current_dir(temp_dir.path())?;
+    metadata::write(jj, &[("log_hint_shown", "1")], "record log hint shown")
+}
 
-    fs::write("log_hint_shown", "1")?;
-    jj::describe("@", "record log hint shown")?;
-    jj::run_quiet(&["bookmark", "set", JJQ_BOOKMARK])?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096"), Some(4096));
+        assert_eq!(parse_size("512K"), Some(512 << 10));
+        assert_eq!(parse_size("2G"), Some(2 << 30));
+        assert_eq!(parse_size("1m"), Some(1 << 20));
+        assert_eq!(parse_size(""), None);
+        assert_eq!(parse_size("G"), None);
+        assert_eq!(parse_size("lots"), None);
+    }
 
-    env::set_current_dir(&orig_dir)?;
-    jj::workspace_forget(&workspace_name)?;
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(
+            parse_list("PATH, HOME  CARGO_*"),
+            ["PATH", "HOME", "CARGO_*"]
+        );
+        assert!(parse_list(" , ").is_empty());
+    }
 
-    Ok(())
+    #[test]
+    fn test_parse_patterns() {
+        let patterns = parse_patterns("error\\[E\n\npanicked at").unwrap();
+        assert_eq!(patterns.len(), 2);
+        assert!(patterns[1].is_match("thread 'main' panicked at src/main.rs"));
+        assert!(parse_patterns("error[E").is_err());
+    }
 }
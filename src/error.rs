@@ -0,0 +1,325 @@
+// ABOUTME: Typed errors for jjq operations, each mapped to a documented exit code.
+// ABOUTME: Serializable so `--json` can report the error variant and its fields.
+
+use serde::Serialize;
+use std::fmt;
+
+use crate::exit_codes;
+use crate::lock::Holder;
+use crate::pause::Pause;
+use crate::schedule::LandingClosed;
+
+/// An error a caller can act on. Errors outside this enum (I/O failures,
+/// invalid settings) are plain `anyhow` errors and exit with code 1.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum Error {
+    /// The current directory (or opened path) is not in a jj repository.
+    NotARepository,
+    NotInitialized,
+    AlreadyInitialized,
+    /// Bad arguments not covered by a more specific variant.
+    Usage {
+        message: String,
+    },
+    /// A sequence ID argument that isn't a number from 1 to 999999.
+    InvalidSequenceId {
+        input: String,
+    },
+    /// A config value rejected by `jjq config`; `reason` says what is
+    /// accepted.
+    InvalidConfig {
+        key: String,
+        value: String,
+        reason: String,
+    },
+    TrunkMissing {
+        bookmark: String,
+    },
+    RevsetNotFound {
+        revset: String,
+    },
+    RevsetAmbiguous {
+        revset: String,
+    },
+    CheckNotConfigured,
+    /// A description in the pushed revisions breaks the description policy;
+    /// `problems` are "<change id>: <problem>".
+    DescriptionRejected {
+        revset: String,
+        problems: Vec<String>,
+    },
+    /// The exact commit is already queued as item `id`.
+    AlreadyQueued {
+        id: u32,
+    },
+    /// Requeue of an item that is in the queue rather than failed.
+    ItemQueued {
+        id: u32,
+    },
+    FailedItemNotFound {
+        id: u32,
+    },
+    /// Item `id` is neither queued nor failed.
+    ItemNotFound {
+        id: u32,
+    },
+    /// No queued or failed item has `change_id` as its candidate.
+    CandidateNotFound {
+        change_id: String,
+    },
+    SequenceExhausted,
+    /// Cancel with no runner processing item `id` (or any item, if `None`).
+    NotRunning {
+        id: Option<u32>,
+    },
+    /// Another process holds the named lock.
+    LockHeld {
+        lock: String,
+        holder: Option<Holder>,
+    },
+    /// The queue is paused; runs refuse to process items.
+    Paused(Pause),
+    /// The landing schedule doesn't allow landing now.
+    LandingClosed(LandingClosed),
+    /// Another runner is processing the queue.
+    RunnerBusy {
+        holder: Option<Holder>,
+    },
+    /// The revision conflicts with trunk.
+    Conflict {
+        revset: String,
+    },
+    /// Queue item `id` conflicted with trunk during a run.
+    MergeConflict {
+        id: u32,
+    },
+    /// With the fast-forward strategy, the revision (queue item `id`, if
+    /// run by the queue) doesn't descend from trunk.
+    NotFastForward {
+        id: Option<u32>,
+    },
+    /// The check command failed. `id` is the queue item, if run by the
+    /// queue; `reason` is "check", "conflicts" or a resource limit.
+    CheckFailed {
+        id: Option<u32>,
+        reason: String,
+    },
+    TrunkMoved,
+    /// Item `id` was cancelled while running, and either returned to the
+    /// queue or marked failed.
+    Cancelled {
+        id: u32,
+        requeued: bool,
+    },
+    /// `run --all` finished with some items failed.
+    Partial {
+        merged: u32,
+        failed: u32,
+    },
+    DoctorFailed,
+    /// A jj command exited unsuccessfully.
+    JjFailed {
+        command: String,
+        stderr: String,
+    },
+}
+
+impl Error {
+    /// Process exit code for this error.
+    pub fn code(&self) -> i32 {
+        match self {
+            Error::NotARepository
+            | Error::NotInitialized
+            | Error::AlreadyInitialized
+            | Error::Usage { .. }
+            | Error::InvalidSequenceId { .. }
+            | Error::InvalidConfig { .. }
+            | Error::TrunkMissing { .. }
+            | Error::RevsetNotFound { .. }
+            | Error::RevsetAmbiguous { .. }
+            | Error::CheckNotConfigured
+            | Error::DescriptionRejected { .. }
+            | Error::AlreadyQueued { .. }
+            | Error::ItemQueued { .. }
+            | Error::FailedItemNotFound { .. }
+            | Error::ItemNotFound { .. }
+            | Error::CandidateNotFound { .. }
+            | Error::SequenceExhausted
+            | Error::NotRunning { .. } => exit_codes::USAGE,
+            Error::LockHeld { .. } => exit_codes::LOCK_HELD,
+            Error::Partial { .. } => exit_codes::PARTIAL,
+            Error::Paused(_) | Error::LandingClosed(_) => exit_codes::PAUSED,
+            Error::RunnerBusy { .. }
+            | Error::Conflict { .. }
+            | Error::MergeConflict { .. }
+            | Error::NotFastForward { .. }
+            | Error::CheckFailed { .. }
+            | Error::TrunkMoved
+            | Error::Cancelled { .. }
+            | Error::DoctorFailed => exit_codes::CONFLICT,
+            Error::JjFailed { .. } => exit_codes::FAILURE,
+        }
+    }
+
+    /// Shorthand for a `Usage` error.
+    pub fn usage(message: impl Into<String>) -> Error {
+        Error::Usage {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotARepository => write!(f, "not in a jj repository"),
+            Error::NotInitialized => write!(f, "jjq is not initialized. Run 'jjq init' first."),
+            Error::AlreadyInitialized => write!(
+                f,
+                "jjq is already initialized. Use 'jjq config' to change settings."
+            ),
+            Error::Usage { message } => write!(f, "{}", message),
+            Error::InvalidSequenceId { input } if input.is_empty() => {
+                write!(f, "invalid sequence ID: empty")
+            }
+            Error::InvalidSequenceId { input } if !input.chars().all(|c| c.is_ascii_digit()) => {
+                write!(f, "invalid sequence ID: '{}' (must be numeric)", input)
+            }
+            Error::InvalidSequenceId { input } => {
+                write!(f, "invalid sequence ID: {} (must be 1-999999)", input)
+            }
+            Error::InvalidConfig { key, value, reason } => {
+                write!(f, "invalid value for {}: {}\n{}", key, value, reason)
+            }
+            Error::TrunkMissing { bookmark } => {
+                write!(f, "trunk bookmark '{}' not found", bookmark)
+            }
+            Error::RevsetNotFound { revset } => write!(f, "revset '{}' not found", revset),
+            Error::RevsetAmbiguous { revset } => {
+                write!(f, "revset '{}' resolves to multiple revisions", revset)
+            }
+            Error::CheckNotConfigured => write!(
+                f,
+                "check_command not configured (use 'jjq config check_command <cmd>')"
+            ),
+            Error::DescriptionRejected { revset, problems } => write!(
+                f,
+                "revision '{}' rejected by description policy ({} problem(s)); use --force to push anyway",
+                revset,
+                problems.len()
+            ),
+            Error::AlreadyQueued { .. } => write!(f, "revision already queued"),
+            Error::ItemQueued { id } => write!(f, "item {} is already queued", id),
+            Error::FailedItemNotFound { id } => write!(f, "failed item {} not found", id),
+            Error::ItemNotFound { id } => write!(f, "item {} not found in queue or failed", id),
+            Error::CandidateNotFound { change_id } => {
+                write!(f, "no item found with candidate change ID '{}'", change_id)
+            }
+            Error::SequenceExhausted => write!(f, "sequence ID exhausted (at 999999)"),
+            Error::NotRunning { id: Some(id) } => write!(f, "item {} is not running", id),
+            Error::NotRunning { id: None } => write!(f, "no item is running"),
+            Error::LockHeld { lock, holder } => {
+                if lock == "id" {
+                    write!(f, "could not acquire sequence ID lock")?;
+                } else {
+                    write!(f, "{} lock unavailable", lock)?;
+                }
+                match holder {
+                    Some(holder) => write!(f, " (held by {})", holder),
+                    None if lock == "id" => write!(f, " (another process may be pushing)"),
+                    None => Ok(()),
+                }
+            }
+            Error::Paused(pause) => write!(f, "queue is {}", pause),
+            Error::LandingClosed(closed) => write!(f, "landing closed: {}", closed),
+            Error::RunnerBusy { holder } => {
+                write!(f, "run lock unavailable")?;
+                match holder {
+                    Some(holder) => write!(f, " (held by {})", holder),
+                    None => Ok(()),
+                }
+            }
+            Error::Conflict { .. } => write!(f, "revision conflicts with trunk"),
+            Error::MergeConflict { id } => write!(f, "merge {} has conflicts", id),
+            Error::NotFastForward { id: Some(id) } => {
+                write!(f, "merge {} is not a fast-forward of trunk", id)
+            }
+            Error::NotFastForward { id: None } => {
+                write!(f, "revision is not a fast-forward of trunk")
+            }
+            Error::CheckFailed { id: Some(id), .. } => write!(f, "merge {} check failed", id),
+            Error::CheckFailed { id: None, reason } => match reason.as_str() {
+                "check" => write!(f, "check failed"),
+                "conflicts" => write!(f, "check failed: conflicts"),
+                limit => write!(f, "check failed ({})", limit),
+            },
+            Error::TrunkMoved => write!(f, "trunk moved during run"),
+            Error::Cancelled { id, .. } => write!(f, "merge {} cancelled", id),
+            Error::Partial { merged, failed } => {
+                write!(f, "processed {} item(s), {} failed", merged, failed)
+            }
+            Error::DoctorFailed => write!(f, "doctor found issues"),
+            Error::JjFailed { command, stderr } => {
+                write!(f, "jj {} failed: {}", command, stderr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Exit code for any error: the typed error's code, or FAILURE.
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    err.downcast_ref::<Error>()
+        .map_or(exit_codes::FAILURE, Error::code)
+}
+
+/// JSON object describing an error for `--json` output: the variant name
+/// under "error", its fields, the exit code and the message.
+pub fn to_json(err: &anyhow::Error) -> serde_json::Value {
+    let mut value = match err.downcast_ref::<Error>() {
+        Some(typed) => serde_json::to_value(typed).unwrap_or_default(),
+        None => serde_json::json!({ "error": "other" }),
+    };
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("code".to_string(), exit_code(err).into());
+        obj.insert("message".to_string(), err.to_string().into());
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_json_includes_variant_fields_and_code() {
+        let err: anyhow::Error = Error::TrunkMissing {
+            bookmark: "main".to_string(),
+        }
+        .into();
+        assert_eq!(
+            to_json(&err),
+            serde_json::json!({
+                "error": "trunk_missing",
+                "bookmark": "main",
+                "code": 10,
+                "message": "trunk bookmark 'main' not found",
+            })
+        );
+
+        let err: anyhow::Error = Error::InvalidConfig {
+            key: "strategy".to_string(),
+            value: "octopus".to_string(),
+            reason: "valid values: merge, rebase".to_string(),
+        }
+        .into();
+        assert_eq!(to_json(&err)["error"], "invalid_config");
+        assert_eq!(to_json(&err)["code"], 10);
+
+        let other = anyhow::anyhow!("disk full");
+        assert_eq!(to_json(&other)["error"], "other");
+        assert_eq!(to_json(&other)["code"], 1);
+    }
+}
@@ -2,350 +2,507 @@
 // ABOUTME: Provides functions to execute jj commands and parse their output.
 
 use anyhow::{bail, Context, Result};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Output};
 use std::sync::OnceLock;
 
-/// Get a jj config value, returning None if not set.
-pub fn config_get(key: &str) -> Result<Option<String>> {
-    let output = run(&["config", "get", key])?;
-    if output.status.success() {
-        Ok(Some(String::from_utf8_lossy(&output.stdout).trim().to_string()))
-    } else {
-        Ok(None)
-    }
+use crate::error::Error;
+
+/// Run jj against the workspace rooted at `dir` (via `-R`, with relative
+/// paths resolved against it). The process itself never changes directory,
+/// so several workspaces can be driven from one process.
+fn command(dir: &Path, args: &[&str]) -> Result<Output> {
+    Command::new("jj")
+        .arg("--color=never")
+        .arg("-R")
+        .arg(dir)
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .context("failed to execute jj")
 }
 
-/// Set a jj config value at repo scope.
-pub fn config_set_repo(key: &str, value: &str) -> Result<()> {
-    run_quiet(&["config", "set", "--repo", key, value])
+/// Execute a jj command in the workspace at `workspace` and return the output.
+pub fn run_in(workspace: &Path, args: &[&str]) -> Result<Output> {
+    command(workspace, args)
 }
 
-/// Execute a jj command and return the output.
-pub fn run(args: &[&str]) -> Result<Output> {
-    let mut full_args = vec!["--color=never"];
-    full_args.extend_from_slice(args);
-    let output = Command::new("jj")
-        .args(&full_args)
-        .output()
-        .context("failed to execute jj")?;
-    Ok(output)
+/// Execute a jj command in the workspace at `workspace` silently, only
+/// returning error on failure.
+pub fn run_quiet_in(workspace: &Path, args: &[&str]) -> Result<()> {
+    check_quiet(run_in(workspace, args)?, args)
 }
 
-/// Execute a jj command and return stdout as string, failing on non-zero exit.
-pub fn run_ok(args: &[&str]) -> Result<String> {
-    let output = run(args)?;
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!("jj {} failed: {}", args.join(" "), stderr.trim());
+/// Error for a jj command that exited unsuccessfully.
+fn failed(command: &str, output: &Output) -> anyhow::Error {
+    Error::JjFailed {
+        command: command.to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
     }
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    .into()
 }
 
-/// Execute a jj command silently, only returning error on failure.
-pub fn run_quiet(args: &[&str]) -> Result<()> {
-    let output = run(args)?;
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!("jj {} failed: {}", args.join(" "), stderr.trim());
+/// The error for a `jj log -r <revset>` that failed: `RevsetNotFound` if jj
+/// couldn't resolve the revset, otherwise `JjFailed`.
+fn log_failed(revset: &str, output: &Output) -> anyhow::Error {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let unresolved = [
+        "doesn't exist",
+        "Failed to parse revset",
+        "Failed to resolve revset",
+    ]
+    .iter()
+    .any(|marker| stderr.contains(marker));
+    match unresolved {
+        true => not_found(revset),
+        false => failed("log", output),
     }
-    Ok(())
 }
 
-/// Verify we're in a jj repository.
-pub fn verify_repo() -> Result<()> {
-    let output = run(&["root"])?;
+fn not_found(revset: &str) -> anyhow::Error {
+    Error::RevsetNotFound {
+        revset: revset.to_string(),
+    }
+    .into()
+}
+
+fn check_quiet(output: Output, args: &[&str]) -> Result<()> {
     if !output.status.success() {
-        bail!("not in a jj repository");
+        return Err(failed(&args.join(" "), &output));
     }
     Ok(())
 }
 
-/// Get the repository root path (cached after first call).
-pub fn repo_root() -> Result<PathBuf> {
-    static ROOT: OnceLock<PathBuf> = OnceLock::new();
-    if let Some(root) = ROOT.get() {
-        return Ok(root.clone());
+/// Ask jj for the root of the workspace containing `dir`, which need not be
+/// the root itself.
+fn find_root(dir: &Path) -> Result<PathBuf> {
+    let output = Command::new("jj")
+        .args(["--color=never", "root"])
+        .current_dir(dir)
+        .output()
+        .context("failed to execute jj")?;
+    if !output.status.success() {
+        return Err(Error::NotARepository.into());
     }
-    let output = run_ok(&["root"])?;
-    let root = PathBuf::from(output.trim());
-    let _ = ROOT.set(root.clone());
-    Ok(root)
+    Ok(PathBuf::from(
+        String::from_utf8_lossy(&output.stdout).trim(),
+    ))
 }
 
-/// Check if a bookmark exists.
-pub fn bookmark_exists(name: &str) -> Result<bool> {
-    let output = run_ok(&[
-        "bookmark",
-        "list",
-        "-r",
-        &format!("bookmarks(exact:{})", name),
-        "-T",
-        "name",
-    ])?;
-    Ok(!output.trim().is_empty())
+/// Edit a revision in the workspace at `workspace`.
+pub fn edit_in(workspace: &Path, rev: &str) -> Result<()> {
+    run_quiet_in(workspace, &["edit", rev])
 }
 
-/// Create a bookmark at a revision.
-pub fn bookmark_create(name: &str, rev: &str) -> Result<()> {
-    run_quiet(&["bookmark", "create", "-r", rev, name])
+/// Replace the working copy's contents in `workspace` with those of `rev`.
+pub fn restore_from_in(workspace: &Path, rev: &str) -> Result<()> {
+    run_quiet_in(workspace, &["restore", "--from", rev])
 }
 
-/// Delete a bookmark.
-pub fn bookmark_delete(name: &str) -> Result<()> {
-    run_quiet(&["bookmark", "delete", name])
+/// Update a revision's description from the workspace at `workspace`, so
+/// that workspace's working copy is snapshotted first.
+pub fn describe_in(workspace: &Path, rev: &str, message: &str) -> Result<()> {
+    run_quiet_in(workspace, &["describe", "-r", rev, "-m", message])
 }
 
-/// Check if the jj binary supports --allow-protected on bookmark move.
-fn supports_allow_protected() -> bool {
-    static SUPPORTED: OnceLock<bool> = OnceLock::new();
-    *SUPPORTED.get_or_init(|| {
-        let Ok(output) = run(&["bookmark", "move", "-h"]) else {
-            return false;
-        };
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        stdout.contains("allow-protected")
-    })
+/// Start a new working-copy commit on `parents` in the workspace at `workspace`.
+pub fn new_in(workspace: &Path, parents: &[&str]) -> Result<()> {
+    let mut args = vec!["new"];
+    args.extend_from_slice(parents);
+    run_quiet_in(workspace, &args)
 }
 
-/// Move a bookmark from one revision to another (compare-and-swap).
-/// Uses --allow-protected if the jj binary supports it.
-pub fn bookmark_move(name: &str, from: &str, to: &str) -> Result<()> {
-    let mut args = vec!["bookmark", "move"];
-    if supports_allow_protected() {
-        args.push("--allow-protected");
-    }
-    args.extend_from_slice(&["--from", from, "--to", to, name]);
-    run_quiet(&args)
+/// Bring the workspace at `workspace` up to date if other operations
+/// rewrote its working-copy commit.
+pub fn workspace_update_stale(workspace: &Path) -> Result<()> {
+    run_quiet_in(workspace, &["workspace", "update-stale"])
 }
 
-/// List bookmarks matching a glob pattern.
-pub fn bookmark_list_glob(pattern: &str) -> Result<Vec<String>> {
-    let output = run_ok(&[
-        "bookmark",
-        "list",
-        "-r",
-        &format!("bookmarks(glob:\"{}\")", pattern),
-        "-T",
-        "name ++ \"\\n\"",
-    ])?;
-    Ok(output
-        .lines()
-        .filter(|s| !s.is_empty())
-        .map(|s| s.to_string())
-        .collect())
+/// A jj repository: every command that acts on the repository rather than
+/// on one workspace runs against its root.
+#[derive(Debug, Clone)]
+pub struct Jj {
+    root: PathBuf,
 }
 
-/// List all local bookmark names.
-pub fn list_bookmarks() -> Result<Vec<String>> {
-    let output = run_ok(&["bookmark", "list", "-T", "name ++ \"\\n\""])?;
-    Ok(output
-        .lines()
-        .filter(|s| !s.is_empty())
-        .map(|s| s.to_string())
-        .collect())
-}
+impl Jj {
+    /// The repository containing `path`, which need not be its root.
+    pub fn open(path: &Path) -> Result<Jj> {
+        Ok(Jj {
+            root: find_root(path)?,
+        })
+    }
 
-/// Resolve a revset to a single change ID.
-pub fn resolve_revset(revset: &str) -> Result<String> {
-    let output = run(&["log", "-r", revset, "--no-graph", "-T", "change_id.short()"])?;
-    if !output.status.success() {
-        bail!("revset '{}' not found", revset);
+    /// Root of the repository's workspace.
+    pub fn root(&self) -> &Path {
+        &self.root
     }
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let change_id = stdout.trim();
-    if change_id.is_empty() {
-        bail!("revset '{}' not found", revset);
+
+    /// Get a jj config value, returning None if not set.
+    pub fn config_get(&self, key: &str) -> Result<Option<String>> {
+        let output = self.run(&["config", "get", key])?;
+        if output.status.success() {
+            Ok(Some(
+                String::from_utf8_lossy(&output.stdout).trim().to_string(),
+            ))
+        } else {
+            Ok(None)
+        }
     }
-    // Check for multiple matches (output would have multiple lines)
-    if change_id.contains('\n') {
-        bail!("revset '{}' resolves to multiple revisions", revset);
+
+    /// The jj user name, or the login name if jj has none, for recording who
+    /// did something.
+    pub fn user_name(&self) -> String {
+        self.config_get("user.name")
+            .ok()
+            .flatten()
+            .filter(|name| !name.is_empty())
+            .or_else(|| std::env::var("USER").ok())
+            .unwrap_or_else(|| "unknown".to_string())
     }
-    Ok(change_id.to_string())
-}
 
-/// Resolve a revset to both change ID and commit ID.
-pub fn resolve_revset_full(revset: &str) -> Result<(String, String)> {
-    let output = run(&[
-        "log", "-r", revset, "--no-graph", "-T",
-        "change_id.short() ++ \" \" ++ commit_id",
-    ])?;
-    if !output.status.success() {
-        bail!("revset '{}' not found", revset);
+    /// Set a jj config value at repo scope.
+    pub fn config_set_repo(&self, key: &str, value: &str) -> Result<()> {
+        self.run_quiet(&["config", "set", "--repo", key, value])
     }
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let line = stdout.trim();
-    if line.is_empty() {
-        bail!("revset '{}' not found", revset);
+
+    /// Execute a jj command in the repository and return the output.
+    pub fn run(&self, args: &[&str]) -> Result<Output> {
+        command(&self.root, args)
     }
-    if line.contains('\n') {
-        bail!("revset '{}' resolves to multiple revisions", revset);
+
+    /// Execute a jj command and return stdout as string, failing on non-zero exit.
+    pub fn run_ok(&self, args: &[&str]) -> Result<String> {
+        let output = self.run(args)?;
+        if !output.status.success() {
+            return Err(failed(&args.join(" "), &output));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
     }
-    let (change_id, commit_id) = line.split_once(' ')
-        .ok_or_else(|| anyhow::anyhow!("unexpected output format from jj log"))?;
-    Ok((change_id.to_string(), commit_id.to_string()))
-}
 
-/// Get the commit ID for a revision.
-pub fn get_commit_id(revset: &str) -> Result<String> {
-    run_ok(&["log", "-r", revset, "--no-graph", "-T", "commit_id"])
-        .map(|s| s.trim().to_string())
-}
+    /// Execute a jj command silently, only returning error on failure.
+    pub fn run_quiet(&self, args: &[&str]) -> Result<()> {
+        check_quiet(self.run(args)?, args)
+    }
 
-/// Get the full description of a revision.
-pub fn get_description(revset: &str) -> Result<String> {
-    run_ok(&["log", "-r", revset, "--no-graph", "-T", "description"])
-}
+    /// Check if a bookmark exists.
+    pub fn bookmark_exists(&self, name: &str) -> Result<bool> {
+        let output = self.run_ok(&[
+            "bookmark",
+            "list",
+            "-r",
+            &format!("bookmarks(exact:{})", name),
+            "-T",
+            "name",
+        ])?;
+        Ok(!output.trim().is_empty())
+    }
 
-/// Check if a revision has conflicts.
-pub fn has_conflicts(revset: &str) -> Result<bool> {
-    let output = run_ok(&[
-        "log",
-        "-r",
-        revset,
-        "--no-graph",
-        "-T",
-        "if(conflict, \"yes\")",
-    ])?;
-    Ok(!output.trim().is_empty())
-}
+    /// Create a bookmark at a revision.
+    pub fn bookmark_create(&self, name: &str, rev: &str) -> Result<()> {
+        self.run_quiet(&["bookmark", "create", "-r", rev, name])
+    }
 
-/// List conflicting file paths in a revision.
-pub fn conflict_paths(revset: &str) -> Result<Vec<String>> {
-    let output = run_ok(&[
-        "log",
-        "-r",
-        revset,
-        "--no-graph",
-        "-T",
-        r#"conflicted_files.map(|e| e.path() ++ "\n").join("")"#,
-    ])?;
-    Ok(output
-        .lines()
-        .filter(|s| !s.is_empty())
-        .map(|s| s.to_string())
-        .collect())
-}
+    /// Delete a bookmark.
+    pub fn bookmark_delete(&self, name: &str) -> Result<()> {
+        self.run_quiet(&["bookmark", "delete", name])
+    }
 
-/// Check if two revisions have the same tree (no diff between them).
-pub fn trees_match(from: &str, to: &str) -> Result<bool> {
-    let output = run_ok(&["diff", "--from", from, "--to", to, "--summary"])?;
-    Ok(output.trim().is_empty())
-}
+    /// Check if the jj binary supports --allow-protected on bookmark move.
+    fn supports_allow_protected(&self) -> bool {
+        static SUPPORTED: OnceLock<bool> = OnceLock::new();
+        *SUPPORTED.get_or_init(|| {
+            let Ok(output) = self.run(&["bookmark", "move", "-h"]) else {
+                return false;
+            };
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            stdout.contains("allow-protected")
+        })
+    }
 
-/// Create a new revision with given parent(s).
-pub fn new_rev(parents: &[&str]) -> Result<String> {
-    let mut args = vec!["new", "--no-edit"];
-    for p in parents {
-        args.push("-r");
-        args.push(p);
+    /// Move a bookmark from one revision to another (compare-and-swap).
+    /// Uses --allow-protected if the jj binary supports it.
+    pub fn bookmark_move(&self, name: &str, from: &str, to: &str) -> Result<()> {
+        let mut args = vec!["bookmark", "move"];
+        if self.supports_allow_protected() {
+            args.push("--allow-protected");
+        }
+        args.extend_from_slice(&["--from", from, "--to", to, name]);
+        self.run_quiet(&args)
     }
-    let output = run(&args)?;
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!("jj new failed: {}", stderr.trim());
+
+    /// List bookmarks matching a glob pattern.
+    pub fn bookmark_list_glob(&self, pattern: &str) -> Result<Vec<String>> {
+        let output = self.run_ok(&[
+            "bookmark",
+            "list",
+            "-r",
+            &format!("bookmarks(glob:\"{}\")", pattern),
+            "-T",
+            "name ++ \"\\n\"",
+        ])?;
+        Ok(output
+            .lines()
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+            .collect())
     }
-    // jj outputs status messages to stderr
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    // Parse change_id from output like "Created new commit xopxuxzw ..."
-    for line in stderr.lines() {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        // "Created new commit <change_id> ..."
-        if parts.len() >= 4 && parts[0] == "Created" && parts[1] == "new" && parts[2] == "commit" {
-            return Ok(parts[3].to_string());
+
+    /// List all local bookmark names.
+    pub fn list_bookmarks(&self) -> Result<Vec<String>> {
+        let output = self.run_ok(&["bookmark", "list", "-T", "name ++ \"\\n\""])?;
+        Ok(output
+            .lines()
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+            .collect())
+    }
+
+    /// Resolve a revset to a single change ID.
+    pub fn resolve_revset(&self, revset: &str) -> Result<String> {
+        let output = self.run(&["log", "-r", revset, "--no-graph", "-T", "change_id.short()"])?;
+        if !output.status.success() {
+            return Err(log_failed(revset, &output));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let change_id = stdout.trim();
+        if change_id.is_empty() {
+            return Err(not_found(revset));
+        }
+        // Check for multiple matches (output would have multiple lines)
+        if change_id.contains('\n') {
+            return Err(Error::RevsetAmbiguous {
+                revset: revset.to_string(),
+            }
+            .into());
         }
+        Ok(change_id.to_string())
     }
-    bail!("failed to parse change ID from jj new output: {}", stderr)
-}
 
-/// Describe a revision.
-pub fn describe(rev: &str, message: &str) -> Result<()> {
-    run_quiet(&["desc", "-r", rev, "-m", message])
-}
+    /// Resolve a revset to both change ID and commit ID.
+    pub fn resolve_revset_full(&self, revset: &str) -> Result<(String, String)> {
+        let output = self.run(&[
+            "log",
+            "-r",
+            revset,
+            "--no-graph",
+            "-T",
+            "change_id.short() ++ \" \" ++ commit_id",
+        ])?;
+        if !output.status.success() {
+            return Err(log_failed(revset, &output));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let line = stdout.trim();
+        if line.is_empty() {
+            return Err(not_found(revset));
+        }
+        if line.contains('\n') {
+            return Err(Error::RevsetAmbiguous {
+                revset: revset.to_string(),
+            }
+            .into());
+        }
+        let (change_id, commit_id) = line
+            .split_once(' ')
+            .ok_or_else(|| anyhow::anyhow!("unexpected output format from jj log"))?;
+        Ok((change_id.to_string(), commit_id.to_string()))
+    }
 
-/// Abandon a revision.
-pub fn abandon(rev: &str) -> Result<()> {
-    run_quiet(&["abandon", rev])
-}
+    /// Get the commit ID for a revision.
+    pub fn get_commit_id(&self, revset: &str) -> Result<String> {
+        self.run_ok(&["log", "-r", revset, "--no-graph", "-T", "commit_id"])
+            .map(|s| s.trim().to_string())
+    }
 
-/// Duplicate the commit range destination..revset onto the destination,
-/// returning all new change IDs (the last one is the tip/candidate).
-/// This handles commit chains: if revset has ancestors between it and
-/// destination, those intermediate commits are also duplicated.
-/// Parses stderr for: "Duplicated <hash> as <new_change_id> <new_hash> ..."
-pub fn duplicate_onto(revset: &str, destination: &str) -> Result<Vec<String>> {
-    let range = format!("{}..{}", destination, revset);
-    let output = run(&["duplicate", &range, "--onto", destination])?;
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!("jj duplicate failed: {}", stderr.trim());
+    /// Get the full description of a revision.
+    pub fn get_description(&self, revset: &str) -> Result<String> {
+        self.run_ok(&["log", "-r", revset, "--no-graph", "-T", "description"])
     }
-    // jj duplicate outputs to stderr; one line per duplicated commit.
-    // Collect all duplicated change IDs (last one is the tip).
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    let mut change_ids = Vec::new();
-    for line in stderr.lines() {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        // "Duplicated <old_hash> as <new_change_id> <new_hash> ..."
-        if parts.len() >= 4 && parts[0] == "Duplicated" && parts[2] == "as" {
-            change_ids.push(parts[3].to_string());
+
+    /// Get the short change ID and full description of every revision in
+    /// `revset`, in `jj log` order.
+    pub fn descriptions(&self, revset: &str) -> Result<Vec<(String, String)>> {
+        let output = self.run_ok(&[
+            "log",
+            "-r",
+            revset,
+            "--no-graph",
+            "-T",
+            "change_id.short() ++ \"\\0\" ++ description ++ \"\\0\"",
+        ])?;
+        let fields: Vec<&str> = output.split('\0').collect();
+        Ok(fields
+            .chunks_exact(2)
+            .map(|pair| (pair[0].to_string(), pair[1].to_string()))
+            .collect())
+    }
+
+    /// Check if `ancestor` is an ancestor of (or the same revision as)
+    /// `descendant`.
+    pub fn is_ancestor(&self, ancestor: &str, descendant: &str) -> Result<bool> {
+        let revset = format!("({}) & ::({})", ancestor, descendant);
+        let output = self.run_ok(&["log", "-r", &revset, "--no-graph", "-T", "commit_id"])?;
+        Ok(!output.trim().is_empty())
+    }
+
+    /// Check if a revision has conflicts.
+    pub fn has_conflicts(&self, revset: &str) -> Result<bool> {
+        let output = self.run_ok(&[
+            "log",
+            "-r",
+            revset,
+            "--no-graph",
+            "-T",
+            "if(conflict, \"yes\")",
+        ])?;
+        Ok(!output.trim().is_empty())
+    }
+
+    /// List conflicting file paths in a revision.
+    pub fn conflict_paths(&self, revset: &str) -> Result<Vec<String>> {
+        let output = self.run_ok(&[
+            "log",
+            "-r",
+            revset,
+            "--no-graph",
+            "-T",
+            r#"conflicted_files.map(|e| e.path() ++ "\n").join("")"#,
+        ])?;
+        Ok(output
+            .lines()
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+            .collect())
+    }
+
+    /// Check if two revisions have the same tree (no diff between them).
+    pub fn trees_match(&self, from: &str, to: &str) -> Result<bool> {
+        let output = self.run_ok(&["diff", "--from", from, "--to", to, "--summary"])?;
+        Ok(output.trim().is_empty())
+    }
+
+    /// Create a new revision with given parent(s).
+    pub fn new_rev(&self, parents: &[&str]) -> Result<String> {
+        let mut args = vec!["new", "--no-edit"];
+        for p in parents {
+            args.push("-r");
+            args.push(p);
+        }
+        let output = self.run(&args)?;
+        if !output.status.success() {
+            return Err(failed("new", &output));
+        }
+        // jj outputs status messages to stderr
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // Parse change_id from output like "Created new commit xopxuxzw ..."
+        for line in stderr.lines() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            // "Created new commit <change_id> ..."
+            if parts.len() >= 4
+                && parts[0] == "Created"
+                && parts[1] == "new"
+                && parts[2] == "commit"
+            {
+                return Ok(parts[3].to_string());
+            }
         }
+        bail!("failed to parse change ID from jj new output: {}", stderr)
     }
-    if change_ids.is_empty() {
-        bail!(
-            "failed to parse change ID from jj duplicate output: {}",
-            stderr.trim()
-        );
+
+    /// Describe a revision.
+    pub fn describe(&self, rev: &str, message: &str) -> Result<()> {
+        self.run_quiet(&["desc", "-r", rev, "-m", message])
     }
-    Ok(change_ids)
-}
 
-/// Edit a revision (set it as working copy in current workspace).
-pub fn edit(rev: &str) -> Result<()> {
-    run_quiet(&["edit", rev])
-}
+    /// Abandon a revision.
+    pub fn abandon(&self, rev: &str) -> Result<()> {
+        self.run_quiet(&["abandon", rev])
+    }
 
-/// Rebase a revision and its ancestors (up to destination) onto the destination.
-/// Uses `jj rebase -b` which rebases the "branch" — all revisions in the
-/// range (destination..source) plus their descendants.
-pub fn rebase_branch_onto(source: &str, destination: &str) -> Result<()> {
-    run_quiet(&["rebase", "-b", source, "-d", destination])
-}
+    /// Duplicate the commit range destination..revset onto the destination,
+    /// returning all new change IDs (the last one is the tip/candidate).
+    /// This handles commit chains: if revset has ancestors between it and
+    /// destination, those intermediate commits are also duplicated.
+    /// Parses stderr for: "Duplicated <hash> as <new_change_id> <new_hash> ..."
+    pub fn duplicate_onto(&self, revset: &str, destination: &str) -> Result<Vec<String>> {
+        let range = format!("{}..{}", destination, revset);
+        let output = self.run(&["duplicate", &range, "--onto", destination])?;
+        if !output.status.success() {
+            return Err(failed("duplicate", &output));
+        }
+        // jj duplicate outputs to stderr; one line per duplicated commit.
+        // Collect all duplicated change IDs (last one is the tip).
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let mut change_ids = Vec::new();
+        for line in stderr.lines() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            // "Duplicated <old_hash> as <new_change_id> <new_hash> ..."
+            if parts.len() >= 4 && parts[0] == "Duplicated" && parts[2] == "as" {
+                change_ids.push(parts[3].to_string());
+            }
+        }
+        if change_ids.is_empty() {
+            bail!(
+                "failed to parse change ID from jj duplicate output: {}",
+                stderr.trim()
+            );
+        }
+        Ok(change_ids)
+    }
 
-/// Show file contents from a revision.
-pub fn file_show(path: &str, rev: &str) -> Result<String> {
-    run_ok(&["file", "show", path, "-r", rev])
-}
+    /// Rebase a revision and its ancestors (up to destination) onto the destination.
+    /// Uses `jj rebase -b` which rebases the "branch" — all revisions in the
+    /// range (destination..source) plus their descendants.
+    pub fn rebase_branch_onto(&self, source: &str, destination: &str) -> Result<()> {
+        self.run_quiet(&["rebase", "-b", source, "-d", destination])
+    }
 
-/// Create a workspace.
-pub fn workspace_add(path: &str, name: &str, parents: &[&str]) -> Result<()> {
-    let mut args = vec!["workspace", "add"];
-    for p in parents {
-        args.push("-r");
-        args.push(p);
-    }
-    args.push("--name");
-    args.push(name);
-    args.push(path);
-    run_quiet(&args)
-}
+    /// Show file contents from a revision.
+    pub fn file_show(&self, path: &str, rev: &str) -> Result<String> {
+        self.run_ok(&["file", "show", path, "-r", rev])
+    }
 
-/// Forget a workspace, updating stale state first if needed.
-pub fn workspace_forget(name: &str) -> Result<()> {
-    let output = run(&["workspace", "forget", name])?;
-    if output.status.success() {
-        return Ok(());
+    /// List which of `paths` exist in a revision.
+    pub fn file_list(&self, rev: &str, paths: &[&str]) -> Result<Vec<String>> {
+        let mut args = vec!["file", "list", "-r", rev, "--"];
+        args.extend(paths);
+        Ok(self.run_ok(&args)?.lines().map(str::to_string).collect())
     }
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    if stderr.contains("stale") {
-        let _ = run(&["workspace", "update-stale"]);
-        return run_quiet(&["workspace", "forget", name]);
+
+    /// Create a workspace.
+    pub fn workspace_add(&self, path: &str, name: &str, parents: &[&str]) -> Result<()> {
+        let mut args = vec!["workspace", "add"];
+        for p in parents {
+            args.push("-r");
+            args.push(p);
+        }
+        args.push("--name");
+        args.push(name);
+        args.push(path);
+        self.run_quiet(&args)
     }
-    bail!("jj workspace forget {} failed: {}", name, stderr.trim())
-}
 
-/// List all workspaces (raw output from jj workspace list).
-pub fn workspace_list() -> Result<String> {
-    run_ok(&["workspace", "list"])
-}
+    /// Forget a workspace, updating stale state first if needed.
+    pub fn workspace_forget(&self, name: &str) -> Result<()> {
+        let output = self.run(&["workspace", "forget", name])?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("stale") {
+            let _ = self.run(&["workspace", "update-stale"]);
+            return self.run_quiet(&["workspace", "forget", name]);
+        }
+        Err(Error::JjFailed {
+            command: format!("workspace forget {}", name),
+            stderr: stderr.trim().to_string(),
+        }
+        .into())
+    }
 
+    /// List all workspaces (raw output from jj workspace list).
+    pub fn workspace_list(&self) -> Result<String> {
+        self.run_ok(&["workspace", "list"])
+    }
+}
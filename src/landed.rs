@@ -0,0 +1,212 @@
+// ABOUTME: Descriptions of landed commits, from the landed_message template and jjq trailers.
+// ABOUTME: Also records landings whose commits carry no trailers so status can still find them.
+
+use anyhow::Result;
+use std::time::Duration;
+
+use crate::config::{JJQ_BOOKMARK, Strategy};
+use crate::jj::Jj;
+use crate::metadata;
+use crate::runner::format_duration;
+
+/// Description a merge-strategy commit gets when no template is configured.
+/// `status` recognizes landed merges by this prefix.
+pub const MERGE_MESSAGE_PREFIX: &str = "Success: merge ";
+
+/// Number of trailer-less landings remembered for `status`.
+const RECORD_HISTORY: usize = 100;
+
+/// Metadata file listing trailer-less landings, one "<full change id> <id>"
+/// line each, oldest first.
+const RECORD_FILE: &str = "landed";
+
+/// What a landed commit's description template can refer to.
+#[derive(Debug)]
+pub struct Landed<'a> {
+    pub id: u32,
+    pub strategy: Strategy,
+    /// Full description of the candidate as pushed.
+    pub description: &'a str,
+    pub check_duration: Duration,
+    /// Change IDs of trunk before and after landing.
+    pub trunk_before: &'a str,
+    pub trunk_after: &'a str,
+    /// Who pushed the item; empty if not recorded.
+    pub pusher: &'a str,
+}
+
+impl Landed<'_> {
+    /// Fill the landed-message placeholders in `template`. Substituted
+    /// values are not scanned again, so a title containing `{body}` stays
+    /// as written; unknown placeholders are left alone.
+    pub fn fill(&self, template: &str) -> String {
+        self.fill_with(template, &[])
+    }
+
+    /// Like `fill`, with `extra` (name, value) placeholders filled in the
+    /// same pass.
+    pub fn fill_with(&self, template: &str, extra: &[(&str, &str)]) -> String {
+        let description = self.description.trim();
+        let (title, body) = match description.split_once('\n') {
+            Some((title, body)) => (title.trim(), body.trim()),
+            None => (description, ""),
+        };
+        let lookup = |name: &str| -> Option<String> {
+            Some(match name {
+                "id" => self.id.to_string(),
+                "strategy" => self.strategy.as_str().to_string(),
+                "duration" => format_duration(self.check_duration),
+                "trunk_before" => self.trunk_before.to_string(),
+                "trunk_after" => self.trunk_after.to_string(),
+                "pusher" => self.pusher.to_string(),
+                "title" => title.to_string(),
+                "body" => body.to_string(),
+                "description" => description.to_string(),
+                _ => {
+                    let (_, value) = extra.iter().find(|(key, _)| *key == name)?;
+                    value.to_string()
+                }
+            })
+        };
+
+        let mut out = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            rest = &rest[start..];
+            match rest
+                .find('}')
+                .and_then(|end| Some((end, lookup(&rest[1..end])?)))
+            {
+                Some((end, value)) => {
+                    out.push_str(&value);
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    out.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// The `jjq-sequence` and `jjq-strategy` trailers `status` uses to
+    /// recognize the landed commit.
+    pub fn trailers(&self) -> String {
+        format!(
+            "jjq-sequence: {}\njjq-strategy: {}",
+            self.id,
+            self.strategy.as_str()
+        )
+    }
+
+    /// Description for a commit landed by the merge or rebase strategy.
+    /// Without a template, a merge is described "Success: merge <id>" and a
+    /// rebase keeps the candidate's description.
+    pub fn description(&self, template: Option<&str>, trailers: bool) -> String {
+        let message = match (template, self.strategy) {
+            (Some(template), _) => self.fill(template),
+            // Recognized by its prefix, so it never had trailers
+            (None, Strategy::Merge) => return format!("{}{}", MERGE_MESSAGE_PREFIX, self.id),
+            (None, _) => self.description.trim().to_string(),
+        };
+        match trailers {
+            true => format!("{}\n\n{}", message.trim(), self.trailers()),
+            false => message.trim().to_string(),
+        }
+    }
+}
+
+/// Remember that item `id` landed as `change_id`, for a landed commit whose
+/// description doesn't identify it.
+pub fn record(jj: &Jj, change_id: &str, id: u32) -> Result<()> {
+    // Short IDs can grow ambiguous as the repo grows
+    let change_id = jj.run_ok(&["log", "-r", change_id, "--no-graph", "-T", "change_id"])?;
+    let change_id = change_id.trim();
+    let mut records = records(jj)?;
+    records.push((change_id.to_string(), id));
+    let start = records.len().saturating_sub(RECORD_HISTORY);
+    let lines: Vec<String> = records[start..]
+        .iter()
+        .map(|(change_id, id)| format!("{} {}", change_id, id))
+        .collect();
+    metadata::write(
+        jj,
+        &[(RECORD_FILE, &(lines.join("\n") + "\n"))],
+        &format!("Sequence-Id: {}\nLanded: {}", id, change_id),
+    )
+}
+
+/// Recorded trailer-less landings as (full change ID, sequence ID), oldest
+/// first.
+pub fn records(jj: &Jj) -> Result<Vec<(String, u32)>> {
+    let contents = jj.file_show(RECORD_FILE, JJQ_BOOKMARK).unwrap_or_default();
+    Ok(contents
+        .lines()
+        .filter_map(|line| {
+            let (change_id, id) = line.trim().split_once(' ')?;
+            Some((change_id.to_string(), id.parse().ok()?))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn landed(strategy: Strategy) -> Landed<'static> {
+        Landed {
+            id: 7,
+            strategy,
+            description: "Add login\n\nUses the new session API.\n",
+            check_duration: Duration::from_secs(95),
+            trunk_before: "kxqpmzvw",
+            trunk_after: "tlsnrouy",
+            pusher: "alice",
+        }
+    }
+
+    #[test]
+    fn test_fill_placeholders() {
+        assert_eq!(
+            landed(Strategy::Rebase).fill(
+                "{title} (#{id})\n\n{body}\n\nChecked in {duration} on {trunk_before} by {pusher}; now {trunk_after} ({strategy})"
+            ),
+            "Add login (#7)\n\nUses the new session API.\n\nChecked in 1m 35s on kxqpmzvw by alice; now tlsnrouy (rebase)"
+        );
+    }
+
+    #[test]
+    fn test_fill_does_not_expand_substituted_values() {
+        let landed = Landed {
+            description: "Document {body} and {id}\n\nSee {title}.",
+            ..landed(Strategy::Rebase)
+        };
+        assert_eq!(
+            landed.fill("{title} (#{id}) {unknown} {\n\n{body}"),
+            "Document {body} and {id} (#7) {unknown} {\n\nSee {title}."
+        );
+    }
+
+    #[test]
+    fn test_description_defaults_and_trailers() {
+        let merge = landed(Strategy::Merge);
+        assert_eq!(merge.description(None, true), "Success: merge 7");
+        assert_eq!(
+            merge.description(Some("Merge #{id}: {title}"), true),
+            "Merge #7: Add login\n\njjq-sequence: 7\njjq-strategy: merge"
+        );
+
+        let rebase = landed(Strategy::Rebase);
+        assert_eq!(
+            rebase.description(None, true),
+            "Add login\n\nUses the new session API.\n\njjq-sequence: 7\njjq-strategy: rebase"
+        );
+        assert_eq!(
+            rebase.description(None, false),
+            "Add login\n\nUses the new session API."
+        );
+    }
+}
@@ -0,0 +1,118 @@
+// ABOUTME: jjq library crate: the merge queue's core operations behind a typed API.
+// ABOUTME: The jjq binary is a command-line front end over the same modules.
+
+//! Embed the jjq merge queue in Rust tools instead of shelling out to the
+//! `jjq` binary.
+//!
+//! ```no_run
+//! let queue = jjq::Queue::open("/path/to/repo")?;
+//! let id = queue.push("my-change")?;
+//! match queue.run_one()? {
+//!     jjq::RunResult::Success => println!("landed {}", id),
+//!     outcome => println!("not landed: {:?}", outcome),
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! Operations print the same progress messages as the CLI. Errors callers
+//! can act on are an [`Error`] inside the returned `anyhow::Error`; use
+//! `err.downcast_ref::<jjq::Error>()` to match on them. Each `Queue` acts
+//! on its own repository, so one process can drive several.
+
+mod approval;
+mod artifacts;
+#[doc(hidden)]
+pub mod commands;
+mod config;
+pub mod error;
+pub mod exit_codes;
+mod jj;
+mod landed;
+mod lint;
+mod lock;
+mod metadata;
+mod pause;
+mod pool;
+mod queue;
+mod report;
+mod runlog;
+mod runner;
+mod schedule;
+mod state;
+#[doc(hidden)]
+pub mod tail;
+
+use anyhow::Result;
+use std::path::Path;
+use std::time::Duration;
+
+pub use commands::{
+    CurrentItem, FailedItem, LandedItem, QueueItem, RunResult, StatusOutput as Status,
+};
+pub use error::Error;
+#[doc(hidden)]
+pub use jj::Jj;
+pub use lock::Holder;
+pub use pause::Pause;
+pub use schedule::LandingClosed;
+pub use state::Phase;
+
+/// The merge queue of one jj repository.
+pub struct Queue {
+    jj: Jj,
+}
+
+impl Queue {
+    /// Open the queue of the jj repository containing `path`. Every later
+    /// operation runs against that repository, wherever the process's
+    /// current directory is.
+    pub fn open(path: impl AsRef<Path>) -> Result<Queue> {
+        Ok(Queue {
+            jj: Jj::open(path.as_ref())?,
+        })
+    }
+
+    /// Root of the repository's workspace.
+    pub fn root(&self) -> &Path {
+        self.jj.root()
+    }
+
+    /// Queue a revision, returning its sequence ID.
+    pub fn push(&self, revset: &str) -> Result<u32> {
+        commands::push(&self.jj, revset, None, false, None)
+    }
+
+    /// Queue a revision, waiting up to `timeout` for a concurrent push to
+    /// finish instead of failing with `Error::LockHeld`.
+    pub fn push_wait(&self, revset: &str, timeout: Duration) -> Result<u32> {
+        commands::push(&self.jj, revset, Some(timeout), false, None)
+    }
+
+    /// Process the next queue item.
+    pub fn run_one(&self) -> Result<RunResult> {
+        commands::require_initialized(&self.jj)?;
+        commands::run_one(&self.jj, None, false)
+    }
+
+    /// Process the next queue item, waiting up to `timeout` for another
+    /// runner to finish instead of failing with `Error::RunnerBusy`.
+    pub fn run_one_wait(&self, timeout: Duration) -> Result<RunResult> {
+        commands::require_initialized(&self.jj)?;
+        commands::run_one(&self.jj, Some(timeout), false)
+    }
+
+    /// Pause the queue: `run_one` returns `Error::Paused` until `resume`.
+    pub fn pause(&self, reason: Option<&str>) -> Result<()> {
+        commands::pause(&self.jj, reason)
+    }
+
+    /// Resume a paused queue.
+    pub fn resume(&self) -> Result<()> {
+        commands::resume(&self.jj)
+    }
+
+    /// Current queue state.
+    pub fn status(&self) -> Result<Status> {
+        commands::status_output(&self.jj)
+    }
+}
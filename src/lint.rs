@@ -0,0 +1,122 @@
+// ABOUTME: Push-time description policy from the description_* and title_max_length settings.
+// ABOUTME: Reports every rule a revision's description breaks so push can reject it.
+
+use anyhow::Result;
+use regex::Regex;
+
+use crate::config;
+use crate::jj::Jj;
+
+/// Description rules configured for the repository.
+#[derive(Debug, Default)]
+pub struct Rules {
+    /// Reject empty descriptions.
+    pub required: bool,
+    /// Maximum length of the title (first line), in characters.
+    pub max_title: Option<usize>,
+    /// Patterns the title must not match, e.g. `^(WIP|fixup!)`.
+    pub deny: Vec<Regex>,
+    /// Patterns the description must match, e.g. a ticket reference.
+    pub require: Vec<Regex>,
+}
+
+impl Rules {
+    pub fn from_config(jj: &Jj) -> Result<Rules> {
+        let patterns = |key: &str| -> Result<Vec<Regex>> {
+            match config::get(jj, key)? {
+                Some(value) => config::parse_patterns(&value)
+                    .map_err(|e| anyhow::anyhow!("invalid {} value: {}", key, e)),
+                None => Ok(Vec::new()),
+            }
+        };
+        Ok(Rules {
+            required: config::get(jj, "description_required")?.as_deref() == Some("true"),
+            max_title: config::get(jj, "title_max_length")?.and_then(|v| v.parse().ok()),
+            deny: patterns("description_deny")?,
+            require: patterns("description_require")?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.required
+            && self.max_title.is_none()
+            && self.deny.is_empty()
+            && self.require.is_empty()
+    }
+
+    /// Every rule `description` breaks, as messages.
+    pub fn problems(&self, description: &str) -> Vec<String> {
+        let description = description.trim();
+        if description.is_empty() && self.required {
+            // The other rules would only restate this
+            return vec!["description is empty".to_string()];
+        }
+        let title = description.lines().next().unwrap_or("").trim();
+        let mut problems = Vec::new();
+        if let Some(max) = self.max_title {
+            let len = title.chars().count();
+            if len > max {
+                problems.push(format!("title is {} characters (max {})", len, max));
+            }
+        }
+        for re in &self.deny {
+            if re.is_match(title) {
+                problems.push(format!("title matches denied pattern '{}'", re));
+            }
+        }
+        for re in &self.require {
+            if !re.is_match(description) {
+                problems.push(format!(
+                    "description does not match required pattern '{}'",
+                    re
+                ));
+            }
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_problems() {
+        let rules = Rules {
+            required: true,
+            max_title: Some(20),
+            deny: vec![Regex::new("^(WIP|fixup!)").unwrap()],
+            require: vec![Regex::new(r"[A-Z]+-\d+").unwrap()],
+        };
+        assert_eq!(rules.problems("  \n"), vec!["description is empty"]);
+        assert!(rules.problems("Fix login\n\nRefs: AUTH-12").is_empty());
+        assert_eq!(
+            rules.problems("WIP: a rather long title here\n\nAUTH-1"),
+            vec![
+                "title is 29 characters (max 20)",
+                "title matches denied pattern '^(WIP|fixup!)'",
+            ]
+        );
+        assert_eq!(
+            rules.problems("Fix login"),
+            vec![r"description does not match required pattern '[A-Z]+-\d+'"]
+        );
+
+        // Without description_required an empty description passes the
+        // length rule but not a required pattern
+        let lenient = Rules {
+            max_title: Some(20),
+            ..Rules::default()
+        };
+        assert!(lenient.problems("").is_empty());
+        let ticket = Rules {
+            require: vec![Regex::new(r"[A-Z]+-\d+").unwrap()],
+            ..Rules::default()
+        };
+        assert_eq!(
+            ticket.problems(""),
+            vec![r"description does not match required pattern '[A-Z]+-\d+'"]
+        );
+        assert!(Rules::default().is_empty());
+    }
+}
@@ -1,52 +1,222 @@
 // ABOUTME: Filesystem-based locking using flock via the fs2 crate.
 // ABOUTME: Implements the locking protocol from the jjq specification.
 
-use anyhow::{bail, Result};
+use anyhow::Result;
 use fs2::FileExt;
-use std::fs::{self, File};
-use std::io;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Seek, Write};
 use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::jj;
+use crate::error::Error;
+use crate::jj::Jj;
 
 /// A held lock that releases on drop (OS releases flock when File is dropped).
 pub struct Lock {
-    _file: File,
+    file: File,
+    holder: Holder,
+}
+
+/// Who holds a lock, written to the lock file as JSON on acquisition.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Holder {
+    pub pid: u32,
+    pub host: String,
+    /// Command line of the holding process.
+    pub command: String,
+    /// Unix time (seconds) the lock was acquired.
+    pub started: u64,
+    /// Queue item being processed, recorded by the runner.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item: Option<u32>,
+}
+
+impl Holder {
+    /// Describe the current process.
+    fn current() -> Holder {
+        Holder {
+            pid: std::process::id(),
+            host: hostname(),
+            command: std::env::args().collect::<Vec<_>>().join(" "),
+            started: unix_now(),
+            item: None,
+        }
+    }
+
+    /// How long the lock has been held.
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_secs(unix_now().saturating_sub(self.started))
+    }
+
+    /// Whether the recorded process is known to be gone: it ran on this host
+    /// and its pid no longer exists.
+    pub fn is_stale(&self) -> bool {
+        self.host == hostname() && !pid_exists(self.pid)
+    }
+}
+
+impl fmt::Display for Holder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "pid {} on {} ({}), started {} ago",
+            self.pid,
+            self.host,
+            self.command,
+            crate::runner::format_duration(self.elapsed())
+        )?;
+        if self.is_stale() {
+            write!(f, "; that process no longer exists")?;
+        }
+        Ok(())
+    }
 }
 
 impl Lock {
     /// Try to acquire a named lock. Returns Ok(Some) if acquired, Ok(None) if
-    /// already held by another process.
-    pub fn acquire(name: &str) -> Result<Option<Lock>> {
-        let path = lock_file_path(name)?;
+    /// already held by another process. The holder's pid is written to the
+    /// lock file so waiters can report it.
+    pub fn acquire(jj: &Jj, name: &str) -> Result<Option<Lock>> {
+        let path = lock_file_path(jj, name)?;
         fs::create_dir_all(path.parent().unwrap())?;
-        let file = File::create(&path)?;
+        // Don't truncate before locking: that would erase the holder's pid
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
         match file.try_lock_exclusive() {
-            Ok(()) => Ok(Some(Lock { _file: file })),
+            Ok(()) => {
+                let mut lock = Lock {
+                    file,
+                    holder: Holder::current(),
+                };
+                lock.write_holder()?;
+                Ok(Some(lock))
+            }
             Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
             Err(e) => Err(e.into()),
         }
     }
 
-    /// Acquire a lock, failing with an error if already held.
-    pub fn acquire_or_fail(name: &str, message: &str) -> Result<Lock> {
-        match Lock::acquire(name)? {
+    /// Record the queue item this lock's holder is processing.
+    pub fn set_item(&mut self, id: u32) -> Result<()> {
+        self.holder.item = Some(id);
+        self.write_holder()
+    }
+
+    fn write_holder(&mut self) -> Result<()> {
+        self.file.set_len(0)?;
+        self.file.rewind()?;
+        serde_json::to_writer(&mut self.file, &self.holder)?;
+        writeln!(self.file)?;
+        Ok(())
+    }
+
+    /// Acquire a named lock, waiting up to `timeout` for another process to
+    /// release it. Prints who holds the lock when it has to wait. Returns
+    /// Ok(None) if the lock is still held at the deadline.
+    pub fn acquire_wait(jj: &Jj, name: &str, timeout: Duration) -> Result<Option<Lock>> {
+        let start = Instant::now();
+        let mut announced = false;
+        loop {
+            if let Some(lock) = Lock::acquire(jj, name)? {
+                return Ok(Some(lock));
+            }
+            if start.elapsed() >= timeout {
+                return Ok(None);
+            }
+            if !announced {
+                let holder = held_by(jj, name)?;
+                eprintln!(
+                    "jjq: waiting up to {}s for {} lock{}",
+                    timeout.as_secs(),
+                    name,
+                    holder
+                );
+                announced = true;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// Acquire a lock, waiting up to `wait` if given, or trying once.
+    pub fn acquire_maybe_wait(jj: &Jj, name: &str, wait: Option<Duration>) -> Result<Option<Lock>> {
+        match wait {
+            Some(timeout) => Lock::acquire_wait(jj, name, timeout),
+            None => Lock::acquire(jj, name),
+        }
+    }
+
+    /// Acquire a lock, failing with `Error::LockHeld` if already held (after
+    /// waiting up to `wait`, if given).
+    pub fn acquire_or_fail(jj: &Jj, name: &str, wait: Option<Duration>) -> Result<Lock> {
+        match Lock::acquire_maybe_wait(jj, name, wait)? {
             Some(lock) => Ok(lock),
-            None => bail!("{}", message),
+            None => Err(Error::LockHeld {
+                lock: name.to_string(),
+                holder: holder(jj, name)?,
+            }
+            .into()),
         }
     }
+}
+
+/// Holder recorded in a lock file: the current holder if the lock is held,
+/// otherwise the last one. None if nothing (readable) was recorded.
+pub fn holder(jj: &Jj, name: &str) -> Result<Option<Holder>> {
+    match fs::read_to_string(lock_file_path(jj, name)?) {
+        Ok(contents) => Ok(serde_json::from_str(contents.trim()).ok()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// " (held by ...)" describing a lock's holder for messages, or "".
+pub fn held_by(jj: &Jj, name: &str) -> Result<String> {
+    Ok(holder(jj, name)?
+        .map(|h| format!(" (held by {})", h))
+        .unwrap_or_default())
+}
+
+/// Current unix time in seconds.
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// This machine's hostname, or "" if it can't be read.
+pub(crate) fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    let rc = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if rc != 0 {
+        return String::new();
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
 
+/// Whether a process with this pid exists on this host.
+fn pid_exists(pid: u32) -> bool {
+    let rc = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    rc == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
 }
 
 /// State of a named lock.
 pub enum LockState {
     Free,
-    Held,
+    /// Held, by the recorded holder if the lock file names one.
+    Held(Option<Holder>),
 }
 
 /// Inspect the state of a named lock.
-pub fn lock_state(name: &str) -> Result<LockState> {
-    let path = lock_file_path(name)?;
+pub fn lock_state(jj: &Jj, name: &str) -> Result<LockState> {
+    let path = lock_file_path(jj, name)?;
     let file = match File::open(&path) {
         Ok(f) => f,
         Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(LockState::Free),
@@ -57,17 +227,36 @@ pub fn lock_state(name: &str) -> Result<LockState> {
             file.unlock()?;
             Ok(LockState::Free)
         }
-        Err(_) => Ok(LockState::Held),
+        Err(_) => Ok(LockState::Held(holder(jj, name)?)),
     }
 }
 
 /// Check if a lock is currently held.
-pub fn is_held(name: &str) -> Result<bool> {
-    Ok(matches!(lock_state(name)?, LockState::Held))
+pub fn is_held(jj: &Jj, name: &str) -> Result<bool> {
+    Ok(matches!(lock_state(jj, name)?, LockState::Held(_)))
 }
 
 /// Get the lock file path for a named lock.
-fn lock_file_path(name: &str) -> Result<PathBuf> {
-    let root = jj::repo_root()?;
+fn lock_file_path(jj: &Jj, name: &str) -> Result<PathBuf> {
+    let root = jj.root();
     Ok(root.join(".jj").join("jjq-locks").join(format!("{}.lock", name)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_holder_stale_only_when_pid_gone_on_this_host() {
+        let mut holder = Holder::current();
+        assert!(!holder.is_stale());
+
+        // Above the kernel's pid_max, so never a live process
+        holder.pid = 1 << 30;
+        assert!(holder.is_stale());
+
+        // Pids on other hosts can't be checked
+        holder.host = format!("{}-elsewhere", hostname());
+        assert!(!holder.is_stale());
+    }
+}
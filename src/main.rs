@@ -1,22 +1,18 @@
 // ABOUTME: jjq - A local merge queue for jj (Jujutsu VCS).
 // ABOUTME: Implements the jjq specification for queuing and processing merge candidates.
 
-mod commands;
-mod config;
-mod exit_codes;
-mod jj;
-mod lock;
-mod queue;
-mod runlog;
-mod runner;
-mod tail;
-
 use anyhow::Result;
 use clap::{Parser, Subcommand};
+use jjq::{Jj, commands, error, tail};
+use std::path::Path;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "jjq", about = "Local merge queue for jj", version)]
 struct Cli {
+    /// Output as JSON: status output, and errors on stderr
+    #[arg(long, global = true)]
+    json: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -31,7 +27,7 @@ enum Commands {
         /// Check command
         #[arg(long)]
         check: Option<String>,
-        /// Merge strategy (rebase or merge)
+        /// Merge strategy (rebase, merge, squash or fast-forward)
         #[arg(long, default_value = "rebase")]
         strategy: String,
     },
@@ -39,6 +35,21 @@ enum Commands {
     Push {
         /// Revset expression resolving to exactly one revision
         revset: String,
+        /// Wait up to SECS (default 300) for a concurrent push instead of failing
+        #[arg(
+            long,
+            value_name = "SECS",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "300"
+        )]
+        wait: Option<u64>,
+        /// Push even if a description breaks the description policy
+        #[arg(long)]
+        force: bool,
+        /// Land this item with STRATEGY instead of the configured strategy
+        #[arg(long, value_name = "STRATEGY")]
+        strategy: Option<String>,
     },
     /// Process the next item(s) in the queue
     Run {
@@ -48,6 +59,18 @@ enum Commands {
         /// Stop processing on first failure (only with --all)
         #[arg(long)]
         stop_on_failure: bool,
+        /// Wait up to SECS (default 300) for another runner instead of failing
+        #[arg(
+            long,
+            value_name = "SECS",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "300"
+        )]
+        wait: Option<u64>,
+        /// Land even inside a freeze window or outside landing windows
+        #[arg(long)]
+        ignore_schedule: bool,
     },
     /// Run check command against a revision without queue processing
     Check {
@@ -57,14 +80,17 @@ enum Commands {
         /// Show workspace path, shell, and environment before running
         #[arg(long, short)]
         verbose: bool,
+        /// Combine the revision with trunk as `run` would (default)
+        #[arg(long, conflicts_with = "isolated")]
+        against_trunk: bool,
+        /// Check the bare revision without combining it with trunk
+        #[arg(long)]
+        isolated: bool,
     },
     /// Display current queue state
     Status {
         /// Sequence ID of a specific item to show
         id: Option<String>,
-        /// Output as JSON
-        #[arg(long)]
-        json: bool,
         /// Look up item by candidate change ID
         #[arg(long, conflicts_with = "id")]
         resolve: Option<String>,
@@ -79,8 +105,33 @@ enum Commands {
         /// Sequence ID of the item
         id: String,
     },
+    /// Approve a queued item for landing
+    Approve {
+        /// Sequence ID of the queued item
+        id: String,
+    },
+    /// Stop runs from landing items until resumed
+    Pause {
+        /// Why the queue is paused, shown by status and doctor
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Let runs land items again after a pause
+    Resume,
+    /// Stop the item the runner is processing
+    Cancel {
+        /// Sequence ID of the running item (default: whichever is running)
+        id: Option<String>,
+        /// Leave the item in the queue instead of marking it failed
+        #[arg(long)]
+        requeue: bool,
+    },
     /// Remove jjq workspaces
-    Clean,
+    Clean {
+        /// Also reset the warm runner workspace pool
+        #[arg(long)]
+        pool: bool,
+    },
     /// Validate configuration and environment
     Doctor,
     /// Get or set configuration
@@ -98,25 +149,31 @@ enum Commands {
         /// Don't follow output, just dump and exit
         #[arg(long)]
         no_follow: bool,
+        /// Prefix each line with the time since the check started
+        #[arg(long)]
+        elapsed: bool,
+        /// Only show lines the check wrote to stderr
+        #[arg(long)]
+        stderr_only: bool,
     },
     /// Print quickstart guide for LLM agents
     Quickstart,
 }
 
 fn main() {
-    if let Err(e) = run() {
-        if let Some(exit_err) = e.downcast_ref::<exit_codes::ExitError>() {
-            eprintln!("jjq: {}", exit_err.message);
-            std::process::exit(exit_err.code);
+    let cli = Cli::parse();
+    let json = cli.json;
+    if let Err(e) = run(cli) {
+        if json {
+            eprintln!("{}", error::to_json(&e));
+        } else {
+            eprintln!("jjq: {}", e);
         }
-        eprintln!("jjq: {}", e);
-        std::process::exit(1);
+        std::process::exit(error::exit_code(&e));
     }
 }
 
-fn run() -> Result<()> {
-    let cli = Cli::parse();
-
+fn run(cli: Cli) -> Result<()> {
     // Quickstart doesn't need a repo
     if let Commands::Quickstart = cli.command {
         print!("{}", include_str!("quickstart.txt"));
@@ -124,27 +181,71 @@ fn run() -> Result<()> {
     }
 
     // Verify we're in a jj repository
-    jj::verify_repo()?;
+    let jj = &Jj::open(Path::new("."))?;
 
     match cli.command {
-        Commands::Init { trunk, check, strategy } => {
-            commands::init(trunk.as_deref(), check.as_deref(), &strategy)
-        }
-        Commands::Push { revset } => commands::push(&revset),
+        Commands::Init {
+            trunk,
+            check,
+            strategy,
+        } => commands::init(jj, trunk.as_deref(), check.as_deref(), &strategy),
+        Commands::Push {
+            revset,
+            wait,
+            force,
+            strategy,
+        } => commands::push(
+            jj,
+            &revset,
+            wait.map(Duration::from_secs),
+            force,
+            strategy.as_deref(),
+        )
+        .map(|_| ()),
         Commands::Run {
             all,
             stop_on_failure,
-        } => commands::run(all, stop_on_failure),
-        Commands::Check { rev, verbose } => commands::check(&rev, verbose),
-        Commands::Status { id, json, resolve } => {
-            commands::status(id.as_deref(), json, resolve.as_deref())
+            wait,
+            ignore_schedule,
+        } => commands::run(
+            jj,
+            all,
+            stop_on_failure,
+            wait.map(Duration::from_secs),
+            ignore_schedule,
+        ),
+        Commands::Check {
+            rev,
+            verbose,
+            against_trunk: _,
+            isolated,
+        } => commands::check(jj, &rev, verbose, isolated),
+        Commands::Status { id, resolve } => {
+            commands::status(jj, id.as_deref(), cli.json, resolve.as_deref())
         }
-        Commands::Requeue { id } => commands::requeue(&id),
-        Commands::Delete { id } => commands::delete(&id),
-        Commands::Clean => commands::clean(),
-        Commands::Doctor => commands::doctor(),
-        Commands::Config { key, value } => commands::config(key.as_deref(), value.as_deref()),
-        Commands::Tail { all, no_follow } => tail::tail(all, !no_follow),
+        Commands::Requeue { id } => commands::requeue(jj, &id),
+        Commands::Delete { id } => commands::delete(jj, &id),
+        Commands::Approve { id } => commands::approve(jj, &id),
+        Commands::Pause { reason } => commands::pause(jj, reason.as_deref()),
+        Commands::Resume => commands::resume(jj),
+        Commands::Cancel { id, requeue } => commands::cancel(jj, id.as_deref(), requeue),
+        Commands::Clean { pool } => commands::clean(jj, pool),
+        Commands::Doctor => commands::doctor(jj),
+        Commands::Config { key, value } => commands::config(jj, key.as_deref(), value.as_deref()),
+        Commands::Tail {
+            all,
+            no_follow,
+            elapsed,
+            stderr_only,
+        } => tail::tail(
+            jj,
+            all,
+            !no_follow,
+            tail::TailOptions {
+                elapsed,
+                stderr_only,
+            },
+        ),
         Commands::Quickstart => unreachable!(),
     }
 }
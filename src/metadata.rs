@@ -0,0 +1,120 @@
+// ABOUTME: Writes files to the jjq metadata branch (jjq/_/_) through one helper.
+// ABOUTME: Uses a persistent workspace under .jj so writes need no workspace add/forget cycle.
+
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::JJQ_BOOKMARK;
+use crate::error::Error;
+use crate::jj::{self, Jj};
+use crate::lock::{self, Lock};
+
+/// Name of the persistent workspace used for metadata writes.
+pub const WORKSPACE_NAME: &str = "jjq-metadata";
+
+/// How long to wait for another process's metadata write to finish.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Return the path of the metadata workspace.
+fn workspace_path(jj: &Jj) -> Result<PathBuf> {
+    Ok(jj.root().join(".jj").join(WORKSPACE_NAME))
+}
+
+/// Write `files` (paths relative to the metadata root, with their contents)
+/// to the metadata branch as a single commit described by `message`. jjq
+/// must already be initialized.
+pub fn write(jj: &Jj, files: &[(&str, &str)], message: &str) -> Result<()> {
+    commit(jj, &bookmark_rev(), files, &[], message)
+}
+
+/// Remove `files` from the metadata branch as a single commit described by
+/// `message`. Files that don't exist are ignored; if none exist, nothing is
+/// committed.
+pub fn remove(jj: &Jj, files: &[&str], message: &str) -> Result<()> {
+    if jj.file_list(&bookmark_rev(), files)?.is_empty() {
+        return Ok(());
+    }
+    commit(jj, &bookmark_rev(), &[], files, message)
+}
+
+/// Create the metadata branch with its initial files.
+pub fn create(jj: &Jj, files: &[(&str, &str)], message: &str) -> Result<()> {
+    commit(jj, "root()", files, &[], message)
+}
+
+fn bookmark_rev() -> String {
+    format!("bookmarks(exact:\"{}\")", JJQ_BOOKMARK)
+}
+
+/// Commit `files` and the removal of `removed` on top of `parent` and point
+/// the metadata bookmark at it.
+///
+/// The metadata workspace's working copy is moved to a new commit on
+/// `parent`, the files are written into it, and `bookmark set` snapshots them
+/// while moving the bookmark, so a write costs two jj invocations. The jj
+/// CLI can't set a file's contents in a commit other than through a working
+/// copy, hence the persistent workspace.
+fn commit(
+    jj: &Jj,
+    parent: &str,
+    files: &[(&str, &str)],
+    removed: &[&str],
+    message: &str,
+) -> Result<()> {
+    let _lock = acquire_lock(jj)?;
+    let path = workspace_path(jj)?;
+
+    start_commit(jj, &path, parent, message)?;
+    for (file, contents) in files {
+        let target = path.join(file);
+        if let Some(dir) = target.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&target, contents)?;
+    }
+    for file in removed {
+        match fs::remove_file(path.join(file)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    jj::run_quiet_in(&path, &["bookmark", "set", JJQ_BOOKMARK, "-r", "@"])
+}
+
+/// Start a new working-copy commit on `parent` in the metadata workspace,
+/// (re)creating the workspace if it is missing or was forgotten.
+fn start_commit(jj: &Jj, path: &Path, parent: &str, message: &str) -> Result<()> {
+    if path.join(".jj").is_dir() {
+        let args = ["new", "-m", message, parent];
+        let output = jj::run_in(path, &args)?;
+        if output.status.success() {
+            return Ok(());
+        }
+        if String::from_utf8_lossy(&output.stderr).contains("stale") {
+            jj::workspace_update_stale(path)?;
+            return jj::run_quiet_in(path, &args);
+        }
+        // Otherwise the workspace was forgotten; recreate it below
+    }
+
+    let _ = jj.workspace_forget(WORKSPACE_NAME);
+    if path.exists() {
+        fs::remove_dir_all(path)?;
+    }
+    jj.workspace_add(path.to_str().unwrap(), WORKSPACE_NAME, &[parent])?;
+    jj::run_quiet_in(path, &["describe", "-m", message])
+}
+
+/// Serialize metadata writes, waiting briefly for a concurrent writer.
+fn acquire_lock(jj: &Jj) -> Result<Lock> {
+    match Lock::acquire_wait(jj, "metadata", LOCK_TIMEOUT)? {
+        Some(lock) => Ok(lock),
+        None => Err(Error::LockHeld {
+            lock: "metadata".to_string(),
+            holder: lock::holder(jj, "metadata")?,
+        }
+        .into()),
+    }
+}
@@ -0,0 +1,74 @@
+// ABOUTME: Queue pause state, stored on the metadata branch by `jjq pause`.
+// ABOUTME: While paused, runs refuse to process items; pushes are still accepted.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+use crate::config::JJQ_BOOKMARK;
+use crate::jj::Jj;
+use crate::lock::{hostname, unix_now};
+use crate::metadata;
+
+/// Metadata file holding the pause record while the queue is paused.
+const PAUSE_FILE: &str = "pause";
+
+/// Who paused the queue, when, and why.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pause {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// The jj user name, or the login name if jj has none.
+    pub by: String,
+    pub host: String,
+    /// Unix time (seconds) the queue was paused.
+    pub since: u64,
+}
+
+impl fmt::Display for Pause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "paused by {} on {}, {} ago",
+            self.by,
+            self.host,
+            crate::runner::format_duration(Duration::from_secs(
+                unix_now().saturating_sub(self.since)
+            ))
+        )?;
+        if let Some(reason) = &self.reason {
+            write!(f, ": {}", reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// The current pause, if the queue is paused.
+pub fn get(jj: &Jj) -> Result<Option<Pause>> {
+    match jj.file_show(PAUSE_FILE, JJQ_BOOKMARK) {
+        Ok(contents) => Ok(serde_json::from_str(&contents).ok()),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Pause the queue, replacing any existing pause.
+pub fn set(jj: &Jj, reason: Option<&str>) -> Result<Pause> {
+    let pause = Pause {
+        reason: reason.map(str::to_string),
+        by: jj.user_name(),
+        host: hostname(),
+        since: unix_now(),
+    };
+    metadata::write(
+        jj,
+        &[(PAUSE_FILE, &serde_json::to_string(&pause)?)],
+        "pause",
+    )?;
+    Ok(pause)
+}
+
+/// Resume the queue.
+pub fn clear(jj: &Jj) -> Result<()> {
+    metadata::remove(jj, &[PAUSE_FILE], "resume")
+}
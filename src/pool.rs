@@ -0,0 +1,128 @@
+// ABOUTME: Pool of long-lived runner workspaces that keep build caches between checks.
+// ABOUTME: Slots are jj workspaces named jjq-pool-N under the configured pool_dir.
+
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::jj::{self, Jj};
+use crate::lock::Lock;
+
+/// Prefix of pool workspace names.
+pub const WORKSPACE_PREFIX: &str = "jjq-pool-";
+
+/// A claimed pool slot. Other processes cannot use it until it is dropped.
+pub struct Slot {
+    pub name: String,
+    pub path: PathBuf,
+    _lock: Lock,
+}
+
+/// Return the pool directory, or None if the pool is disabled. A relative
+/// `pool_dir` is resolved against the repository root.
+pub fn dir(jj: &Jj) -> Result<Option<PathBuf>> {
+    match config::get_pool_dir(jj)? {
+        Some(dir) => Ok(Some(jj.root().join(dir))),
+        None => Ok(None),
+    }
+}
+
+/// Whether `path` is a pool workspace, which must never be removed along
+/// with a failed item.
+pub fn owns(jj: &Jj, path: &Path) -> bool {
+    matches!(dir(jj), Ok(Some(dir)) if path.starts_with(&dir))
+}
+
+/// Lock name guarding slot `name`.
+fn lock_name(name: &str) -> String {
+    format!("pool-{}", name.trim_start_matches(WORKSPACE_PREFIX))
+}
+
+/// Claim a free slot. Returns None if the pool is disabled or every slot is
+/// in use.
+pub fn claim(jj: &Jj) -> Result<Option<Slot>> {
+    let Some(dir) = dir(jj)? else {
+        return Ok(None);
+    };
+    for n in 1..=config::get_pool_size(jj)? {
+        let name = format!("{}{}", WORKSPACE_PREFIX, n);
+        if let Some(lock) = Lock::acquire(jj, &lock_name(&name))? {
+            return Ok(Some(Slot {
+                path: dir.join(&name),
+                name,
+                _lock: lock,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+impl Slot {
+    /// Move the slot to a new working-copy commit on `parents`, creating its
+    /// workspace on first use. Ignored files (build outputs) are left in
+    /// place, and tracked files are only rewritten where they differ.
+    pub fn checkout(&self, jj: &Jj, parents: &[&str]) -> Result<()> {
+        if self.path.join(".jj").is_dir() && workspace_names(jj)?.contains(&self.name) {
+            jj::workspace_update_stale(&self.path)?;
+            return jj::new_in(&self.path, parents);
+        }
+
+        // First use, or the workspace was forgotten or its directory removed
+        let _ = jj.workspace_forget(&self.name);
+        if self.path.exists() {
+            fs::remove_dir_all(&self.path)?;
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        jj.workspace_add(self.path.to_str().unwrap(), &self.name, parents)
+    }
+}
+
+/// Names of all pool workspaces known to jj.
+pub fn workspace_names(jj: &Jj) -> Result<Vec<String>> {
+    Ok(jj
+        .workspace_list()?
+        .lines()
+        .filter_map(|line| line.split(':').next())
+        .filter(|name| name.starts_with(WORKSPACE_PREFIX))
+        .map(|name| name.to_string())
+        .collect())
+}
+
+/// Forget every idle pool workspace and delete its directory. Returns the
+/// names of the slots removed and of those skipped because they are in use.
+pub fn reset(jj: &Jj) -> Result<(Vec<String>, Vec<String>)> {
+    let mut names = workspace_names(jj)?;
+    let dir = dir(jj)?;
+    if let Some(dir) = &dir
+        && dir.is_dir()
+    {
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            if name.starts_with(WORKSPACE_PREFIX) && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names.sort();
+
+    let mut removed = Vec::new();
+    let mut busy = Vec::new();
+    for name in names {
+        let Some(_lock) = Lock::acquire(jj, &lock_name(&name))? else {
+            busy.push(name);
+            continue;
+        };
+        let _ = jj.workspace_forget(&name);
+        if let Some(dir) = &dir {
+            let path = dir.join(&name);
+            if path.is_dir() {
+                fs::remove_dir_all(&path)?;
+            }
+        }
+        removed.push(name);
+    }
+    Ok((removed, busy))
+}
@@ -1,39 +1,31 @@
 // ABOUTME: Queue management for jjq - sequence IDs and queue operations.
 // ABOUTME: Handles bookmark-based queue state and FIFO ordering.
 
-use anyhow::{bail, Result};
+use anyhow::Result;
 use regex::Regex;
-use std::env;
-use std::fs;
 use std::sync::OnceLock;
-use tempfile::TempDir;
+use std::time::Duration;
 
-use crate::config::{self, JJQ_BOOKMARK};
-use crate::exit_codes::{self, ExitError};
-use crate::jj;
-use crate::lock::Lock;
+use crate::config::{self, JJQ_BOOKMARK, Strategy};
+use crate::error::Error;
+use crate::jj::Jj;
+use crate::lock::{self, Lock};
+use crate::metadata;
 
 /// Validate and parse a sequence ID from user input.
 /// Returns the integer value on success.
 pub fn parse_seq_id(input: &str) -> Result<u32> {
-    if input.is_empty() {
-        bail!("invalid sequence ID: empty");
+    // Only digits allowed, and the message says why the input is invalid
+    let invalid = || Error::InvalidSequenceId {
+        input: input.to_string(),
+    };
+    if input.is_empty() || !input.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid().into());
     }
-
-    // Only digits allowed
-    if !input.chars().all(|c| c.is_ascii_digit()) {
-        bail!("invalid sequence ID: '{}' (must be numeric)", input);
+    match input.parse::<u32>() {
+        Ok(id) if (1..=999999).contains(&id) => Ok(id),
+        _ => Err(invalid().into()),
     }
-
-    let id: u32 = input
-        .parse()
-        .map_err(|_| anyhow::anyhow!("invalid sequence ID: {} (must be 1-999999)", input))?;
-
-    if !(1..=999999).contains(&id) {
-        bail!("invalid sequence ID: {} (must be 1-999999)", input);
-    }
-
-    Ok(id)
 }
 
 /// Format a sequence ID as zero-padded string for bookmark names.
@@ -41,49 +33,61 @@ pub fn format_seq_id(id: u32) -> String {
     format!("{:06}", id)
 }
 
-/// Get the next sequence ID, incrementing the counter.
-pub fn next_id() -> Result<u32> {
-    let _lock = match Lock::acquire("id")? {
+/// Get the next sequence ID, incrementing the counter. With `wait`, waits up
+/// to that long for another process's allocation to finish.
+///
+/// Who pushed the item and, if given, the strategy it lands with instead of
+/// the configured one are recorded in the same metadata commit.
+pub fn next_id(
+    jj: &Jj,
+    wait: Option<Duration>,
+    pusher: &str,
+    strategy: Option<Strategy>,
+) -> Result<u32> {
+    let _lock = match Lock::acquire_maybe_wait(jj, "id", wait)? {
         Some(lock) => lock,
         None => {
-            return Err(ExitError::new(
-                exit_codes::LOCK_HELD,
-                "could not acquire sequence ID lock (another process may be pushing)",
-            )
+            return Err(Error::LockHeld {
+                lock: "id".to_string(),
+                holder: lock::holder(jj, "id")?,
+            }
             .into());
         }
     };
 
-    config::ensure_initialized()?;
-
-    let temp_dir = TempDir::new()?;
-    let workspace_name = format!("jjq{}", std::process::id());
-
-    jj::workspace_add(
-        temp_dir.path().to_str().unwrap(),
-        &workspace_name,
-        &[JJQ_BOOKMARK],
-    )?;
-
-    let orig_dir = env::current_dir()?;
-    env::set_current_dir(temp_dir.path())?;
+    config::ensure_initialized(jj)?;
 
     // Read current value
-    let current: u32 = fs::read_to_string("last_id")?.trim().parse().unwrap_or(0);
+    let current: u32 = jj
+        .file_show("last_id", JJQ_BOOKMARK)?
+        .trim()
+        .parse()
+        .unwrap_or(0);
 
     if current >= 999999 {
-        env::set_current_dir(&orig_dir)?;
-        jj::workspace_forget(&workspace_name)?;
-        return Err(ExitError::new(exit_codes::USAGE, "sequence ID exhausted (at 999999)").into());
+        return Err(Error::SequenceExhausted.into());
     }
 
     let new_id = current + 1;
-    fs::write("last_id", new_id.to_string())?;
-    jj::describe("@", &format!("{} -> {}\npid: {}", current, new_id, std::process::id()))?;
-    jj::run_quiet(&["bookmark", "set", JJQ_BOOKMARK])?;
-
-    env::set_current_dir(&orig_dir)?;
-    jj::workspace_forget(&workspace_name)?;
+    let last_id = new_id.to_string();
+    let pusher_file = pusher_path(new_id);
+    let strategy_file = strategy_path(new_id);
+    let mut files = vec![
+        ("last_id", last_id.as_str()),
+        (pusher_file.as_str(), pusher),
+    ];
+    let mut message = format!(
+        "{} -> {}\npid: {}\nPusher: {}",
+        current,
+        new_id,
+        std::process::id(),
+        pusher
+    );
+    if let Some(strategy) = strategy {
+        files.push((strategy_file.as_str(), strategy.as_str()));
+        message.push_str(&format!("\nStrategy: {}", strategy.as_str()));
+    }
+    metadata::write(jj, &files, &message)?;
 
     Ok(new_id)
 }
@@ -101,9 +105,9 @@ fn failed_re() -> &'static Regex {
 }
 
 /// Get all queued items sorted by sequence ID (ascending).
-pub fn get_queue() -> Result<Vec<u32>> {
+pub fn get_queue(jj: &Jj) -> Result<Vec<u32>> {
     let re = queue_re();
-    let bookmarks = jj::bookmark_list_glob("jjq/queue/??????")?;
+    let bookmarks = jj.bookmark_list_glob("jjq/queue/??????")?;
 
     let mut ids: Vec<u32> = bookmarks
         .iter()
@@ -119,9 +123,9 @@ pub fn get_queue() -> Result<Vec<u32>> {
 }
 
 /// Get all failed items sorted by sequence ID (descending, for display).
-pub fn get_failed() -> Result<Vec<u32>> {
+pub fn get_failed(jj: &Jj) -> Result<Vec<u32>> {
     let re = failed_re();
-    let bookmarks = jj::bookmark_list_glob("jjq/failed/??????")?;
+    let bookmarks = jj.bookmark_list_glob("jjq/failed/??????")?;
 
     let mut ids: Vec<u32> = bookmarks
         .iter()
@@ -136,12 +140,6 @@ pub fn get_failed() -> Result<Vec<u32>> {
     Ok(ids)
 }
 
-/// Get the next item to process (lowest sequence ID).
-pub fn next_item() -> Result<Option<u32>> {
-    let queue = get_queue()?;
-    Ok(queue.into_iter().next())
-}
-
 /// Get the queue bookmark name for an ID.
 pub fn queue_bookmark(id: u32) -> String {
     format!("jjq/queue/{}", format_seq_id(id))
@@ -152,12 +150,51 @@ pub fn failed_bookmark(id: u32) -> String {
     format!("jjq/failed/{}", format_seq_id(id))
 }
 
+/// Metadata file recording the strategy an item was pushed with.
+fn strategy_path(id: u32) -> String {
+    format!("strategy/{}", format_seq_id(id))
+}
+
+/// The strategy item `id` was pushed with, if it overrides the configured
+/// one.
+pub fn item_strategy(jj: &Jj, id: u32) -> Result<Option<Strategy>> {
+    match jj.file_show(&strategy_path(id), JJQ_BOOKMARK) {
+        Ok(value) => Strategy::try_from(value.trim())
+            .map(Some)
+            .map_err(|_| anyhow::anyhow!("invalid strategy for item {}: {}", id, value.trim())),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Metadata file recording who pushed an item.
+fn pusher_path(id: u32) -> String {
+    format!("pusher/{}", format_seq_id(id))
+}
+
+/// Who pushed item `id`, if recorded.
+pub fn item_pusher(jj: &Jj, id: u32) -> Result<Option<String>> {
+    match jj.file_show(&pusher_path(id), JJQ_BOOKMARK) {
+        Ok(value) => Ok(Some(value.trim().to_string())),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Remove the metadata recorded for item `id` once it has left both the
+/// queue and the failed list.
+pub fn forget_item(jj: &Jj, id: u32) -> Result<()> {
+    metadata::remove(
+        jj,
+        &[&pusher_path(id), &strategy_path(id)],
+        &format!("Sequence-Id: {}\nForget", id),
+    )
+}
+
 /// Check if a queue item exists.
-pub fn queue_item_exists(id: u32) -> Result<bool> {
-    jj::bookmark_exists(&queue_bookmark(id))
+pub fn queue_item_exists(jj: &Jj, id: u32) -> Result<bool> {
+    jj.bookmark_exists(&queue_bookmark(id))
 }
 
 /// Check if a failed item exists.
-pub fn failed_item_exists(id: u32) -> Result<bool> {
-    jj::bookmark_exists(&failed_bookmark(id))
+pub fn failed_item_exists(jj: &Jj, id: u32) -> Result<bool> {
+    jj.bookmark_exists(&failed_bookmark(id))
 }
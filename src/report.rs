@@ -0,0 +1,122 @@
+// ABOUTME: Parses JUnit XML and TAP test reports written by the check command.
+// ABOUTME: Extracts the names of failing tests for a failed item's metadata.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Maximum number of failing test names recorded for a failed item.
+pub const MAX_FAILING_TESTS: usize = 50;
+
+/// Read a test report and return the names of its failing tests, in report
+/// order without duplicates. The format is detected from the content: XML is
+/// parsed as JUnit, anything else as TAP.
+pub fn failing_tests(path: &Path) -> Result<Vec<String>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    parse(&content)
+}
+
+/// Parse report content, detecting JUnit XML or TAP.
+pub fn parse(content: &str) -> Result<Vec<String>> {
+    let names = if content.trim_start().starts_with('<') {
+        parse_junit(content)?
+    } else {
+        parse_tap(content)
+    };
+    let mut unique: Vec<String> = Vec::new();
+    for name in names {
+        // Names end up in single-line trailers
+        let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !name.is_empty() && !unique.contains(&name) {
+            unique.push(name);
+        }
+    }
+    Ok(unique)
+}
+
+/// Failing tests in a JUnit report: testcases with a failure or error child,
+/// named "classname::name" when a classname is present.
+fn parse_junit(content: &str) -> Result<Vec<String>> {
+    let doc = roxmltree::Document::parse(content).context("invalid JUnit XML")?;
+    let names = doc
+        .descendants()
+        .filter(|n| n.has_tag_name("testcase"))
+        .filter(|n| {
+            n.children()
+                .any(|c| c.has_tag_name("failure") || c.has_tag_name("error"))
+        })
+        .map(|n| {
+            let name = n.attribute("name").unwrap_or("");
+            match n.attribute("classname") {
+                Some(class) if !class.is_empty() => format!("{}::{}", class, name),
+                _ => name.to_string(),
+            }
+        })
+        .collect();
+    Ok(names)
+}
+
+/// Failing tests in a TAP stream: "not ok" lines, ignoring TODO and SKIP
+/// tests.
+fn parse_tap(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| line.trim_start().strip_prefix("not ok"))
+        .filter_map(|rest| {
+            let (desc, directive) = match rest.split_once('#') {
+                Some((desc, directive)) => (desc, directive.trim_start()),
+                None => (rest, ""),
+            };
+            let directive = directive.get(..4).unwrap_or_default();
+            if directive.eq_ignore_ascii_case("todo") || directive.eq_ignore_ascii_case("skip") {
+                return None;
+            }
+            let desc = desc.trim_start();
+            let number_len = desc
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(desc.len());
+            let (number, desc) = desc.split_at(number_len);
+            let desc = desc.trim_start();
+            let desc = desc.strip_prefix("- ").unwrap_or(desc).trim();
+            if desc.is_empty() {
+                Some(format!("test {}", number))
+            } else {
+                Some(desc.to_string())
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_junit() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites>
+  <testsuite name="unit" tests="4">
+    <testcase classname="math" name="adds"/>
+    <testcase classname="math" name="divides">
+      <failure message="assertion failed">expected 2, got 3</failure>
+    </testcase>
+    <testcase name="parses">
+      <error message="panicked"/>
+    </testcase>
+    <testcase classname="math" name="skipped"><skipped/></testcase>
+  </testsuite>
+</testsuites>"#;
+        assert_eq!(parse(xml).unwrap(), vec!["math::divides", "parses"]);
+    }
+
+    #[test]
+    fn test_parse_junit_invalid() {
+        assert!(parse("<testsuite><testcase>").is_err());
+    }
+
+    #[test]
+    fn test_parse_tap() {
+        let tap = "TAP version 13\n1..7\nok 1 - adds\nnot ok 2 - divides\n  ---\n  message: bad\n  ...\nnot ok 3 flaky # TODO fix later\nnot ok 4\nnot ok 5 - divides\nnot ok 6 - fetches # SKIP no network\nnot ok 7 # skip\n";
+        assert_eq!(parse(tap).unwrap(), vec!["divides", "test 4"]);
+    }
+}
@@ -1,8 +1,18 @@
-// ABOUTME: Utilities for the jjq run log file, including path resolution and sentinel markers.
+// ABOUTME: Utilities for the jjq run log files, including path resolution and sentinel markers.
 // ABOUTME: The sentinel line marks the end of a check command's output in the log.
 
 use anyhow::Result;
-use std::path::PathBuf;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use crate::config;
+use crate::jj::Jj;
 
 /// Prefix used to identify sentinel lines in log output.
 pub const SENTINEL_PREFIX: &str = "--- jjq: run complete";
@@ -13,7 +23,516 @@ pub fn sentinel_line(exit_code: i32) -> String {
 }
 
 /// Return the path to the jjq run log file within the repository's .jj directory.
-pub fn log_path() -> Result<PathBuf> {
-    let root = crate::jj::repo_root()?;
+pub fn log_path(jj: &Jj) -> Result<PathBuf> {
+    let root = jj.root();
     Ok(root.join(".jj").join("jjq-run.log"))
 }
+
+/// Return the path to the structured (JSON lines) run log.
+pub fn events_path(jj: &Jj) -> Result<PathBuf> {
+    Ok(events_path_for(&log_path(jj)?))
+}
+
+/// Return the structured log path that accompanies a plain-text log.
+pub fn events_path_for(log_path: &Path) -> PathBuf {
+    log_path.with_extension("jsonl")
+}
+
+/// Return the rolling file that holds the recent output of a capped run
+/// while it is held back from `path`.
+pub fn live_path_for(path: &Path) -> PathBuf {
+    let mut live = path.as_os_str().to_owned();
+    live.push(".live");
+    PathBuf::from(live)
+}
+
+/// Last non-empty line of a run log, ignoring the sentinel. Only the end of
+/// the file is read, so this is cheap on long logs.
+pub fn last_line(path: &Path) -> Option<String> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut file = File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(8192))).ok()?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail).ok()?;
+    String::from_utf8_lossy(&tail)
+        .lines()
+        .rev()
+        .map(str::trim_end)
+        .find(|line| !line.trim().is_empty() && !line.starts_with(SENTINEL_PREFIX))
+        .map(str::to_string)
+}
+
+/// Which output stream a log line came from. `Jjq` marks lines written by
+/// jjq itself (elision markers and the sentinel).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+    Stdout,
+    Stderr,
+    Jjq,
+}
+
+/// One line of the structured run log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEvent {
+    /// Milliseconds since the check command started.
+    pub elapsed_ms: u64,
+    pub stream: Stream,
+    pub text: String,
+}
+
+impl LogEvent {
+    /// Whether this event is the end-of-run sentinel.
+    pub fn is_sentinel(&self) -> bool {
+        self.stream == Stream::Jjq && self.text.starts_with(SENTINEL_PREFIX)
+    }
+}
+
+/// Format an elapsed-time prefix for a log line, e.g. "[   12.345s]".
+pub fn elapsed_prefix(elapsed_ms: u64) -> String {
+    format!("[{:>9.3}s]", elapsed_ms as f64 / 1000.0)
+}
+
+/// How check output is persisted to the run log.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LogOptions {
+    /// Maximum log size in bytes; output beyond it is elided from the middle.
+    pub max_bytes: Option<u64>,
+    /// Strip ANSI escape sequences from persisted lines.
+    pub strip_ansi: bool,
+}
+
+impl LogOptions {
+    /// Read log options from jjq config.
+    pub fn from_config(jj: &Jj) -> Result<LogOptions> {
+        Ok(LogOptions {
+            max_bytes: config::get_log_max_bytes(jj)?,
+            strip_ansi: config::get_log_strip_ansi(jj)?,
+        })
+    }
+}
+
+/// Build the marker line that replaces elided output.
+pub fn elided_line(count: u64) -> String {
+    format!("... {} lines elided ...", count)
+}
+
+/// Maximum number of pattern matches included in a failure excerpt.
+pub const EXCERPT_MAX_MATCHES: usize = 3;
+
+/// Lines of context kept after each matching line in a failure excerpt.
+pub const EXCERPT_CONTEXT_LINES: usize = 5;
+
+/// Longest line, in characters, kept in a failure excerpt.
+const EXCERPT_MAX_LINE: usize = 200;
+
+/// Extract the lines of a run log that identify a failure: each line matching
+/// one of `patterns`, followed by a few lines of context. Up to
+/// `EXCERPT_MAX_MATCHES` matches are kept; non-adjacent sections are
+/// separated by a "..." line. The first line is always the first match.
+pub fn failure_excerpt(log: &str, patterns: &[Regex]) -> Vec<String> {
+    let lines: Vec<&str> = log
+        .lines()
+        .filter(|l| !l.starts_with(SENTINEL_PREFIX))
+        .collect();
+    let mut excerpt = Vec::new();
+    let mut matches = 0;
+    // Index of the first line not yet copied into the excerpt
+    let mut next = 0;
+    for (i, line) in lines.iter().enumerate() {
+        if i < next || !patterns.iter().any(|p| p.is_match(line)) {
+            continue;
+        }
+        if matches == EXCERPT_MAX_MATCHES {
+            break;
+        }
+        if !excerpt.is_empty() && i > next {
+            excerpt.push("...".to_string());
+        }
+        next = (i + 1 + EXCERPT_CONTEXT_LINES).min(lines.len());
+        excerpt.extend(lines[i..next].iter().map(|l| truncate_line(l)));
+        matches += 1;
+    }
+    excerpt
+}
+
+/// Shorten a line to `EXCERPT_MAX_LINE` characters.
+fn truncate_line(line: &str) -> String {
+    match line.char_indices().nth(EXCERPT_MAX_LINE) {
+        Some((i, _)) => format!("{}...", &line[..i]),
+        None => line.to_string(),
+    }
+}
+
+/// Remove ANSI escape sequences (CSI, OSC and two-byte escapes) from a line.
+pub fn strip_ansi(line: &str) -> String {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)|\x1b[@-Z\\-_]")
+            .unwrap()
+    });
+    re.replace_all(line, "").into_owned()
+}
+
+/// Writes check output to the plain-text run log and its structured
+/// companion, honoring `LogOptions`.
+///
+/// With a size cap, the first half of the budget is written as output
+/// arrives; after that the most recent lines are held in memory and written
+/// by `finish`, preceded by an elision marker if anything was dropped.
+/// Meanwhile they are also appended to rolling live files (see
+/// `live_path_for`) so `jjq tail` can keep following the run; a live file
+/// starts over once it outgrows the tail budget.
+pub struct LogWriter {
+    file: File,
+    events: File,
+    log_path: PathBuf,
+    live: Option<LiveFiles>,
+    options: LogOptions,
+    start: Instant,
+    head_bytes: u64,
+    truncating: bool,
+    tail: VecDeque<LogEvent>,
+    tail_bytes: u64,
+    elided: u64,
+}
+
+/// The open live files of a capped run and how much has been written to them.
+struct LiveFiles {
+    file: File,
+    events: File,
+    bytes: u64,
+}
+
+impl LogWriter {
+    /// Create a writer for the logs opened at `log_path` and its structured
+    /// companion; timestamps are measured from `start`. Live files left by
+    /// an earlier run are removed.
+    pub fn new(
+        file: File,
+        events: File,
+        log_path: &Path,
+        options: LogOptions,
+        start: Instant,
+    ) -> LogWriter {
+        for path in [log_path.to_path_buf(), events_path_for(log_path)] {
+            let _ = fs::remove_file(live_path_for(&path));
+        }
+        LogWriter {
+            file,
+            events,
+            log_path: log_path.to_path_buf(),
+            live: None,
+            options,
+            start,
+            head_bytes: 0,
+            truncating: false,
+            tail: VecDeque::new(),
+            tail_bytes: 0,
+            elided: 0,
+        }
+    }
+
+    /// Record one line of output (without its trailing newline).
+    pub fn write_line(&mut self, stream: Stream, line: &str) -> io::Result<()> {
+        let text = if self.options.strip_ansi {
+            strip_ansi(line)
+        } else {
+            line.to_string()
+        };
+        let event = LogEvent {
+            elapsed_ms: self.start.elapsed().as_millis() as u64,
+            stream,
+            text,
+        };
+        let len = event.text.len() as u64 + 1;
+
+        let Some(max) = self.options.max_bytes else {
+            return self.emit(&event);
+        };
+
+        if !self.truncating && self.head_bytes + len <= max / 2 {
+            self.head_bytes += len;
+            return self.emit(&event);
+        }
+
+        self.truncating = true;
+        let tail_budget = max.saturating_sub(self.head_bytes);
+        self.emit_live(&event, tail_budget)?;
+        self.tail_bytes += len;
+        self.tail.push_back(event);
+        while self.tail_bytes > tail_budget {
+            let Some(dropped) = self.tail.pop_front() else {
+                break;
+            };
+            self.tail_bytes -= dropped.text.len() as u64 + 1;
+            self.elided += 1;
+        }
+        Ok(())
+    }
+
+    /// Write any retained tail output followed by the sentinel line.
+    pub fn finish(mut self, exit_code: i32) -> io::Result<()> {
+        if self.elided > 0 {
+            let marker = self.jjq_event(elided_line(self.elided));
+            self.emit(&marker)?;
+        }
+        for event in std::mem::take(&mut self.tail) {
+            self.emit(&event)?;
+        }
+        let sentinel = self.jjq_event(sentinel_line(exit_code));
+        // A follower reading the live file stops at its sentinel
+        if let Some(live) = &mut self.live {
+            write_event(&mut live.file, &mut live.events, &sentinel)?;
+        }
+        self.emit(&sentinel)
+    }
+
+    /// Append a held-back event to the live files, starting them over once
+    /// they hold more than `budget` bytes. A new file replaces the old one
+    /// by rename, so followers can tell it apart.
+    fn emit_live(&mut self, event: &LogEvent, budget: u64) -> io::Result<()> {
+        let len = event.text.len() as u64 + 1;
+        let full = self
+            .live
+            .as_ref()
+            .is_some_and(|live| live.bytes + len > budget);
+        if self.live.is_none() || full {
+            let log_path = live_path_for(&self.log_path);
+            let events_path = live_path_for(&events_path_for(&self.log_path));
+            let file = replace_file(&log_path)?;
+            let events = replace_file(&events_path)?;
+            self.live = Some(LiveFiles {
+                file,
+                events,
+                bytes: 0,
+            });
+        }
+        let live = self.live.as_mut().expect("live files were just opened");
+        live.bytes += len;
+        write_event(&mut live.file, &mut live.events, event)
+    }
+
+    fn jjq_event(&self, text: String) -> LogEvent {
+        LogEvent {
+            elapsed_ms: self.start.elapsed().as_millis() as u64,
+            stream: Stream::Jjq,
+            text,
+        }
+    }
+
+    /// Write an event to both logs.
+    fn emit(&mut self, event: &LogEvent) -> io::Result<()> {
+        write_event(&mut self.file, &mut self.events, event)
+    }
+}
+
+/// Write an event to a plain-text log and its structured companion.
+fn write_event(file: &mut File, events: &mut File, event: &LogEvent) -> io::Result<()> {
+    writeln!(file, "{}", event.text)?;
+    file.flush()?;
+    let json = serde_json::to_string(event).map_err(io::Error::other)?;
+    writeln!(events, "{}", json)?;
+    events.flush()
+}
+
+/// Create an empty file and move it into place at `path`.
+pub fn replace_file(path: &Path) -> io::Result<File> {
+    let mut new_path = path.as_os_str().to_owned();
+    new_path.push(".new");
+    let file = File::create(&new_path)?;
+    fs::rename(&new_path, path)?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_line_skips_sentinel_and_blank_lines() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("run.log");
+        std::fs::write(&path, format!("one\ntwo\n\n{}\n", sentinel_line(0))).unwrap();
+        assert_eq!(last_line(&path).as_deref(), Some("two"));
+        assert_eq!(last_line(&dir.path().join("missing.log")), None);
+    }
+
+    /// Write lines to a fresh log, returning (plain text, structured events).
+    fn write_lines(options: LogOptions, lines: &[(Stream, &str)]) -> (String, Vec<LogEvent>) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("run.log");
+        let events_path = events_path_for(&path);
+        let mut writer = LogWriter::new(
+            File::create(&path).unwrap(),
+            File::create(&events_path).unwrap(),
+            &path,
+            options,
+            Instant::now(),
+        );
+        for (stream, line) in lines {
+            writer.write_line(*stream, line).unwrap();
+        }
+        writer.finish(0).unwrap();
+        let events = std::fs::read_to_string(&events_path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        (std::fs::read_to_string(&path).unwrap(), events)
+    }
+
+    fn stdout(lines: &[&'static str]) -> Vec<(Stream, &'static str)> {
+        lines.iter().map(|l| (Stream::Stdout, *l)).collect()
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\x1b[1m\x1b[32mok\x1b[0m done"), "ok done");
+        assert_eq!(strip_ansi("\x1b]8;;http://x\x07link\x1b]8;;\x07"), "link");
+        assert_eq!(strip_ansi("plain"), "plain");
+    }
+
+    #[test]
+    fn test_log_writer_unbounded() {
+        let options = LogOptions {
+            max_bytes: None,
+            strip_ansi: true,
+        };
+        let (text, _) = write_lines(options, &stdout(&["\x1b[31ma\x1b[0m", "b"]));
+        assert_eq!(text, format!("a\nb\n{}\n", sentinel_line(0)));
+    }
+
+    #[test]
+    fn test_log_writer_keeps_head_and_tail() {
+        let options = LogOptions {
+            max_bytes: Some(12),
+            strip_ansi: false,
+        };
+        let lines = stdout(&["l1", "l2", "l3", "l4", "l5", "l6", "l7"]);
+        let (text, _) = write_lines(options, &lines);
+        assert_eq!(
+            text,
+            format!(
+                "l1\nl2\n... 3 lines elided ...\nl6\nl7\n{}\n",
+                sentinel_line(0)
+            )
+        );
+    }
+
+    #[test]
+    fn test_log_writer_live_tail() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("run.log");
+        let live = live_path_for(&path);
+        std::fs::write(&live, "stale\n").unwrap();
+        let options = LogOptions {
+            max_bytes: Some(12),
+            strip_ansi: false,
+        };
+        let mut writer = LogWriter::new(
+            File::create(&path).unwrap(),
+            File::create(events_path_for(&path)).unwrap(),
+            &path,
+            options,
+            Instant::now(),
+        );
+        assert!(!live.exists());
+
+        // Held-back lines are followed in the live file, which starts over
+        // once it outgrows the 6-byte tail budget
+        for line in ["l1", "l2", "l3", "l4"] {
+            writer.write_line(Stream::Stdout, line).unwrap();
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "l1\nl2\n");
+        assert_eq!(std::fs::read_to_string(&live).unwrap(), "l3\nl4\n");
+        writer.write_line(Stream::Stdout, "l5").unwrap();
+        writer.write_line(Stream::Stdout, "l6").unwrap();
+        assert_eq!(std::fs::read_to_string(&live).unwrap(), "l5\nl6\n");
+
+        writer.finish(1).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&live).unwrap(),
+            format!("l5\nl6\n{}\n", sentinel_line(1))
+        );
+        let events = std::fs::read_to_string(live_path_for(&events_path_for(&path))).unwrap();
+        assert_eq!(events.lines().count(), 3);
+    }
+
+    #[test]
+    fn test_log_writer_under_cap() {
+        let options = LogOptions {
+            max_bytes: Some(100),
+            strip_ansi: false,
+        };
+        let (text, _) = write_lines(options, &stdout(&["a", "b", "c"]));
+        assert_eq!(text, format!("a\nb\nc\n{}\n", sentinel_line(0)));
+    }
+
+    #[test]
+    fn test_log_writer_structured_events() {
+        let lines = [
+            (Stream::Stdout, "running 2 tests"),
+            (Stream::Stderr, "warning: slow"),
+        ];
+        let (_, events) = write_lines(LogOptions::default(), &lines);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].stream, Stream::Stdout);
+        assert_eq!(events[1].stream, Stream::Stderr);
+        assert_eq!(events[1].text, "warning: slow");
+        assert!(events[2].is_sentinel());
+        assert!(events[0].elapsed_ms <= events[2].elapsed_ms);
+    }
+
+    #[test]
+    fn test_failure_excerpt() {
+        let patterns = vec![
+            Regex::new(r"error\[E").unwrap(),
+            Regex::new("panicked at").unwrap(),
+        ];
+        let mut log: Vec<String> = vec!["   Compiling foo".into()];
+        log.push("error[E0308]: mismatched types".into());
+        log.extend((1..=7).map(|n| format!("context {}", n)));
+        log.push("thread 'main' panicked at src/lib.rs:1:1".into());
+        log.push(sentinel_line(1));
+        let excerpt = failure_excerpt(&log.join("\n"), &patterns);
+        assert_eq!(
+            excerpt,
+            vec![
+                "error[E0308]: mismatched types",
+                "context 1",
+                "context 2",
+                "context 3",
+                "context 4",
+                "context 5",
+                "...",
+                "thread 'main' panicked at src/lib.rs:1:1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failure_excerpt_limits() {
+        let patterns = vec![Regex::new("FAILED").unwrap()];
+        let log = (0..30)
+            .map(|n| format!("test {} FAILED", n))
+            .collect::<Vec<_>>();
+        let excerpt = failure_excerpt(&log.join("\n"), &patterns);
+        // Matches inside an earlier match's context are not counted again
+        assert_eq!(
+            excerpt.len(),
+            EXCERPT_MAX_MATCHES * (1 + EXCERPT_CONTEXT_LINES)
+        );
+        assert_eq!(excerpt[0], "test 0 FAILED");
+        assert!(failure_excerpt("all good", &patterns).is_empty());
+        assert_eq!(truncate_line(&"x".repeat(250)).len(), EXCERPT_MAX_LINE + 3);
+    }
+
+    #[test]
+    fn test_elapsed_prefix() {
+        assert_eq!(elapsed_prefix(0), "[    0.000s]");
+        assert_eq!(elapsed_prefix(12345), "[   12.345s]");
+    }
+}
@@ -1,22 +1,259 @@
 // ABOUTME: Executes the check command as a child process, capturing output to a log file.
 // ABOUTME: Provides spinner progress and keypress-toggled live output for interactive terminals.
 
-use anyhow::{Context, Result};
-use std::fs::{self, File, OpenOptions};
-use std::io::{BufRead, BufReader, IsTerminal, Read as _, Seek, SeekFrom, Write};
-use std::path::Path;
-use std::process::{Command, ExitStatus, Stdio};
+use anyhow::{Context, Result, bail};
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{BufRead, BufReader, IsTerminal, Read, Write};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use crate::runlog;
+use crate::config;
+use crate::jj::Jj;
+use crate::runlog::{self, LogOptions, LogWriter, Stream};
+
+/// The shell, working directory, environment and resource limits a check
+/// command runs with.
+pub struct CheckEnv {
+    pub shell: String,
+    pub dir: PathBuf,
+    pub vars: Vec<(OsString, OsString)>,
+    pub limits: Limits,
+}
+
+/// Optional resource limits applied to the check process with setrlimit.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Limits {
+    /// Address space, in bytes (RLIMIT_AS).
+    pub memory: Option<u64>,
+    /// CPU time, in seconds (RLIMIT_CPU).
+    pub cpu: Option<u64>,
+    /// Open file descriptors (RLIMIT_NOFILE).
+    pub files: Option<u64>,
+    /// Processes for the invoking user (RLIMIT_NPROC).
+    pub procs: Option<u64>,
+}
+
+impl Limits {
+    /// Read configured limits from jjq config.
+    pub fn from_config(jj: &Jj) -> Result<Limits> {
+        Ok(Limits {
+            memory: config::get_check_limit_memory(jj)?,
+            cpu: config::get_check_limit_cpu(jj)?,
+            files: config::get_check_limit_files(jj)?,
+            procs: config::get_check_limit_procs(jj)?,
+        })
+    }
+
+    /// Apply the limits to the current process. Called in the child between
+    /// fork and exec, so it only makes async-signal-safe libc calls.
+    ///
+    /// Limits never raise an existing hard limit. The CPU hard limit is one
+    /// second above the soft limit so SIGXCPU is delivered before SIGKILL.
+    fn apply(&self) -> std::io::Result<()> {
+        macro_rules! set_limit {
+            ($resource:expr, $value:expr, $slack:expr) => {
+                if let Some(value) = $value {
+                    unsafe {
+                        let mut rl: libc::rlimit = std::mem::zeroed();
+                        if libc::getrlimit($resource, &mut rl) != 0 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                        let value = value as libc::rlim_t;
+                        rl.rlim_cur = value.min(rl.rlim_max);
+                        rl.rlim_max = value.saturating_add($slack).min(rl.rlim_max);
+                        if libc::setrlimit($resource, &rl) != 0 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                    }
+                }
+            };
+        }
+        set_limit!(libc::RLIMIT_AS, self.memory, 0);
+        set_limit!(libc::RLIMIT_CPU, self.cpu, 1);
+        set_limit!(libc::RLIMIT_NOFILE, self.files, 0);
+        set_limit!(libc::RLIMIT_NPROC, self.procs, 0);
+        Ok(())
+    }
+
+    /// Determine whether a failed check was stopped by one of the limits,
+    /// returning the failure reason to record.
+    ///
+    /// The CPU limit is recognized by how the check ended: killed by SIGXCPU
+    /// (directly or via the shell's 128+N exit code), or by SIGKILL after
+    /// using up its CPU time. The other limits make system calls fail rather
+    /// than stop the check, so they are recognized by the error the check
+    /// printed to `output`: ENOMEM for memory, EMFILE for open files and
+    /// EAGAIN (from fork) for processes. Only limits that were set count.
+    pub fn violation(&self, exit: &CheckExit, output: &str) -> Option<&'static str> {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(cpu) = self.cpu {
+            let xcpu = exit.status.signal() == Some(libc::SIGXCPU)
+                || exit.status.code() == Some(128 + libc::SIGXCPU)
+                || (exit.status.signal() == Some(libc::SIGKILL)
+                    && exit.cpu_time >= Duration::from_secs(cpu));
+            if xcpu {
+                return Some("cpu-limit");
+            }
+        }
+        let output = output.to_lowercase();
+        let printed = |markers: &[&str]| markers.iter().any(|m| output.contains(m));
+        if self.memory.is_some() && printed(MEMORY_ERRORS) {
+            Some("memory-limit")
+        } else if self.files.is_some() && printed(FILES_ERRORS) {
+            Some("nofile-limit")
+        } else if self.procs.is_some() && printed(PROCS_ERRORS) {
+            Some("nproc-limit")
+        } else {
+            None
+        }
+    }
+
+    /// The limits that were set, as a `jjq-limits` trailer value such as
+    /// `memory=1073741824 cpu=60`. None if no limit was set.
+    pub fn describe(&self) -> Option<String> {
+        let set: Vec<String> = [
+            ("memory", self.memory),
+            ("cpu", self.cpu),
+            ("files", self.files),
+            ("procs", self.procs),
+        ]
+        .iter()
+        .filter_map(|(name, value)| value.map(|value| format!("{}={}", name, value)))
+        .collect();
+        (!set.is_empty()).then(|| set.join(" "))
+    }
+}
+
+/// Lowercased error messages printed when an allocation fails under the
+/// memory limit: the C library's ENOMEM message, and those of common
+/// runtimes (Go, Rust, C++, Python).
+const MEMORY_ERRORS: &[&str] = &[
+    "cannot allocate memory",
+    "out of memory",
+    "memory allocation of",
+    "bad_alloc",
+    "memoryerror",
+];
+
+/// Lowercased EMFILE message, printed when the open files limit is reached.
+const FILES_ERRORS: &[&str] = &["too many open files"];
+
+/// Lowercased EAGAIN message, printed when fork fails under the process
+/// limit.
+const PROCS_ERRORS: &[&str] = &["resource temporarily unavailable"];
+
+/// How a check command exited.
+#[derive(Debug, Clone, Copy)]
+pub struct CheckExit {
+    pub status: ExitStatus,
+    /// User and system CPU time used by the check and the processes it waited
+    /// for.
+    pub cpu_time: Duration,
+    /// Whether the check was stopped because `cancel` returned true, rather
+    /// than exiting on its own.
+    pub cancelled: bool,
+}
+
+impl CheckExit {
+    pub fn success(&self) -> bool {
+        self.status.success()
+    }
+}
+
+/// Total user and system CPU time of this process's waited-for children.
+fn children_cpu_time() -> Duration {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_CHILDREN, &mut usage) } != 0 {
+        return Duration::ZERO;
+    }
+    let seconds = |tv: libc::timeval| {
+        Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64)
+    };
+    seconds(usage.ru_utime) + seconds(usage.ru_stime)
+}
+
+impl CheckEnv {
+    /// Build the check environment for a workspace from jjq config.
+    ///
+    /// The parent environment is filtered through `check_env_allow` and
+    /// `check_env_deny`, then `jjq_vars` (JJQ_TRUNK etc.) are added on top
+    /// along with JJQ_WORKSPACE.
+    pub fn from_config(jj: &Jj, workspace: &Path, jjq_vars: &[(&str, String)]) -> Result<CheckEnv> {
+        let shell = config::get_check_shell(jj)?;
+        let dir = match config::get_check_workdir(jj)? {
+            Some(subdir) => workspace.join(subdir),
+            None => workspace.to_path_buf(),
+        };
+        if !dir.is_dir() {
+            bail!("check working directory {} does not exist", dir.display());
+        }
+
+        let allow = config::get_list(jj, "check_env_allow")?;
+        let deny = config::get_list(jj, "check_env_deny")?;
+        // Variables need not be UTF-8; they are passed through unchanged
+        let mut vars = filter_env(env::vars_os().collect(), &allow, &deny);
+        vars.retain(|(k, _)| !k.to_string_lossy().starts_with("JJQ_"));
+        vars.push(("JJQ_WORKSPACE".into(), workspace.as_os_str().to_owned()));
+        for (key, value) in jjq_vars {
+            vars.push((key.into(), value.into()));
+        }
+        vars.sort();
+
+        let limits = Limits::from_config(jj)?;
+
+        Ok(CheckEnv {
+            shell,
+            dir,
+            vars,
+            limits,
+        })
+    }
+}
+
+/// Check whether an environment variable name matches a pattern.
+/// A trailing `*` matches any suffix (e.g. `CARGO_*`).
+fn env_pattern_matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == pattern,
+    }
+}
+
+/// Apply allow and deny lists to environment variables. An empty allow list
+/// passes everything through; deny is applied after allow. Names that aren't
+/// UTF-8 are matched in their lossy form.
+fn filter_env(
+    vars: Vec<(OsString, OsString)>,
+    allow: &[String],
+    deny: &[String],
+) -> Vec<(OsString, OsString)> {
+    let matches = |patterns: &[String], name: &OsString| {
+        let name = name.to_string_lossy();
+        patterns.iter().any(|p| env_pattern_matches(p, &name))
+    };
+    vars.into_iter()
+        .filter(|(k, _)| allow.is_empty() || matches(allow, k))
+        .filter(|(k, _)| !matches(deny, k))
+        .collect()
+}
 
 /// Braille spinner frames for the interactive wait loop.
 const SPINNER_FRAMES: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
 
 /// Format a duration as human-readable elapsed time.
 /// Under 60 seconds: "Xs", otherwise "Nm Xs".
-fn format_duration(d: Duration) -> String {
+pub(crate) fn format_duration(d: Duration) -> String {
     let secs = d.as_secs();
     if secs < 60 {
         format!("{}s", secs)
@@ -70,126 +307,228 @@ impl Drop for RawMode {
     }
 }
 
-/// Print the last `n` lines from a log file to stderr, skipping sentinel lines.
-fn show_tail_lines(path: &Path, n: usize) {
-    let Ok(contents) = fs::read_to_string(path) else {
-        return;
-    };
-    let lines: Vec<&str> = contents
-        .lines()
-        .filter(|l| !l.starts_with(runlog::SENTINEL_PREFIX))
-        .collect();
-    let start = lines.len().saturating_sub(n);
-    for line in &lines[start..] {
-        eprintln!("{}", line);
-    }
-}
+/// Number of recent output lines shown when live output is toggled on.
+const LIVE_CONTEXT_LINES: usize = 20;
 
-/// Read new content from `path` starting at byte offset `pos`, print lines to
-/// stderr (skipping sentinel lines), and update `pos` to the new end.
-fn stream_from_pos(path: &Path, pos: &mut u64) {
-    let Ok(mut file) = File::open(path) else {
-        return;
-    };
-    if file.seek(SeekFrom::Start(*pos)).is_err() {
-        return;
-    }
-    let mut buf = String::new();
-    if let Ok(n) = file.read_to_string(&mut buf) {
-        for line in buf.lines() {
-            if !line.starts_with(runlog::SENTINEL_PREFIX) {
-                eprintln!("{}", line);
-            }
-        }
-        *pos += n as u64;
-    }
-}
+/// How long a cancelled check gets to exit after SIGTERM before SIGKILL.
+const KILL_GRACE: Duration = Duration::from_secs(5);
+
+/// Process group of the running check, stopped if jjq is interrupted.
+static CHECK_PGID: AtomicI32 = AtomicI32::new(0);
 
-/// Run a check command, logging its merged stdout+stderr to `log_path`.
+/// Run a check command, logging its stdout and stderr to `log_path` and,
+/// tagged by stream with timestamps, to the structured log beside it.
 ///
-/// Returns the child's exit status. A sentinel line is appended to the log
-/// after the child exits regardless of success or failure.
-pub fn run_check_command(command: &str, log_path: &Path) -> Result<ExitStatus> {
+/// Output is persisted according to `log_options` (size cap, ANSI
+/// stripping); live output in interactive mode is streamed unmodified.
+/// The check runs in its own process group. `cancel` is polled while it
+/// runs; once it returns true the whole group is stopped.
+///
+/// Returns the child's exit status and CPU time, and whether it was
+/// cancelled. A sentinel line is appended to the logs after the child exits
+/// regardless of success or failure.
+pub fn run_check_command(
+    command: &str,
+    check_env: &CheckEnv,
+    log_options: &LogOptions,
+    log_path: &Path,
+    cancel: &dyn Fn() -> bool,
+) -> Result<CheckExit> {
     // Ensure parent directories exist.
     if let Some(parent) = log_path.parent() {
         fs::create_dir_all(parent)
             .with_context(|| format!("creating log directory {}", parent.display()))?;
     }
 
-    // Truncate/create the log file.
-    let log_file = File::create(log_path)
+    // Replace the log files, so `jjq tail` sees a new file for a new run.
+    let log_file = runlog::replace_file(log_path)
         .with_context(|| format!("creating log file {}", log_path.display()))?;
-
-    // Spawn child: sh -c "<command> 2>&1" with stdout piped.
-    let mut child = Command::new("sh")
-        .arg("-c")
-        .arg(format!("{} 2>&1", command))
+    let events_path = runlog::events_path_for(log_path);
+    let events_file = runlog::replace_file(&events_path)
+        .with_context(|| format!("creating log file {}", events_path.display()))?;
+
+    // Spawn child: <shell> -c "<command>" with stdout and stderr piped.
+    let mut cmd = Command::new(&check_env.shell);
+    cmd.arg("-c")
+        .arg(command)
+        .current_dir(&check_env.dir)
+        .env_clear()
+        .envs(check_env.vars.iter().map(|(k, v)| (k, v)))
         .stdout(Stdio::piped())
-        .stderr(Stdio::null())
+        .stderr(Stdio::piped())
+        .process_group(0);
+    let limits = check_env.limits;
+    unsafe {
+        cmd.pre_exec(move || limits.apply());
+    }
+    let start = Instant::now();
+    let cpu_before = children_cpu_time();
+    let mut child = cmd
         .spawn()
         .with_context(|| format!("spawning check command: {}", command))?;
-
-    // Take the child's stdout pipe for the reader thread.
-    let child_stdout = child.stdout.take().expect("child stdout was piped");
-
-    // Spawn a reader thread that writes each line to the log file.
-    let mut log_writer = log_file;
-    let reader_handle = thread::spawn(move || -> Result<()> {
-        let reader = BufReader::new(child_stdout);
-        for line in reader.lines() {
-            let line = line.context("reading child stdout")?;
-            writeln!(log_writer, "{}", line).context("writing to log file")?;
-            log_writer.flush().context("flushing log file")?;
-        }
-        Ok(())
-    });
+    CHECK_PGID.store(child.id() as i32, Ordering::SeqCst);
 
     let interactive = std::io::stderr().is_terminal();
 
-    // Set up ctrlc handler to restore terminal before exiting.
-    if interactive {
-        let original_termios = unsafe {
-            let mut t: libc::termios = std::mem::zeroed();
-            libc::tcgetattr(libc::STDIN_FILENO, &mut t);
-            t
-        };
-        // ctrlc::set_handler can only be called once per process; subsequent
-        // calls are silently ignored (ctrlc v2 returns () regardless).
-        ctrlc::set_handler(move || {
+    // In interactive mode, raw lines are also sent to the wait loop so live
+    // output keeps its colors even when the persisted log is stripped.
+    let (live_tx, live_rx) = if interactive {
+        let (tx, rx) = mpsc::channel();
+        (Some(tx), Some(rx))
+    } else {
+        (None, None)
+    };
+
+    // One reader thread per stream, sharing the log writer.
+    let log_writer = Arc::new(Mutex::new(LogWriter::new(
+        log_file,
+        events_file,
+        log_path,
+        *log_options,
+        start,
+    )));
+    let stdout_handle = spawn_reader(
+        child.stdout.take().expect("child stdout was piped"),
+        Stream::Stdout,
+        Arc::clone(&log_writer),
+        live_tx.clone(),
+    );
+    let stderr_handle = spawn_reader(
+        child.stderr.take().expect("child stderr was piped"),
+        Stream::Stderr,
+        Arc::clone(&log_writer),
+        live_tx,
+    );
+
+    // Set up ctrlc handler to stop the check (which is in its own process
+    // group, so doesn't get the terminal's SIGINT) and restore the terminal
+    // before exiting.
+    let original_termios = interactive.then(|| unsafe {
+        let mut t: libc::termios = std::mem::zeroed();
+        libc::tcgetattr(libc::STDIN_FILENO, &mut t);
+        t
+    });
+    // ctrlc::set_handler can only be called once per process; subsequent
+    // calls are silently ignored (ctrlc v2 returns () regardless).
+    ctrlc::set_handler(move || {
+        let pgid = CHECK_PGID.load(Ordering::SeqCst);
+        if pgid > 0 {
             unsafe {
-                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &original_termios);
+                libc::killpg(pgid, libc::SIGTERM);
+            }
+        }
+        if let Some(termios) = &original_termios {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, termios);
             }
             eprint!("\r\x1b[2K");
-            std::process::exit(130);
-        });
-    }
+        }
+        std::process::exit(130);
+    });
+
+    // The wait loops only poll `cancel` while the child runs and stop it as
+    // soon as it returns true, so a true answer means it was cancelled
+    let cancelled = Cell::new(false);
+    let cancel = || {
+        let stop = cancel();
+        cancelled.set(cancelled.get() || stop);
+        stop
+    };
 
     // Wait loop: behaviour depends on whether stderr is a TTY.
-    let status = if interactive {
-        wait_interactive(&mut child, log_path)?
+    let status = if let Some(live_rx) = live_rx {
+        wait_interactive(&mut child, &live_rx, &cancel)
     } else {
-        wait_non_interactive(&mut child)?
+        wait_non_interactive(&mut child, &cancel)
     };
+    CHECK_PGID.store(0, Ordering::SeqCst);
+    let status = status?;
 
-    // Join the reader thread and propagate any I/O errors.
-    reader_handle
-        .join()
-        .expect("reader thread panicked")?;
+    // Join the reader threads and propagate any I/O errors.
+    stdout_handle.join().expect("reader thread panicked")?;
+    stderr_handle.join().expect("reader thread panicked")?;
 
-    // Append sentinel line.
+    // Write retained output and the sentinel line.
     let exit_code = status.code().unwrap_or(-1);
-    let mut log_append = OpenOptions::new()
-        .append(true)
-        .open(log_path)
-        .with_context(|| format!("reopening log file {}", log_path.display()))?;
-    writeln!(log_append, "{}", runlog::sentinel_line(exit_code))
+    let log_writer = Arc::try_unwrap(log_writer)
+        .ok()
+        .expect("reader threads finished")
+        .into_inner()
+        .expect("log writer lock poisoned");
+    log_writer
+        .finish(exit_code)
         .context("writing sentinel line")?;
 
-    Ok(status)
+    Ok(CheckExit {
+        status,
+        cpu_time: children_cpu_time().saturating_sub(cpu_before),
+        cancelled: cancelled.get(),
+    })
+}
+
+/// Spawn a thread that reads lines from a child pipe into the log writer.
+/// Output is read as bytes so non-UTF-8 output is logged lossily instead of
+/// failing.
+fn spawn_reader(
+    pipe: impl Read + Send + 'static,
+    stream: Stream,
+    log_writer: Arc<Mutex<LogWriter>>,
+    live_tx: Option<Sender<String>>,
+) -> thread::JoinHandle<Result<()>> {
+    thread::spawn(move || -> Result<()> {
+        let mut reader = BufReader::new(pipe);
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            let n = reader
+                .read_until(b'\n', &mut buf)
+                .context("reading child output")?;
+            if n == 0 {
+                break;
+            }
+            if buf.last() == Some(&b'\n') {
+                buf.pop();
+            }
+            let line = String::from_utf8_lossy(&buf);
+            log_writer
+                .lock()
+                .expect("log writer lock poisoned")
+                .write_line(stream, &line)
+                .context("writing to log file")?;
+            if let Some(tx) = &live_tx {
+                let _ = tx.send(line.into_owned());
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Stop a check's whole process group: SIGTERM, then SIGKILL if it is still
+/// running after KILL_GRACE. Descendants are killed even if the shell exits,
+/// so the output pipes close.
+fn stop_check(child: &mut Child) -> Result<ExitStatus> {
+    let pgid = child.id() as libc::pid_t;
+    unsafe {
+        libc::killpg(pgid, libc::SIGTERM);
+    }
+    let deadline = Instant::now() + KILL_GRACE;
+    loop {
+        let status = child.try_wait().context("polling child process")?;
+        if status.is_some() || Instant::now() >= deadline {
+            unsafe {
+                libc::killpg(pgid, libc::SIGKILL);
+            }
+            return match status {
+                Some(status) => Ok(status),
+                None => child.wait().context("waiting for child process"),
+            };
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
 }
 
 /// Non-interactive wait: poll every second, emit heartbeat every 15 seconds.
-fn wait_non_interactive(child: &mut std::process::Child) -> Result<ExitStatus> {
+fn wait_non_interactive(child: &mut Child, cancel: &dyn Fn() -> bool) -> Result<ExitStatus> {
     let start = Instant::now();
     let mut last_heartbeat = Instant::now();
     let heartbeat_interval = Duration::from_secs(15);
@@ -199,6 +538,9 @@ fn wait_non_interactive(child: &mut std::process::Child) -> Result<ExitStatus> {
         if let Some(status) = child.try_wait().context("polling child process")? {
             return Ok(status);
         }
+        if cancel() {
+            return stop_check(child);
+        }
 
         thread::sleep(poll_interval);
 
@@ -211,13 +553,17 @@ fn wait_non_interactive(child: &mut std::process::Child) -> Result<ExitStatus> {
 }
 
 /// Interactive wait: show a spinner with elapsed time, allow pressing `v` to
-/// toggle live output streaming from the log file.
-fn wait_interactive(child: &mut std::process::Child, log_path: &Path) -> Result<ExitStatus> {
+/// toggle live output streaming of the lines received on `live`.
+fn wait_interactive(
+    child: &mut Child,
+    live: &Receiver<String>,
+    cancel: &dyn Fn() -> bool,
+) -> Result<ExitStatus> {
     let start = Instant::now();
     let poll_interval = Duration::from_millis(100);
     let mut frame_idx: usize = 0;
     let mut streaming = false;
-    let mut stream_pos: u64 = 0;
+    let mut recent: VecDeque<String> = VecDeque::new();
 
     let _raw_mode = RawMode::enter();
 
@@ -228,34 +574,47 @@ fn wait_interactive(child: &mut std::process::Child, log_path: &Path) -> Result<
         {
             streaming = !streaming;
             if streaming {
-                // Clear spinner line, print header, show tail context.
+                // Clear spinner line, print header, show recent context.
                 eprint!("\r\x1b[2K");
                 eprintln!("jjq: --- check output (press v to hide) ---");
-                show_tail_lines(log_path, 20);
-                // Set stream position to current end of file.
-                stream_pos = fs::metadata(log_path)
-                    .map(|m| m.len())
-                    .unwrap_or(0);
+                for line in &recent {
+                    eprintln!("{}", line);
+                }
             } else {
                 eprintln!("jjq: --- output hidden ---");
             }
         }
 
+        // Receive new output, printing it if streaming.
+        while let Ok(line) = live.try_recv() {
+            if streaming {
+                eprintln!("{}", line);
+            }
+            recent.push_back(line);
+            if recent.len() > LIVE_CONTEXT_LINES {
+                recent.pop_front();
+            }
+        }
+
         // Check if child exited.
         if let Some(status) = child.try_wait().context("polling child process")? {
             if streaming {
-                // Flush remaining output.
-                stream_from_pos(log_path, &mut stream_pos);
+                // Flush remaining output until the reader thread finishes.
+                for line in live.iter() {
+                    eprintln!("{}", line);
+                }
             } else {
                 // Clear spinner line.
                 eprint!("\r\x1b[2K");
             }
             return Ok(status);
         }
+        if cancel() {
+            eprint!("\r\x1b[2K");
+            return stop_check(child);
+        }
 
-        if streaming {
-            stream_from_pos(log_path, &mut stream_pos);
-        } else {
+        if !streaming {
             let elapsed = format_duration(start.elapsed());
             let spinner = SPINNER_FRAMES[frame_idx % SPINNER_FRAMES.len()];
             eprint!("\r\x1b[2Kjjq: running check {} {}", spinner, elapsed);
@@ -278,6 +637,162 @@ mod tests {
         assert_eq!(format_duration(Duration::from_secs(59)), "59s");
     }
 
+    fn vars(names: &[&str]) -> Vec<(OsString, OsString)> {
+        names.iter().map(|n| (n.into(), OsString::new())).collect()
+    }
+
+    fn names(vars: &[(OsString, OsString)]) -> Vec<&str> {
+        vars.iter().map(|(k, _)| k.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_filter_env_allow_and_deny() {
+        let all = vars(&[
+            "PATH",
+            "HOME",
+            "CARGO_HOME",
+            "CARGO_TARGET_DIR",
+            "AWS_SECRET",
+        ]);
+
+        let passed = filter_env(all.clone(), &[], &[]);
+        assert_eq!(passed.len(), 5);
+
+        let allowed = filter_env(all.clone(), &["PATH".into(), "CARGO_*".into()], &[]);
+        assert_eq!(names(&allowed), ["PATH", "CARGO_HOME", "CARGO_TARGET_DIR"]);
+
+        let denied = filter_env(
+            all,
+            &["PATH".into(), "CARGO_*".into()],
+            &["CARGO_HOME".into()],
+        );
+        assert_eq!(names(&denied), ["PATH", "CARGO_TARGET_DIR"]);
+    }
+
+    #[test]
+    fn test_filter_env_non_utf8_value() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let mut all = vars(&["PATH", "HOME"]);
+        all[1].1 = OsString::from_vec(vec![b'/', 0xff, 0xfe]);
+
+        let passed = filter_env(all.clone(), &["HOME".into()], &[]);
+        assert_eq!(passed, [all[1].clone()]);
+        let denied = filter_env(all, &[], &["HOME".into()]);
+        assert_eq!(names(&denied), ["PATH"]);
+    }
+
+    #[test]
+    fn test_limit_violation() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let none = Limits::default();
+        let all = Limits {
+            memory: Some(1 << 30),
+            cpu: Some(60),
+            files: Some(256),
+            procs: Some(64),
+        };
+        let exit = |raw: i32, cpu_secs: u64| CheckExit {
+            status: ExitStatus::from_raw(raw),
+            cpu_time: Duration::from_secs(cpu_secs),
+            cancelled: false,
+        };
+        let xcpu = exit(libc::SIGXCPU, 60);
+        let shell_xcpu = exit((128 + libc::SIGXCPU) << 8, 60);
+
+        assert_eq!(all.violation(&xcpu, ""), Some("cpu-limit"));
+        assert_eq!(all.violation(&shell_xcpu, ""), Some("cpu-limit"));
+        assert_eq!(none.violation(&xcpu, ""), None);
+        // SIGKILL only counts once the CPU time has been used up
+        assert_eq!(
+            all.violation(&exit(libc::SIGKILL, 61), ""),
+            Some("cpu-limit")
+        );
+        assert_eq!(all.violation(&exit(libc::SIGKILL, 2), ""), None);
+        assert_eq!(all.violation(&exit(1 << 8, 60), ""), None);
+
+        // The other limits are recognized by the errors the check printed
+        let failed = exit(1 << 8, 1);
+        let oom = "fatal error: runtime: out of memory";
+        let emfile = "open foo.txt: Too many open files";
+        let eagain = "sh: fork: retry: Resource temporarily unavailable";
+        assert_eq!(all.violation(&failed, oom), Some("memory-limit"));
+        assert_eq!(all.violation(&failed, emfile), Some("nofile-limit"));
+        assert_eq!(all.violation(&failed, eagain), Some("nproc-limit"));
+        assert_eq!(none.violation(&failed, oom), None);
+        assert_eq!(none.violation(&failed, emfile), None);
+        assert_eq!(none.violation(&failed, eagain), None);
+        assert_eq!(all.violation(&failed, "FAIL: TestParse"), None);
+    }
+
+    #[test]
+    fn test_limits_describe() {
+        assert_eq!(Limits::default().describe(), None);
+        let limits = Limits {
+            memory: Some(1 << 30),
+            procs: Some(64),
+            ..Limits::default()
+        };
+        assert_eq!(
+            limits.describe().as_deref(),
+            Some("memory=1073741824 procs=64")
+        );
+    }
+
+    #[test]
+    fn test_run_check_command_applies_limits() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let log_path = dir.path().join("run.log");
+        let check_env = CheckEnv {
+            shell: "/bin/sh".to_string(),
+            dir: dir.path().to_path_buf(),
+            vars: vec![("PATH".into(), env::var_os("PATH").unwrap_or_default())],
+            limits: Limits {
+                files: Some(64),
+                ..Limits::default()
+            },
+        };
+        let status = run_check_command(
+            "ulimit -n",
+            &check_env,
+            &LogOptions::default(),
+            &log_path,
+            &|| false,
+        )
+        .unwrap();
+        assert!(status.success());
+        assert!(!status.cancelled);
+        let log = fs::read_to_string(&log_path).unwrap();
+        assert!(log.starts_with("64\n"), "unexpected log: {}", log);
+    }
+
+    #[test]
+    fn test_run_check_command_cancel_stops_process_group() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let log_path = dir.path().join("run.log");
+        let check_env = CheckEnv {
+            shell: "/bin/sh".to_string(),
+            dir: dir.path().to_path_buf(),
+            vars: vec![("PATH".into(), env::var_os("PATH").unwrap_or_default())],
+            limits: Limits::default(),
+        };
+        // The backgrounded sleep holds the output pipes open, so this only
+        // returns promptly if the whole group is killed
+        let started = Instant::now();
+        let status = run_check_command(
+            "sleep 30 & wait",
+            &check_env,
+            &LogOptions::default(),
+            &log_path,
+            &|| true,
+        )
+        .unwrap();
+        assert!(!status.success());
+        assert!(status.cancelled);
+        assert!(started.elapsed() < Duration::from_secs(15));
+    }
+
     #[test]
     fn test_format_duration_minutes() {
         assert_eq!(format_duration(Duration::from_secs(60)), "1m 0s");
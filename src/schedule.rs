@@ -0,0 +1,275 @@
+// ABOUTME: Landing schedule from the freeze_windows and landing_windows settings.
+// ABOUTME: Decides whether runs may land now and when the next landing window opens.
+
+use anyhow::{Result, bail};
+use serde::Serialize;
+use std::fmt;
+
+use crate::config;
+use crate::jj::Jj;
+
+/// Minutes in a week; times are minutes since Monday 00:00 local time.
+const WEEK: u32 = 7 * 24 * 60;
+const DAY: u32 = 24 * 60;
+const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// A recurring window of local time, as written in the config.
+#[derive(Debug, Clone, PartialEq)]
+struct Window {
+    text: String,
+    /// Minute of the week the window starts.
+    start: u32,
+    /// Length in minutes (up to a whole week).
+    len: u32,
+}
+
+impl Window {
+    fn contains(&self, minute: u32) -> bool {
+        (minute + WEEK - self.start) % WEEK < self.len
+    }
+}
+
+/// Landings are refused inside a freeze window and, if any landing windows
+/// are configured, outside all of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schedule {
+    freeze: Vec<Window>,
+    landing: Vec<Window>,
+}
+
+/// Why landing is currently refused, and when it is next allowed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LandingClosed {
+    /// "freeze window <window>" or "outside landing windows".
+    pub reason: String,
+    /// Local weekday and time the next landing window opens, e.g.
+    /// "Mon 08:00"; absent if the schedule never allows landing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opens_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opens_in_secs: Option<u64>,
+}
+
+impl fmt::Display for LandingClosed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reason)?;
+        match (&self.opens_at, self.opens_in_secs) {
+            (Some(at), Some(secs)) => write!(
+                f,
+                "; next landing window opens {} (in {})",
+                at,
+                format_wait(secs)
+            ),
+            _ => write!(f, "; no landing window is scheduled"),
+        }
+    }
+}
+
+impl Schedule {
+    /// Read the schedule from config.
+    pub fn from_config(jj: &Jj) -> Result<Schedule> {
+        Ok(Schedule {
+            freeze: parse_windows(&config::get(jj, "freeze_windows")?.unwrap_or_default())?,
+            landing: parse_windows(&config::get(jj, "landing_windows")?.unwrap_or_default())?,
+        })
+    }
+
+    /// Whether landing is refused at the current local time, and why.
+    pub fn closed_now(&self) -> Option<LandingClosed> {
+        if self.freeze.is_empty() && self.landing.is_empty() {
+            return None;
+        }
+        let (minute, second) = local_minute_of_week();
+        self.closed_at(minute, second)
+    }
+
+    fn closed_at(&self, minute: u32, second: u32) -> Option<LandingClosed> {
+        let reason = self.closed_reason(minute)?;
+        let wait = (1..=WEEK).find(|i| self.closed_reason((minute + i) % WEEK).is_none());
+        Some(LandingClosed {
+            reason,
+            opens_at: wait.map(|i| format_minute((minute + i) % WEEK)),
+            opens_in_secs: wait.map(|i| u64::from(i * 60 - second)),
+        })
+    }
+
+    fn closed_reason(&self, minute: u32) -> Option<String> {
+        if let Some(window) = self.freeze.iter().find(|w| w.contains(minute)) {
+            return Some(format!("freeze window {}", window.text));
+        }
+        if !self.landing.is_empty() && !self.landing.iter().any(|w| w.contains(minute)) {
+            return Some("outside landing windows".to_string());
+        }
+        None
+    }
+}
+
+/// Parse comma-separated windows: `Fri 16:00-Mon 08:00` recurs weekly and
+/// `22:00-06:00` daily. A window ending when it starts covers the whole
+/// week or day.
+fn parse_windows(value: &str) -> Result<Vec<Window>> {
+    let mut windows = Vec::new();
+    for text in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let Some((from, to)) = text.split_once('-') else {
+            bail!("invalid window '{}': expected START-END", text);
+        };
+        match (parse_time(from.trim()), parse_time(to.trim())) {
+            (Some((Some(from_day), from)), Some((Some(to_day), to))) => {
+                let start = from_day * DAY + from;
+                let len = match (to_day * DAY + to + WEEK - start) % WEEK {
+                    0 => WEEK,
+                    len => len,
+                };
+                windows.push(Window {
+                    text: text.to_string(),
+                    start,
+                    len,
+                });
+            }
+            (Some((None, from)), Some((None, to))) => {
+                let len = match (to + DAY - from) % DAY {
+                    0 => DAY,
+                    len => len,
+                };
+                windows.extend((0..7).map(|day| Window {
+                    text: text.to_string(),
+                    start: day * DAY + from,
+                    len,
+                }));
+            }
+            _ => bail!(
+                "invalid window '{}': expected 'DAY HH:MM-DAY HH:MM' or 'HH:MM-HH:MM'",
+                text
+            ),
+        }
+    }
+    Ok(windows)
+}
+
+/// Validate a window list for `jjq config`.
+pub fn validate(value: &str) -> Result<()> {
+    parse_windows(value).map(|_| ())
+}
+
+/// Parse `[DAY ]HH:MM` into an optional day index (Monday is 0) and the
+/// minute of the day.
+fn parse_time(text: &str) -> Option<(Option<u32>, u32)> {
+    let (day, time) = match text.split_once(char::is_whitespace) {
+        Some((day, time)) => {
+            let day = DAYS
+                .iter()
+                .position(|d| d.eq_ignore_ascii_case(day.trim()))?;
+            (Some(day as u32), time.trim())
+        }
+        None => (None, text),
+    };
+    let (hours, minutes) = time.split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some((day, hours * 60 + minutes))
+}
+
+/// "Mon 08:00" for a minute of the week.
+fn format_minute(minute: u32) -> String {
+    format!(
+        "{} {:02}:{:02}",
+        DAYS[(minute / DAY) as usize],
+        minute % DAY / 60,
+        minute % 60
+    )
+}
+
+/// "2d 15h 30m" for a wait of up to a week, rounded up to the minute.
+fn format_wait(secs: u64) -> String {
+    let minutes = secs.div_ceil(60);
+    let (days, hours, minutes) = (minutes / 1440, minutes % 1440 / 60, minutes % 60);
+    match (days, hours) {
+        (0, 0) => format!("{}m", minutes),
+        (0, _) => format!("{}h {}m", hours, minutes),
+        _ => format!("{}d {}h {}m", days, hours, minutes),
+    }
+}
+
+/// The current local minute of the week, and seconds into that minute.
+fn local_minute_of_week() -> (u32, u32) {
+    let tm = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        tm
+    };
+    // tm_wday counts from Sunday
+    let day = (tm.tm_wday as u32 + 6) % 7;
+    let minute = day * DAY + tm.tm_hour as u32 * 60 + tm.tm_min as u32;
+    (minute, (tm.tm_sec as u32).min(59))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32, hour: u32, minute: u32) -> u32 {
+        day * DAY + hour * 60 + minute
+    }
+
+    #[test]
+    fn test_parse_windows() {
+        let weekly = parse_windows("Fri 16:00-Mon 08:00").unwrap();
+        assert_eq!(weekly.len(), 1);
+        assert!(weekly[0].contains(at(4, 16, 0)));
+        assert!(weekly[0].contains(at(6, 12, 0)));
+        assert!(weekly[0].contains(at(0, 7, 59)));
+        assert!(!weekly[0].contains(at(0, 8, 0)));
+        assert!(!weekly[0].contains(at(4, 15, 59)));
+
+        let daily = parse_windows("22:00-06:00, sat 10:00-sat 12:00").unwrap();
+        assert_eq!(daily.len(), 8);
+        assert!(daily.iter().any(|w| w.contains(at(2, 23, 0))));
+        assert!(daily.iter().any(|w| w.contains(at(0, 5, 0))));
+        assert!(!daily.iter().any(|w| w.contains(at(2, 12, 0))));
+
+        assert_eq!(parse_windows("00:00-00:00").unwrap()[0].len, DAY);
+        assert_eq!(parse_windows("").unwrap(), Vec::new());
+        assert!(parse_windows("Fri 16:00").is_err());
+        assert!(parse_windows("Fri 16:00-08:00").is_err());
+        assert!(parse_windows("Fri 25:00-Mon 08:00").is_err());
+        assert!(parse_windows("Someday 10:00-Mon 08:00").is_err());
+    }
+
+    #[test]
+    fn test_closed_at_reports_next_opening() {
+        let schedule = Schedule {
+            freeze: parse_windows("Fri 16:00-Mon 08:00").unwrap(),
+            landing: parse_windows("09:00-17:00").unwrap(),
+        };
+        assert_eq!(schedule.closed_at(at(1, 10, 0), 0), None);
+
+        let frozen = schedule.closed_at(at(5, 12, 0), 30).unwrap();
+        assert_eq!(frozen.reason, "freeze window Fri 16:00-Mon 08:00");
+        assert_eq!(frozen.opens_at.as_deref(), Some("Mon 09:00"));
+        assert_eq!(frozen.opens_in_secs, Some(45 * 3600 - 30));
+
+        assert_eq!(
+            frozen.to_string(),
+            "freeze window Fri 16:00-Mon 08:00; next landing window opens Mon 09:00 (in 1d 21h 0m)"
+        );
+
+        let outside = schedule.closed_at(at(1, 18, 0), 0).unwrap();
+        assert_eq!(outside.reason, "outside landing windows");
+        assert_eq!(outside.opens_at.as_deref(), Some("Wed 09:00"));
+
+        let always = Schedule {
+            freeze: parse_windows("00:00-00:00").unwrap(),
+            landing: Vec::new(),
+        };
+        let closed = always.closed_at(at(3, 3, 3), 0).unwrap();
+        assert_eq!(closed.opens_at, None);
+        assert_eq!(closed.opens_in_secs, None);
+        assert_eq!(
+            closed.to_string(),
+            "freeze window 00:00-00:00; no landing window is scheduled"
+        );
+    }
+}
@@ -0,0 +1,195 @@
+// ABOUTME: Runner state file publishing the item a run is processing and its phase.
+// ABOUTME: Also keeps recent check durations so status can estimate time remaining.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::jj::Jj;
+use crate::lock::{Lock, unix_now};
+
+/// Number of recent check durations kept for estimates.
+const DURATION_HISTORY: usize = 20;
+
+/// What the runner is doing with its current item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    CreatingWorkspace,
+    Checking,
+    Landing,
+}
+
+impl Phase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Phase::CreatingWorkspace => "creating workspace",
+            Phase::Checking => "checking",
+            Phase::Landing => "landing",
+        }
+    }
+}
+
+/// Contents of the runner state file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunnerState {
+    pub id: u32,
+    pub candidate_change_id: String,
+    pub phase: Phase,
+    pub pid: u32,
+    /// Unix time (seconds) processing of the item started.
+    pub started: u64,
+    /// Unix time (seconds) the check command started, once it has.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub check_started: Option<u64>,
+}
+
+/// Publishes the runner's state while it processes an item. Owns the run
+/// lock so the state file is removed, on every path, just before the lock
+/// is released and another runner can publish its own.
+pub struct Progress {
+    state: RunnerState,
+    jj: Jj,
+    _run_lock: Lock,
+}
+
+impl Progress {
+    /// Start publishing state for item `id`, in the creating-workspace phase.
+    pub fn start(jj: &Jj, run_lock: Lock, id: u32, candidate_change_id: &str) -> Result<Progress> {
+        let progress = Progress {
+            state: RunnerState {
+                id,
+                candidate_change_id: candidate_change_id.to_string(),
+                phase: Phase::CreatingWorkspace,
+                pid: std::process::id(),
+                started: unix_now(),
+                check_started: None,
+            },
+            jj: jj.clone(),
+            _run_lock: run_lock,
+        };
+        // A cancel request left by a runner that died before seeing it
+        // must not apply to this item.
+        clear_cancel(jj);
+        progress.write()?;
+        Ok(progress)
+    }
+
+    /// Move to `phase`, recording the check start time on entering the
+    /// checking phase.
+    pub fn set_phase(&mut self, phase: Phase) -> Result<()> {
+        self.state.phase = phase;
+        if phase == Phase::Checking {
+            self.state.check_started = Some(unix_now());
+        }
+        self.write()
+    }
+
+    fn write(&self) -> Result<()> {
+        // Write then rename so readers never see a partial file
+        let path = state_path(&self.jj)?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string(&self.state)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        clear_cancel(&self.jj);
+        if let Ok(path) = state_path(&self.jj) {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// A request, from `jjq cancel`, that the runner stop processing an item.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CancelRequest {
+    pub id: u32,
+    /// Return the item to the queue rather than marking it failed.
+    pub requeue: bool,
+}
+
+/// Ask the runner to cancel item `id`. The runner polls for the request
+/// while the check runs.
+pub fn request_cancel(jj: &Jj, id: u32, requeue: bool) -> Result<()> {
+    let path = cancel_path(jj)?;
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_string(&CancelRequest { id, requeue })?)?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+/// The pending cancel request for item `id`, if there is one.
+pub fn cancel_request(jj: &Jj, id: u32) -> Option<CancelRequest> {
+    let contents = fs::read_to_string(cancel_path(jj).ok()?).ok()?;
+    serde_json::from_str::<CancelRequest>(&contents)
+        .ok()
+        .filter(|request| request.id == id)
+}
+
+fn clear_cancel(jj: &Jj) {
+    if let Ok(path) = cancel_path(jj) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Read the published runner state. Only meaningful while the run lock is
+/// held; a runner killed without cleanup leaves a stale file behind.
+pub fn read(jj: &Jj) -> Result<Option<RunnerState>> {
+    match fs::read_to_string(state_path(jj)?) {
+        Ok(contents) => Ok(serde_json::from_str(&contents).ok()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Append a completed check's duration to the history.
+pub fn record_check_duration(jj: &Jj, duration: Duration) -> Result<()> {
+    let path = durations_path(jj)?;
+    let mut history = read_durations(&path);
+    history.push(duration.as_secs());
+    let start = history.len().saturating_sub(DURATION_HISTORY);
+    let lines: Vec<String> = history[start..].iter().map(|s| s.to_string()).collect();
+    fs::write(&path, lines.join("\n") + "\n")?;
+    Ok(())
+}
+
+/// Typical check duration: the median of recent checks, if any ran.
+pub fn typical_check_duration(jj: &Jj) -> Result<Option<Duration>> {
+    let mut history = read_durations(&durations_path(jj)?);
+    if history.is_empty() {
+        return Ok(None);
+    }
+    history.sort_unstable();
+    Ok(Some(Duration::from_secs(history[history.len() / 2])))
+}
+
+fn read_durations(path: &std::path::Path) -> Vec<u64> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .collect()
+}
+
+/// Seconds from unix time `since` until now.
+pub fn secs_since(since: u64) -> u64 {
+    unix_now().saturating_sub(since)
+}
+
+fn state_path(jj: &Jj) -> Result<PathBuf> {
+    Ok(jj.root().join(".jj").join("jjq-runner.json"))
+}
+
+fn cancel_path(jj: &Jj) -> Result<PathBuf> {
+    Ok(jj.root().join(".jj").join("jjq-cancel"))
+}
+
+fn durations_path(jj: &Jj) -> Result<PathBuf> {
+    Ok(jj.root().join(".jj").join("jjq-check-durations"))
+}
@@ -4,103 +4,190 @@
 use anyhow::Result;
 use std::fs::File;
 use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use crate::jj::Jj;
+use crate::runlog::{self, LogEvent, Stream};
+
+/// How to render lines read from the run log.
+#[derive(Clone, Copy)]
+pub struct TailOptions {
+    /// Prefix each line with the time since the check started.
+    pub elapsed: bool,
+    /// Only show lines the check wrote to stderr.
+    pub stderr_only: bool,
+}
+
+impl TailOptions {
+    /// Whether these options need the structured (JSON lines) log.
+    fn structured(&self) -> bool {
+        self.elapsed || self.stderr_only
+    }
+}
+
+/// A raw log line classified for display.
+enum Line {
+    Show(String),
+    Skip,
+    Sentinel,
+}
+
+/// Classify one raw line from the plain or structured log.
+fn classify(raw: &str, options: TailOptions) -> Line {
+    if !options.structured() {
+        if raw.starts_with(runlog::SENTINEL_PREFIX) {
+            return Line::Sentinel;
+        }
+        return Line::Show(raw.to_string());
+    }
+
+    let Ok(event) = serde_json::from_str::<LogEvent>(raw) else {
+        return Line::Skip;
+    };
+    if event.is_sentinel() {
+        return Line::Sentinel;
+    }
+    if options.stderr_only && event.stream != Stream::Stderr {
+        return Line::Skip;
+    }
+    if options.elapsed {
+        Line::Show(format!(
+            "{} {}",
+            runlog::elapsed_prefix(event.elapsed_ms),
+            event.text
+        ))
+    } else {
+        Line::Show(event.text)
+    }
+}
+
+/// Read the complete lines of a log file, returning them, their length in
+/// bytes and the file's inode. A partial last line is left to be re-read
+/// when following.
+fn read_complete(path: &Path) -> io::Result<(String, usize, u64)> {
+    let mut file = File::open(path)?;
+    let inode = file.metadata()?.ino();
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    let mut content = String::from_utf8_lossy(&bytes).into_owned();
+    let complete_len = content.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    content.truncate(complete_len);
+    Ok((content, complete_len, inode))
+}
 
 /// View check command output, optionally following new output in real time.
 ///
 /// In dump mode (`!follow`), prints existing log content and exits.
 /// In follow mode, prints initial content then polls for new lines until
 /// a sentinel or runner exit is detected.
-pub fn tail(all: bool, follow: bool) -> Result<()> {
-    let path = crate::runlog::log_path()?;
+///
+/// Once a run reaches `log_max_bytes`, its recent output goes to a rolling
+/// live file until the run finishes, so that file is read and followed
+/// after the log itself.
+pub fn tail(jj: &Jj, all: bool, follow: bool, options: TailOptions) -> Result<()> {
+    let log_path = if options.structured() {
+        runlog::events_path(jj)?
+    } else {
+        runlog::log_path(jj)?
+    };
+    let live_path = runlog::live_path_for(&log_path);
 
-    if !path.exists() {
+    if !log_path.exists() {
         eprintln!("jjq: no run output available");
         return Ok(());
     }
 
-    let content = std::fs::read_to_string(&path)?;
-    let lines: Vec<&str> = content.lines().collect();
-
-    if !follow {
-        // Dump mode: print content and exit.
-        let visible: Vec<&str> = lines
-            .iter()
-            .filter(|l| !l.starts_with(crate::runlog::SENTINEL_PREFIX))
-            .copied()
-            .collect();
-        let start = if all || visible.len() <= 20 {
-            0
-        } else {
-            visible.len() - 20
-        };
-        let stdout = io::stdout();
-        let mut out = stdout.lock();
-        for line in &visible[start..] {
-            writeln!(out, "{}", line)?;
-        }
-        return Ok(());
+    let mut path = log_path.clone();
+    let (content, mut complete_len, mut inode) = read_complete(&path)?;
+    let mut lines: Vec<Line> = content.lines().map(|l| classify(l, options)).collect();
+    let mut already_finished = lines.iter().any(|l| matches!(l, Line::Sentinel));
+    if !already_finished && let Ok((content, len, live_inode)) = read_complete(&live_path) {
+        lines.extend(content.lines().map(|l| classify(l, options)));
+        already_finished = lines.iter().any(|l| matches!(l, Line::Sentinel));
+        path = live_path.clone();
+        complete_len = len;
+        inode = live_inode;
     }
 
-    // Follow mode: print initial content, then poll for new lines.
-    let stdout = io::stdout();
-    let mut out = stdout.lock();
-
     let visible: Vec<&str> = lines
         .iter()
-        .filter(|l| !l.starts_with(crate::runlog::SENTINEL_PREFIX))
-        .copied()
+        .filter_map(|l| match l {
+            Line::Show(s) => Some(s.as_str()),
+            _ => None,
+        })
         .collect();
-    let already_finished = lines
-        .iter()
-        .any(|l| l.starts_with(crate::runlog::SENTINEL_PREFIX));
     let start = if all || visible.len() <= 20 {
         0
     } else {
         visible.len() - 20
     };
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
     for line in &visible[start..] {
         writeln!(out, "{}", line)?;
     }
-    if already_finished {
+
+    if !follow || already_finished {
+        // Dump mode, or nothing more to follow.
         return Ok(());
     }
     out.flush()?;
 
     // Track our read position by byte offset; use seek-based reads
     // to avoid re-reading the entire file each iteration.
-    let mut offset = content.len() as u64;
+    let mut offset = complete_len as u64;
 
     loop {
         std::thread::sleep(std::time::Duration::from_millis(200));
 
         let mut file = match File::open(&path) {
             Ok(f) => f,
+            Err(_) if path == live_path => {
+                // A new run removed the live file; its output is in the log
+                path = log_path.clone();
+                offset = 0;
+                continue;
+            }
             Err(_) => {
                 eprintln!("jjq: log file disappeared");
                 return Ok(());
             }
         };
 
-        let file_len = file.metadata().map(|m| m.len()).unwrap_or(0);
+        let metadata = file.metadata()?;
+        let file_len = metadata.len();
 
-        if file_len < offset {
-            // File was truncated (new run started); reset to beginning
+        if metadata.ino() != inode || file_len < offset {
+            // The file was replaced or truncated: a new run started, or
+            // the live file started over. Read it from the beginning.
+            inode = metadata.ino();
             offset = 0;
         }
 
         if file_len > offset {
             file.seek(SeekFrom::Start(offset))?;
-            let mut buf = String::new();
-            file.read_to_string(&mut buf)?;
-            for line in buf.lines() {
-                if line.starts_with(crate::runlog::SENTINEL_PREFIX) {
-                    return Ok(());
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            let Some(end) = bytes.iter().rposition(|&b| b == b'\n') else {
+                continue;
+            };
+            let buf = String::from_utf8_lossy(&bytes[..=end]);
+            for raw in buf.lines() {
+                match classify(raw, options) {
+                    Line::Sentinel => return Ok(()),
+                    Line::Show(line) => writeln!(out, "{}", line)?,
+                    Line::Skip => {}
                 }
-                writeln!(out, "{}", line)?;
             }
             out.flush()?;
-            offset = file_len;
-        } else if !crate::lock::is_held("run")? {
+            offset += end as u64 + 1;
+        } else if path != live_path && live_path.exists() {
+            // The log reached its cap; follow the live file from its start
+            path = live_path.clone();
+            offset = 0;
+        } else if !crate::lock::is_held(jj, "run")? {
             eprintln!("jjq: run process is no longer active");
             return Ok(());
         }
@@ -629,7 +629,7 @@ fn test_config_invalid_key() {
     let output = repo.jjq_failure(&["config", "invalid_key"]);
     insta::assert_snapshot!(output, @r"
     jjq: unknown config key: invalid_key
-    valid keys: trunk_bookmark, check_command, strategy
+    valid keys: trunk_bookmark, check_command, strategy, check_shell, check_workdir, check_env_allow, check_env_deny, check_limit_memory, check_limit_cpu, check_limit_files, check_limit_procs, log_max_bytes, log_strip_ansi, check_report, failure_patterns, artifacts, pool_dir, pool_size, workspace_dir, freeze_windows, landing_windows, require_approval, approval_trailers, description_required, title_max_length, description_deny, description_require, squash_message, squash_originals, landed_message, landed_trailers
     ");
 }
 
@@ -1479,6 +1479,20 @@ fn test_init_with_flags() {
         "should show ready message: {}",
         output
     );
+
+    // The initial settings are written in one metadata commit
+    let commits = run_jj(
+        repo.path(),
+        &[
+            "log",
+            "-r",
+            "::jjq/_/_ ~ root()",
+            "--no-graph",
+            "-T",
+            "description.first_line() ++ \"\\n\"",
+        ],
+    );
+    assert_eq!(commits, "init jjq\n");
 }
 
 #[test]
@@ -2074,3 +2088,1479 @@ fn test_status_shows_recently_landed_rebase() {
       1: <CHANGE_ID> feature 1
     ");
 }
+
+#[test]
+fn test_check_against_trunk_success() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq();
+
+    run_jj(repo.path(), &["new", "-m", "add file", "main"]);
+    fs::write(repo.path().join("newfile.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+
+    let output = repo.jjq_success(&["check", "--rev", "feature"]);
+    insta::assert_snapshot!(output, @r"
+    jjq: checking revision <CHANGE_ID> against main (rebase strategy) with: true
+    jjq: check passed
+    ");
+
+    // check never touches the queue
+    let status = repo.jjq_success(&["status"]);
+    insta::assert_snapshot!(status, @"jjq: queue is empty");
+}
+
+#[test]
+fn test_check_against_trunk_conflict() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq();
+
+    run_jj(repo.path(), &["new", "-m", "trunk advance", "main"]);
+    fs::write(repo.path().join("main.go"), "package main\n\n// trunk\n").unwrap();
+    run_jj(repo.path(), &["bookmark", "move", "main"]);
+
+    run_jj(repo.path(), &["new", "-m", "conflicting change", "root()"]);
+    fs::write(repo.path().join("main.go"), "package main\n\n// branch\n").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "conflict-branch"]);
+
+    let output = repo.jjq_failure(&["check", "--rev", "conflict-branch"]);
+    insta::assert_snapshot!(output, @r"
+    jjq: checking revision <CHANGE_ID> against main (rebase strategy) with: true
+    jjq: revision <CHANGE_ID> conflicts with main
+    jjq:   conflicting files: main.go
+    jjq:
+    jjq: To resolve:
+    jjq:   jj rebase -r <CHANGE_ID> -d main
+    jjq:   # resolve conflicts in <CHANGE_ID>
+    jjq: check failed: conflicts
+    ");
+
+    // The bare revision is fine on its own
+    let isolated = repo.jjq_success(&["check", "--rev", "conflict-branch", "--isolated"]);
+    insta::assert_snapshot!(isolated, @r"
+    jjq: checking revision <CHANGE_ID> with: true
+    jjq: check passed
+    ");
+
+    // No rebase duplicates or workspaces left behind
+    let ws = run_jj(repo.path(), &["workspace", "list"]);
+    assert!(
+        !ws.contains("jjq-check-"),
+        "workspace should be forgotten: {}",
+        ws
+    );
+}
+
+#[test]
+fn test_check_against_trunk_empty() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_merge();
+
+    run_jj(repo.path(), &["new", "-m", "nothing here", "main"]);
+    run_jj(repo.path(), &["bookmark", "create", "empty-branch"]);
+
+    let output = repo.jjq_success(&["check", "--rev", "empty-branch"]);
+    insta::assert_snapshot!(output, @r"
+    jjq: checking revision <CHANGE_ID> against main (merge strategy) with: true
+    jjq: revision <CHANGE_ID> is empty (no changes vs main), run would skip it
+    ");
+}
+
+#[test]
+fn test_run_exports_jjq_env() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_with_check(
+        "echo \"id=$JJQ_ID trunk=$JJQ_TRUNK strategy=$JJQ_STRATEGY\" && test -d \"$JJQ_WORKSPACE\"",
+    );
+
+    run_jj(repo.path(), &["new", "-m", "add file", "main"]);
+    fs::write(repo.path().join("newfile.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    repo.jjq_success(&["push", "feature"]);
+    repo.jjq_success(&["run"]);
+
+    let output = repo.jjq_success(&["tail", "--no-follow"]);
+    insta::assert_snapshot!(output, @"id=1 trunk=main strategy=rebase");
+}
+
+#[test]
+fn test_check_env_filter_and_workdir() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_with_check("echo \"dir=$(basename \"$PWD\")\" && echo \"home=${HOME:-unset}\"");
+    repo.jjq_success(&["config", "check_env_deny", "HOME"]);
+    repo.jjq_success(&["config", "check_workdir", "sub"]);
+
+    run_jj(repo.path(), &["new", "-m", "add sub", "main"]);
+    fs::create_dir_all(repo.path().join("sub")).unwrap();
+    fs::write(repo.path().join("sub/file.txt"), "content").unwrap();
+
+    let output = repo.jjq_success(&["check", "-v"]);
+    assert!(
+        output.contains("jjq: shell: /bin/sh"),
+        "should show shell: {}",
+        output
+    );
+    assert!(
+        output.contains("jjq:   JJQ_TRUNK=main"),
+        "should show JJQ vars: {}",
+        output
+    );
+    assert!(
+        !output.contains("jjq:   HOME="),
+        "denied var should be hidden: {}",
+        output
+    );
+    assert!(
+        output.contains("home=unset"),
+        "denied var should not reach check: {}",
+        output
+    );
+    assert!(
+        output.contains("dir=sub"),
+        "check should run in subdirectory: {}",
+        output
+    );
+
+    let bad = repo.jjq_failure(&["config", "check_workdir", "../outside"]);
+    insta::assert_snapshot!(bad, @r"
+    jjq: invalid value for check_workdir: ../outside
+    must be a relative path inside the workspace
+    ");
+}
+
+#[test]
+fn test_run_cpu_limit_failure_reason() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_with_check("while :; do :; done");
+    repo.jjq_success(&["config", "check_limit_cpu", "1"]);
+
+    run_jj(repo.path(), &["new", "-m", "spins forever", "main"]);
+    fs::write(repo.path().join("spin.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "spin"]);
+    repo.jjq_success(&["push", "spin"]);
+
+    let output = repo.jjq_failure(&["run"]);
+    assert!(
+        output.contains("merge 1 failed check (cpu-limit), marked as failed"),
+        "expected cpu limit failure: {}",
+        output
+    );
+
+    let (stdout, _, _) = repo.jjq_raw_output(&["status", "1", "--json"]);
+    let item: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(item["failure_reason"], "cpu-limit");
+    assert_eq!(item["limits"], "cpu=1");
+}
+
+#[test]
+fn test_run_nofile_limit_failure_reason() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_with_check("echo 'open data.txt: Too many open files' >&2; exit 1");
+    repo.jjq_success(&["config", "check_limit_files", "256"]);
+
+    run_jj(repo.path(), &["new", "-m", "opens files", "main"]);
+    fs::write(repo.path().join("files.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "files"]);
+    repo.jjq_success(&["push", "files"]);
+
+    let output = repo.jjq_failure(&["run"]);
+    assert!(
+        output.contains("merge 1 failed check (nofile-limit), marked as failed"),
+        "expected open files limit failure: {}",
+        output
+    );
+    let output = repo.jjq_success(&["status", "1"]);
+    assert!(output.contains("  Limits:      files=256"), "{}", output);
+
+    // Without the limit set, the same error is an ordinary check failure
+    repo.jjq_success(&["config", "check_limit_files", ""]);
+    repo.jjq_success(&["push", "files"]);
+    let output = repo.jjq_failure(&["run"]);
+    assert!(
+        output.contains("merge 2 failed check, marked as failed"),
+        "expected plain check failure: {}",
+        output
+    );
+}
+
+#[test]
+fn test_config_limit_validation() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq();
+
+    repo.jjq_success(&["config", "check_limit_memory", "2G"]);
+    let bad = repo.jjq_failure(&["config", "check_limit_procs", "lots"]);
+    insta::assert_snapshot!(bad, @r"
+    jjq: invalid value for check_limit_procs: lots
+    must be a number
+    ");
+}
+
+#[test]
+fn test_run_log_size_cap() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_with_check("seq 1 1000");
+    repo.jjq_success(&["config", "log_max_bytes", "200"]);
+
+    run_jj(repo.path(), &["new", "-m", "add file", "main"]);
+    fs::write(repo.path().join("newfile.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    repo.jjq_success(&["push", "feature"]);
+    repo.jjq_success(&["run"]);
+
+    let output = repo.jjq_success(&["tail", "--all", "--no-follow"]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.first(), Some(&"1"), "head should be kept: {}", output);
+    assert_eq!(
+        lines.last(),
+        Some(&"1000"),
+        "tail should be kept: {}",
+        output
+    );
+    assert!(
+        output.contains(" lines elided ..."),
+        "expected elision marker: {}",
+        output
+    );
+    assert!(output.len() < 300, "log should be capped: {}", output);
+}
+
+#[test]
+fn test_run_log_strip_ansi_and_invalid_utf8() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_with_check(r"printf '\033[31mred\033[0m\n\377bytes\n'");
+    repo.jjq_success(&["config", "log_strip_ansi", "true"]);
+
+    run_jj(repo.path(), &["new", "-m", "add file", "main"]);
+    fs::write(repo.path().join("newfile.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    repo.jjq_success(&["push", "feature"]);
+    repo.jjq_success(&["run"]);
+
+    let output = repo.jjq_success(&["tail", "--no-follow"]);
+    insta::assert_snapshot!(output, @r"
+    red
+    �bytes
+    ");
+}
+
+#[test]
+fn test_tail_stderr_only_and_elapsed() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_with_check("echo to-stdout && echo to-stderr >&2");
+
+    run_jj(repo.path(), &["new", "-m", "add file", "main"]);
+    fs::write(repo.path().join("newfile.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    repo.jjq_success(&["push", "feature"]);
+    repo.jjq_success(&["run"]);
+
+    let stderr_only = repo.jjq_success(&["tail", "--no-follow", "--stderr-only"]);
+    insta::assert_snapshot!(stderr_only, @"to-stderr");
+
+    let elapsed = repo.jjq_success(&["tail", "--no-follow", "--elapsed"]);
+    let re = regex::Regex::new(r"^\[ +\d+\.\d{3}s\] to-std(out|err)$").unwrap();
+    assert_eq!(
+        elapsed.lines().count(),
+        2,
+        "expected both streams: {}",
+        elapsed
+    );
+    for line in elapsed.lines() {
+        assert!(re.is_match(line), "expected elapsed prefix: {}", line);
+    }
+
+    // The structured log records which stream each line came from
+    let events = fs::read_to_string(repo.path().join(".jj/jjq-run.jsonl")).unwrap();
+    assert!(
+        events.contains(r#""stream":"stderr","text":"to-stderr""#),
+        "structured log should tag stderr: {}",
+        events
+    );
+}
+
+#[test]
+fn test_run_records_failing_tests_from_report() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_with_check(
+        "printf 'TAP version 13\\nok 1 - adds\\nnot ok 2 - divides\\nnot ok 3 - parses\\n' > report.tap; exit 1",
+    );
+    repo.jjq_success(&["config", "check_report", "report.tap"]);
+
+    run_jj(repo.path(), &["new", "-m", "breaks tests", "main"]);
+    fs::write(repo.path().join("broken.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "broken"]);
+    repo.jjq_success(&["push", "broken"]);
+
+    let output = repo.jjq_failure(&["run"]);
+    assert!(
+        output.contains("  failing tests: divides, parses"),
+        "expected failing tests in run output: {}",
+        output
+    );
+
+    let (stdout, _, _) = repo.jjq_raw_output(&["status", "1", "--json"]);
+    let item: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(
+        item["failing_tests"],
+        serde_json::json!(["divides", "parses"])
+    );
+    assert_eq!(item["failing_test_count"], 2);
+
+    let status = repo.jjq_success(&["status", "1"]);
+    assert!(
+        status.contains("  Failing tests (2):\n    divides\n    parses\n"),
+        "expected failing tests in status: {}",
+        status
+    );
+}
+
+#[test]
+fn test_run_ignores_report_from_previous_check() {
+    let repo = TestRepo::with_go_project();
+    // Only the first check writes a report; the second fails without one
+    repo.init_jjq_with_check(
+        "test -f target/seen && exit 1; mkdir -p target && touch target/seen; printf 'not ok 1 - old\\n' > target/report.tap; exit 1",
+    );
+    repo.jjq_success(&["config", "check_report", "target/report.tap"]);
+    repo.jjq_success(&["config", "pool_dir", ".jj/jjq-pool"]);
+
+    run_jj(repo.path(), &["new", "-m", "ignore target", "main"]);
+    fs::write(repo.path().join(".gitignore"), "target/\n").unwrap();
+    run_jj(repo.path(), &["bookmark", "set", "main", "-r", "@"]);
+
+    for name in ["one", "two"] {
+        run_jj(repo.path(), &["new", "-m", name, "main"]);
+        fs::write(repo.path().join(format!("{}.txt", name)), name).unwrap();
+        run_jj(repo.path(), &["bookmark", "create", name]);
+        repo.jjq_success(&["push", name]);
+    }
+
+    let output = repo.jjq_failure(&["run"]);
+    assert!(output.contains("  failing tests: old"), "{}", output);
+    let output = repo.jjq_failure(&["run"]);
+    assert!(
+        !output.contains("failing tests"),
+        "stale report was read: {}",
+        output
+    );
+}
+
+#[test]
+fn test_run_records_failure_excerpt() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_with_check(
+        "echo building; echo 'error[E0308]: mismatched types'; echo '  --> src/lib.rs:3:5'; exit 1",
+    );
+    repo.jjq_success(&["config", "failure_patterns", "error\\[E\npanicked at"]);
+
+    run_jj(repo.path(), &["new", "-m", "type error", "main"]);
+    fs::write(repo.path().join("broken.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "broken"]);
+    repo.jjq_success(&["push", "broken"]);
+    repo.jjq_failure(&["run"]);
+
+    let (stdout, _, _) = repo.jjq_raw_output(&["status", "1", "--json"]);
+    let item: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(
+        item["failure_excerpt"],
+        serde_json::json!(["error[E0308]: mismatched types", "  --> src/lib.rs:3:5"])
+    );
+
+    let status = repo.jjq_success(&["status"]);
+    assert!(
+        status.contains("\n      error[E0308]: mismatched types\n"),
+        "expected excerpt in status: {}",
+        status
+    );
+
+    let bad = repo.jjq_failure(&["config", "failure_patterns", "error[E"]);
+    assert!(
+        bad.contains("invalid value for failure_patterns"),
+        "{}",
+        bad
+    );
+}
+
+#[test]
+fn test_run_collects_artifacts() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_with_check("mkdir -p out && echo coverage > out/cover.txt && echo bin > out/app");
+    repo.jjq_success(&["config", "artifacts", "out/*.txt"]);
+
+    run_jj(repo.path(), &["new", "-m", "add file", "main"]);
+    fs::write(repo.path().join("newfile.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    repo.jjq_success(&["push", "feature"]);
+
+    let output = repo.jjq_success(&["run"]);
+    assert!(
+        output.contains("jjq: saved 1 artifact(s) to <REPO>/.jj/jjq-artifacts/000001"),
+        "expected artifacts to be saved: {}",
+        output
+    );
+    let saved = repo.path().join(".jj/jjq-artifacts/000001/out/cover.txt");
+    assert_eq!(fs::read_to_string(&saved).unwrap(), "coverage\n");
+
+    let status = repo.jjq_success(&["status", "1"]);
+    insta::assert_snapshot!(status, @r"
+    Item 1
+      Artifacts:   <REPO>/.jj/jjq-artifacts/000001
+        out/cover.txt
+    ");
+
+    let output = repo.jjq_success(&["clean"]);
+    assert!(
+        output.contains("removed artifacts for 1 item(s)"),
+        "{}",
+        output
+    );
+    assert!(!saved.exists());
+}
+
+#[test]
+fn test_clean_and_requeue_remove_only_their_artifacts() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_with_check("mkdir -p out && echo coverage > out/cover.txt; exit 1");
+    repo.jjq_success(&["config", "artifacts", "out/*.txt"]);
+    // A pool slot is reused rather than left behind, so clean has no
+    // workspace to remove for the failed item
+    repo.jjq_success(&["config", "pool_dir", ".jj/jjq-pool"]);
+
+    run_jj(repo.path(), &["new", "-m", "add file", "main"]);
+    fs::write(repo.path().join("newfile.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    repo.jjq_success(&["push", "feature"]);
+    repo.jjq_failure(&["run"]);
+    let saved = repo.path().join(".jj/jjq-artifacts/000001");
+    assert!(saved.is_dir());
+
+    // An uncleaned failed item keeps its artifacts
+    let output = repo.jjq_success(&["clean"]);
+    assert!(!output.contains("removed artifacts"), "{}", output);
+    assert!(saved.is_dir());
+
+    let output = repo.jjq_success(&["requeue", "1"]);
+    assert!(
+        output.contains("removed artifacts for item 1"),
+        "{}",
+        output
+    );
+    assert!(!saved.exists());
+}
+
+#[test]
+fn test_clean_removes_artifacts_of_cleaned_workspaces() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_with_check("mkdir -p out && echo coverage > out/cover.txt; exit 1");
+    repo.jjq_success(&["config", "artifacts", "out/*.txt"]);
+
+    run_jj(repo.path(), &["new", "-m", "add file", "main"]);
+    fs::write(repo.path().join("newfile.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    repo.jjq_success(&["push", "feature"]);
+    repo.jjq_failure(&["run"]);
+    let saved = repo.path().join(".jj/jjq-artifacts/000001");
+    assert!(saved.is_dir());
+
+    let output = repo.jjq_success(&["clean"]);
+    assert!(output.contains("(failed item 1)"), "{}", output);
+    assert!(
+        output.contains("removed artifacts for 1 item(s)"),
+        "{}",
+        output
+    );
+    assert!(!saved.exists());
+}
+
+#[test]
+fn test_run_reuses_pool_workspace() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_with_check(
+        "if test -f target/warm; then echo warm; else echo cold; fi; mkdir -p target && touch target/warm",
+    );
+    repo.jjq_success(&["config", "pool_dir", ".jj/jjq-pool"]);
+
+    // Build outputs are ignored, so they survive between runs
+    run_jj(repo.path(), &["new", "-m", "ignore target", "main"]);
+    fs::write(repo.path().join(".gitignore"), "target/\n").unwrap();
+    run_jj(repo.path(), &["bookmark", "set", "main", "-r", "@"]);
+
+    for name in ["one", "two"] {
+        run_jj(repo.path(), &["new", "-m", name, "main"]);
+        fs::write(repo.path().join(format!("{}.txt", name)), name).unwrap();
+        run_jj(repo.path(), &["bookmark", "create", name]);
+        repo.jjq_success(&["push", name]);
+    }
+
+    repo.jjq_success(&["run"]);
+    let first = repo.jjq_success(&["tail", "--no-follow"]);
+    assert!(
+        first.contains("cold"),
+        "expected a cold first run: {}",
+        first
+    );
+
+    repo.jjq_success(&["run"]);
+    let second = repo.jjq_success(&["tail", "--no-follow"]);
+    assert!(
+        second.contains("warm"),
+        "expected a warm second run: {}",
+        second
+    );
+
+    // Pool workspaces are not leftovers for a plain clean
+    let output = repo.jjq_success(&["clean"]);
+    assert!(output.contains("no workspaces to clean"), "{}", output);
+    let pool_path = repo.path().join(".jj/jjq-pool/jjq-pool-1");
+    assert!(pool_path.join("target/warm").exists());
+
+    let output = repo.jjq_success(&["clean", "--pool"]);
+    assert!(
+        output.contains("reset 1 pool workspace(s): jjq-pool-1"),
+        "{}",
+        output
+    );
+    assert!(!pool_path.exists());
+}
+
+#[test]
+fn test_run_failure_in_pool_slot_records_no_workspace() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_with_check("false");
+    repo.jjq_success(&["config", "pool_dir", ".jj/jjq-pool"]);
+
+    run_jj(repo.path(), &["new", "-m", "fails", "main"]);
+    fs::write(repo.path().join("fails.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "fails"]);
+    repo.jjq_success(&["push", "fails"]);
+
+    let output = repo.jjq_failure(&["run"]);
+    assert!(
+        output.contains("workspace: not kept (pool slot is reused by the next run)"),
+        "{}",
+        output
+    );
+
+    // The slot is reused, so it isn't reported as the failure's workspace
+    let (stdout, _, _) = repo.jjq_raw_output(&["status", "1", "--json"]);
+    let item: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(item["workspace_path"], "");
+}
+
+#[test]
+fn test_run_uses_workspace_dir() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_with_check("false");
+    let workspaces = TempDir::new().unwrap();
+    let workspace_dir = workspaces.path().join("jjq");
+    repo.jjq_success(&["config", "workspace_dir", workspace_dir.to_str().unwrap()]);
+
+    run_jj(repo.path(), &["new", "-m", "fails check", "main"]);
+    fs::write(repo.path().join("broken.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "broken"]);
+    repo.jjq_success(&["push", "broken"]);
+    repo.jjq_failure(&["run"]);
+
+    let (stdout, _, _) = repo.jjq_raw_output(&["status", "1", "--json"]);
+    let item: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let workspace_path = PathBuf::from(item["workspace_path"].as_str().unwrap());
+    assert!(
+        workspace_path.starts_with(&workspace_dir),
+        "expected workspace under {}: {}",
+        workspace_dir.display(),
+        workspace_path.display()
+    );
+    assert!(
+        workspace_path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("jjq-run-000001-")
+    );
+    assert!(workspace_path.is_dir());
+
+    // Changing the setting doesn't lose track of existing workspaces
+    repo.jjq_success(&["config", "workspace_dir", ""]);
+    repo.jjq_success(&["delete", "1"]);
+    assert!(!workspace_path.exists());
+}
+
+#[test]
+fn test_metadata_workspace_recreated_after_forget() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq();
+
+    let ws = run_jj(repo.path(), &["workspace", "list"]);
+    assert!(
+        ws.contains("jjq-metadata:"),
+        "expected metadata workspace: {}",
+        ws
+    );
+
+    run_jj(repo.path(), &["workspace", "forget", "jjq-metadata"]);
+    repo.jjq_success(&["config", "check_command", "make test"]);
+    assert_eq!(
+        repo.jjq_success(&["config", "check_command"]).trim(),
+        "make test"
+    );
+
+    run_jj(repo.path(), &["new", "-m", "add file", "main"]);
+    fs::write(repo.path().join("newfile.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    let output = repo.jjq_success(&["push", "feature"]);
+    assert!(output.contains("queued at 1"), "{}", output);
+}
+
+#[test]
+fn test_json_errors() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq();
+
+    let output = repo
+        .jjq()
+        .args(["push", "nonexistent", "--json"])
+        .assert()
+        .code(10)
+        .get_output()
+        .clone();
+    let err: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(err["error"], "revset_not_found");
+    assert_eq!(err["revset"], "nonexistent");
+    assert_eq!(err["code"], 10);
+    assert_eq!(err["message"], "revset 'nonexistent' not found");
+
+    let output = repo
+        .jjq()
+        .args(["--json", "requeue", "999"])
+        .assert()
+        .code(10)
+        .get_output()
+        .clone();
+    let err: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(err["error"], "failed_item_not_found");
+    assert_eq!(err["id"], 999);
+
+    let output = repo
+        .jjq()
+        .args(["--json", "delete", "999"])
+        .assert()
+        .code(10)
+        .get_output()
+        .clone();
+    let err: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(err["error"], "item_not_found");
+    assert_eq!(err["id"], 999);
+
+    let output = repo
+        .jjq()
+        .args(["--json", "delete", "abc"])
+        .assert()
+        .code(10)
+        .get_output()
+        .clone();
+    let err: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(err["error"], "invalid_sequence_id");
+    assert_eq!(
+        err["message"],
+        "invalid sequence ID: 'abc' (must be numeric)"
+    );
+
+    let output = repo
+        .jjq()
+        .args(["--json", "config", "strategy", "octopus"])
+        .assert()
+        .code(10)
+        .get_output()
+        .clone();
+    let err: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(err["error"], "invalid_config");
+    assert_eq!(err["key"], "strategy");
+    assert_eq!(err["value"], "octopus");
+
+    repo.jjq().args(["config", "no_such_key"]).assert().code(10);
+}
+
+#[test]
+fn test_run_wait_reports_lock_holder() {
+    use std::io::Write;
+    use std::os::unix::io::AsRawFd;
+
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq();
+    run_jj(repo.path(), &["new", "-m", "add file", "main"]);
+    fs::write(repo.path().join("newfile.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    repo.jjq_success(&["push", "feature", "--wait"]);
+
+    // Hold the run lock as another runner would
+    let lock_dir = repo.path().join(".jj/jjq-locks");
+    fs::create_dir_all(&lock_dir).unwrap();
+    let mut lock = fs::File::create(lock_dir.join("run.lock")).unwrap();
+    assert_eq!(unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX) }, 0);
+    writeln!(
+        lock,
+        r#"{{"pid":4242,"host":"elsewhere","command":"jjq run","started":0,"item":1}}"#
+    )
+    .unwrap();
+
+    let status = repo.jjq_success(&["status"]);
+    assert!(
+        status.contains("Run in progress: item 1, pid 4242, started"),
+        "{}",
+        status
+    );
+    let json: serde_json::Value =
+        serde_json::from_str(&repo.jjq_success(&["status", "--json"])).unwrap();
+    assert_eq!(json["runner"]["pid"], 4242);
+    assert_eq!(json["runner"]["host"], "elsewhere");
+
+    let output = repo
+        .jjq()
+        .args(["run", "--wait=1"])
+        .assert()
+        .code(1)
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("waiting up to 1s for run lock (held by pid 4242 on elsewhere (jjq run)"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("queue runner lock already held by pid 4242"),
+        "{}",
+        stderr
+    );
+
+    // Once the lock is released a waiting run proceeds
+    drop(lock);
+    let output = repo.jjq_success(&["run", "--wait=5"]);
+    assert!(output.contains("processing queue item 1"), "{}", output);
+}
+
+#[test]
+fn test_status_shows_current_item() {
+    let repo = TestRepo::with_go_project();
+    let release = repo.path().join(".jj/release-check");
+    repo.init_jjq_with_check(&format!(
+        "echo compiling; while [ ! -f {} ]; do sleep 0.1; done",
+        release.display()
+    ));
+    run_jj(repo.path(), &["new", "-m", "add file", "main"]);
+    fs::write(repo.path().join("newfile.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    repo.jjq_success(&["push", "feature"]);
+
+    let mut runner = process::Command::new(&*JJQ_BIN)
+        .current_dir(repo.path())
+        .env("NON_INTERACTIVE", "1")
+        .env("HOME", repo.path())
+        .arg("run")
+        .stdout(process::Stdio::null())
+        .stderr(process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    // Wait for the runner to reach its check
+    let mut current = serde_json::Value::Null;
+    for _ in 0..200 {
+        let json: serde_json::Value =
+            serde_json::from_str(&repo.jjq_success(&["status", "--json"])).unwrap();
+        if json["current"]["phase"] == "checking" && json["current"]["last_log_line"].is_string() {
+            current = json["current"].clone();
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    assert_eq!(current["id"], 1, "runner never reached its check");
+    assert_eq!(current["last_log_line"], "compiling");
+    assert!(current["check_elapsed_secs"].is_u64());
+    // No completed checks yet, so no estimate
+    assert!(current.get("eta_secs").is_none());
+
+    let status = repo.jjq_success(&["status"]);
+    assert!(status.contains("Run in progress: item 1"), "{}", status);
+    assert!(status.contains("checking for"), "{}", status);
+    assert!(status.contains("      compiling"), "{}", status);
+
+    fs::write(&release, "").unwrap();
+    assert!(runner.wait().unwrap().success());
+
+    // The state file is gone once the run finishes, and the check's
+    // duration is kept for estimates
+    let json: serde_json::Value =
+        serde_json::from_str(&repo.jjq_success(&["status", "--json"])).unwrap();
+    assert!(json.get("current").is_none());
+    assert!(repo.path().join(".jj/jjq-check-durations").exists());
+}
+
+#[test]
+fn test_cancel_running_item() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_with_check("echo started; sleep 60");
+    run_jj(repo.path(), &["new", "-m", "add file", "main"]);
+    fs::write(repo.path().join("newfile.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    repo.jjq_success(&["push", "feature"]);
+
+    // Nothing is running yet
+    let output = repo.jjq_failure(&["cancel"]);
+    assert!(output.contains("no item is running"), "{}", output);
+
+    let spawn_runner = || {
+        process::Command::new(&*JJQ_BIN)
+            .current_dir(repo.path())
+            .env("NON_INTERACTIVE", "1")
+            .env("HOME", repo.path())
+            .arg("run")
+            .stdout(process::Stdio::null())
+            .stderr(process::Stdio::null())
+            .spawn()
+            .unwrap()
+    };
+    let wait_for_check = || {
+        for _ in 0..200 {
+            let json: serde_json::Value =
+                serde_json::from_str(&repo.jjq_success(&["status", "--json"])).unwrap();
+            if json["current"]["phase"] == "checking" {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        panic!("runner never reached its check");
+    };
+
+    // --requeue leaves the item in the queue
+    let mut runner = spawn_runner();
+    wait_for_check();
+    let output = repo.jjq_failure(&["cancel", "2"]);
+    assert!(output.contains("item 2 is not running"), "{}", output);
+    let output = repo.jjq_success(&["cancel", "--requeue"]);
+    assert!(output.contains("cancelling item 1"), "{}", output);
+    assert_eq!(runner.wait().unwrap().code(), Some(1));
+    let json: serde_json::Value =
+        serde_json::from_str(&repo.jjq_success(&["status", "--json"])).unwrap();
+    assert_eq!(json["queue"][0]["id"], 1);
+    assert!(json["failed"].as_array().unwrap().is_empty());
+    let workspaces = run_jj(repo.path(), &["workspace", "list"]);
+    assert!(!workspaces.contains("jjq-run-"), "{}", workspaces);
+    let descriptions = run_jj(
+        repo.path(),
+        &["log", "-r", "all()", "--no-graph", "-T", "description"],
+    );
+    assert!(
+        !descriptions.contains("WIP: attempting merge"),
+        "{}",
+        descriptions
+    );
+
+    // Without it the item is marked failed
+    let mut runner = spawn_runner();
+    wait_for_check();
+    repo.jjq_success(&["cancel", "1"]);
+    assert_eq!(runner.wait().unwrap().code(), Some(1));
+    let json: serde_json::Value =
+        serde_json::from_str(&repo.jjq_success(&["status", "--json"])).unwrap();
+    assert!(json["queue"].as_array().unwrap().is_empty());
+    assert_eq!(json["failed"][0]["id"], 1);
+    let desc = run_jj(
+        repo.path(),
+        &[
+            "log",
+            "-r",
+            "jjq/failed/000001",
+            "--no-graph",
+            "-T",
+            "description",
+        ],
+    );
+    assert!(desc.contains("jjq-failure: cancelled"), "{}", desc);
+    // The workspace and its commits are discarded
+    assert_eq!(json["failed"][0]["workspace_path"], "");
+    let workspaces = run_jj(repo.path(), &["workspace", "list"]);
+    assert!(!workspaces.contains("jjq-run-"), "{}", workspaces);
+    let descriptions = run_jj(
+        repo.path(),
+        &["log", "-r", "all()", "--no-graph", "-T", "description"],
+    );
+    assert!(
+        !descriptions.contains("WIP: attempting merge"),
+        "{}",
+        descriptions
+    );
+
+    // The cancelled item can be requeued from its recorded candidate
+    let output = repo.jjq_success(&["requeue", "1"]);
+    assert!(output.contains("requeued failed item 1 as 2"), "{}", output);
+}
+
+#[test]
+fn test_cancel_after_check_passed_is_ignored() {
+    let repo = TestRepo::with_go_project();
+    // The check asks (from the main workspace, where the runner's state
+    // lives) to cancel its own item, then finishes successfully before the
+    // runner polls for the request
+    repo.init_jjq_with_check(&format!(
+        "cd '{}' && '{}' cancel $JJQ_ID",
+        repo.path().display(),
+        JJQ_BIN.display()
+    ));
+    run_jj(repo.path(), &["new", "-m", "add file", "main"]);
+    fs::write(repo.path().join("newfile.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    repo.jjq_success(&["push", "feature"]);
+
+    let output = repo.jjq_success(&["run"]);
+    assert!(output.contains("rebased 1 to main"), "{}", output);
+    let json: serde_json::Value =
+        serde_json::from_str(&repo.jjq_success(&["status", "--json"])).unwrap();
+    assert!(json["failed"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn test_pause_and_resume() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_with_check("true");
+    repo.jjq_success(&["pause", "--reason", "incident 42"]);
+
+    // Pushes are still accepted while paused
+    run_jj(repo.path(), &["new", "-m", "add file", "main"]);
+    fs::write(repo.path().join("newfile.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    let output = repo.jjq_success(&["push", "feature"]);
+    assert!(
+        output.contains("will land after 'jjq resume'"),
+        "{}",
+        output
+    );
+
+    let output = repo.jjq().arg("run").assert().code(4).get_output().clone();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("jjq: queue is paused by"), "{}", stderr);
+    assert!(stderr.contains(": incident 42"), "{}", stderr);
+    repo.jjq().args(["run", "--all"]).assert().code(4);
+
+    let status = repo.jjq_success(&["status"]);
+    assert!(status.contains("QUEUE PAUSED: paused by"), "{}", status);
+    assert!(status.contains("incident 42"), "{}", status);
+    let json: serde_json::Value =
+        serde_json::from_str(&repo.jjq_success(&["status", "--json"])).unwrap();
+    assert_eq!(json["paused"]["reason"], "incident 42");
+    assert!(json["paused"]["since"].is_u64());
+    assert_eq!(json["queue"][0]["id"], 1);
+
+    let doctor = repo.jjq_success(&["doctor"]);
+    assert!(doctor.contains("WARN  queue paused by"), "{}", doctor);
+
+    repo.jjq_success(&["resume"]);
+    let output = repo.jjq_success(&["run"]);
+    assert!(output.contains("rebased 1 to main"), "{}", output);
+    let json: serde_json::Value =
+        serde_json::from_str(&repo.jjq_success(&["status", "--json"])).unwrap();
+    assert!(json.get("paused").is_none());
+}
+
+#[test]
+fn test_freeze_window_blocks_run() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq();
+
+    let output = repo.jjq_failure(&["config", "freeze_windows", "Fri 16:00-08:00"]);
+    assert!(
+        output.contains("invalid value for freeze_windows"),
+        "{}",
+        output
+    );
+
+    // A window ending when it starts covers the whole day, every day
+    repo.jjq_success(&["config", "freeze_windows", "00:00-00:00"]);
+    run_jj(repo.path(), &["new", "-m", "add file", "main"]);
+    fs::write(repo.path().join("newfile.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    repo.jjq_success(&["push", "feature"]);
+
+    let output = repo.jjq().arg("run").assert().code(4).get_output().clone();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr
+            .contains("landing closed: freeze window 00:00-00:00; no landing window is scheduled"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("--ignore-schedule"), "{}", stderr);
+
+    let status = repo.jjq_success(&["status"]);
+    assert!(
+        status.contains("Landing closed: freeze window 00:00-00:00"),
+        "{}",
+        status
+    );
+    let json: serde_json::Value =
+        serde_json::from_str(&repo.jjq_success(&["status", "--json"])).unwrap();
+    assert_eq!(
+        json["landing_closed"]["reason"],
+        "freeze window 00:00-00:00"
+    );
+
+    let output = repo.jjq_success(&["run", "--ignore-schedule"]);
+    assert!(output.contains("rebased 1 to main"), "{}", output);
+}
+
+#[test]
+fn test_required_approval() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq();
+    repo.jjq_success(&["config", "require_approval", "true"]);
+    repo.jjq_success(&["config", "approval_trailers", "Reviewed-by"]);
+
+    run_jj(repo.path(), &["new", "-m", "unreviewed", "main"]);
+    fs::write(repo.path().join("a.txt"), "a").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "unreviewed"]);
+    repo.jjq_success(&["push", "unreviewed"]);
+
+    run_jj(
+        repo.path(),
+        &["new", "-m", "reviewed\n\nReviewed-by: Alice", "main"],
+    );
+    fs::write(repo.path().join("b.txt"), "b").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "reviewed"]);
+    repo.jjq_success(&["push", "reviewed"]);
+
+    let status = repo.jjq_success(&["status"]);
+    assert!(
+        status.contains("unreviewed (blocked: needs approval)"),
+        "{}",
+        status
+    );
+    let json: serde_json::Value =
+        serde_json::from_str(&repo.jjq_success(&["status", "--json"])).unwrap();
+    assert_eq!(json["queue"][0]["blocked"], "needs approval");
+    assert_eq!(json["queue"][1]["approved_by"], "Reviewed-by: Alice");
+
+    // The unapproved item is skipped, not tested, and stays queued
+    let output = repo.jjq_success(&["run"]);
+    assert!(
+        output.contains("skipping queue item 1: needs approval"),
+        "{}",
+        output
+    );
+    assert!(output.contains("rebased 2 to main"), "{}", output);
+    let output = repo.jjq_success(&["run"]);
+    assert!(
+        output.contains("no approved items in queue (1 blocked: needs approval)"),
+        "{}",
+        output
+    );
+
+    let output = repo.jjq_success(&["approve", "1"]);
+    assert!(output.contains("approved queue item 1"), "{}", output);
+    let detail = repo.jjq_success(&["status", "1"]);
+    assert!(detail.contains("(jjq approve)"), "{}", detail);
+    let output = repo.jjq_success(&["run"]);
+    assert!(output.contains("rebased 1 to main"), "{}", output);
+
+    let output = repo.jjq_failure(&["approve", "1"]);
+    assert!(output.contains("item 1 is not in the queue"), "{}", output);
+}
+
+#[test]
+fn test_delete_and_requeue_remove_approval() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq_with_check("exit 1");
+    repo.jjq_success(&["config", "require_approval", "true"]);
+    let approval_for = |bookmark: &str| {
+        let commit = run_jj(
+            repo.path(),
+            &["log", "-r", bookmark, "--no-graph", "-T", "commit_id"],
+        );
+        format!("approvals/{}", commit.trim())
+    };
+
+    run_jj(repo.path(), &["new", "-m", "add file", "main"]);
+    fs::write(repo.path().join("newfile.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    let record = approval_for("feature");
+
+    // Deleting a queued item drops its approval
+    repo.jjq_success(&["push", "feature"]);
+    repo.jjq_success(&["approve", "1"]);
+    assert!(repo.jj_file_exists(&record, "jjq/_/_"));
+    repo.jjq_success(&["delete", "1"]);
+    assert!(!repo.jj_file_exists(&record, "jjq/_/_"));
+
+    // So does deleting a failed one
+    repo.jjq_success(&["push", "feature"]);
+    repo.jjq_success(&["approve", "2"]);
+    repo.jjq_failure(&["run"]);
+    assert!(repo.jj_file_exists(&record, "jjq/_/_"));
+    repo.jjq_success(&["delete", "2"]);
+    assert!(!repo.jj_file_exists(&record, "jjq/_/_"));
+
+    // Requeuing the unchanged commit keeps its approval; requeuing a
+    // rewritten one drops the old commit's
+    repo.jjq_success(&["push", "feature"]);
+    repo.jjq_success(&["approve", "3"]);
+    repo.jjq_failure(&["run"]);
+    repo.jjq_success(&["requeue", "3"]);
+    assert!(repo.jj_file_exists(&record, "jjq/_/_"));
+    repo.jjq_success(&["config", "require_approval", "false"]);
+    repo.jjq_failure(&["run"]);
+    run_jj(
+        repo.path(),
+        &["describe", "-r", "feature", "-m", "add file, fixed"],
+    );
+    repo.jjq_success(&["requeue", "4"]);
+    assert!(!repo.jj_file_exists(&record, "jjq/_/_"));
+}
+
+#[test]
+fn test_push_description_policy() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq();
+    repo.jjq_success(&["config", "description_required", "true"]);
+    repo.jjq_success(&["config", "description_deny", "^(WIP|fixup!)"]);
+    repo.jjq_success(&["config", "description_require", r"[A-Z]+-\d+"]);
+
+    run_jj(repo.path(), &["new", "-m", "WIP: try things", "main"]);
+    fs::write(repo.path().join("newfile.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+
+    let output = repo
+        .jjq()
+        .args(["push", "feature"])
+        .assert()
+        .code(10)
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("title matches denied pattern '^(WIP|fixup!)'"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains(r"does not match required pattern '[A-Z]+-\d+'"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains(
+            "revision 'feature' rejected by description policy (2 problem(s)); use --force"
+        ),
+        "{}",
+        stderr
+    );
+    let json: serde_json::Value =
+        serde_json::from_str(&repo.jjq_success(&["status", "--json"])).unwrap();
+    assert!(json["queue"].as_array().unwrap().is_empty());
+
+    // A fixed description passes; --force bypasses the policy
+    run_jj(
+        repo.path(),
+        &["describe", "-m", "Add file\n\nRefs: PROJ-7", "feature"],
+    );
+    repo.jjq_success(&["push", "feature"]);
+    run_jj(repo.path(), &["new", "-m", "", "feature"]);
+    fs::write(repo.path().join("other.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "empty-desc"]);
+    let output = repo.jjq_failure(&["push", "empty-desc"]);
+    assert!(output.contains(": description is empty"), "{}", output);
+    repo.jjq_success(&["push", "empty-desc", "--force"]);
+}
+
+#[test]
+fn test_squash_strategy() {
+    let repo = TestRepo::with_go_project();
+    repo.jjq_success(&[
+        "init",
+        "--trunk",
+        "main",
+        "--check",
+        "true",
+        "--strategy",
+        "squash",
+    ]);
+    repo.jjq_success(&["config", "squash_message", "{title} (#{id})\n\n{titles}"]);
+
+    run_jj(repo.path(), &["new", "-m", "add a", "main"]);
+    fs::write(repo.path().join("a.txt"), "a").unwrap();
+    run_jj(repo.path(), &["new", "-m", "add b"]);
+    fs::write(repo.path().join("b.txt"), "b").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    let originals = run_jj(
+        repo.path(),
+        &[
+            "log",
+            "-r",
+            "main..feature",
+            "--no-graph",
+            "-T",
+            "change_id.short() ++ \"\\n\"",
+        ],
+    );
+
+    let old_trunk = run_jj(
+        repo.path(),
+        &["log", "-r", "main", "--no-graph", "-T", "commit_id"],
+    );
+
+    repo.jjq_success(&["push", "feature"]);
+    let output = repo.jjq_success(&["run"]);
+    assert!(output.contains("squashed 1 to main"), "{}", output);
+
+    // One new commit on the old trunk, holding both files
+    let parent = run_jj(
+        repo.path(),
+        &["log", "-r", "main-", "--no-graph", "-T", "commit_id"],
+    );
+    assert_eq!(parent, old_trunk);
+    assert!(repo.jj_file_exists("a.txt", "main"));
+    assert!(repo.jj_file_exists("b.txt", "main"));
+
+    let desc = run_jj(
+        repo.path(),
+        &["log", "-r", "main", "--no-graph", "-T", "description"],
+    );
+    assert!(
+        desc.starts_with("add b (#1)\n\n- add a\n- add b\n"),
+        "{}",
+        desc
+    );
+    assert!(
+        desc.contains("jjq-sequence: 1\njjq-strategy: squash"),
+        "{}",
+        desc
+    );
+    for change_id in originals.lines() {
+        assert!(
+            desc.contains(&format!("jjq-squashed: {}", change_id)),
+            "{}",
+            desc
+        );
+    }
+
+    // The originals are kept by default
+    let kept = run_jj(
+        repo.path(),
+        &["log", "-r", "feature", "--no-graph", "-T", "description"],
+    );
+    assert_eq!(kept.trim(), "add b");
+
+    let json: serde_json::Value =
+        serde_json::from_str(&repo.jjq_success(&["status", "--json"])).unwrap();
+    assert_eq!(json["landed"][0]["id"], 1);
+}
+
+#[test]
+fn test_fast_forward_strategy() {
+    let repo = TestRepo::with_go_project();
+    repo.jjq_success(&[
+        "init",
+        "--trunk",
+        "main",
+        "--check",
+        "true",
+        "--strategy",
+        "fast-forward",
+    ]);
+    let old_trunk = run_jj(
+        repo.path(),
+        &["log", "-r", "main", "--no-graph", "-T", "commit_id"],
+    );
+
+    // A descendant of trunk lands as-is
+    run_jj(repo.path(), &["new", "-m", "add a", "main"]);
+    fs::write(repo.path().join("a.txt"), "a").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    let feature = run_jj(
+        repo.path(),
+        &["log", "-r", "feature", "--no-graph", "-T", "commit_id"],
+    );
+
+    repo.jjq_success(&["push", "feature"]);
+    let output = repo.jjq_success(&["run"]);
+    assert!(output.contains("fast-forwarded 1 to main"), "{}", output);
+    let trunk = run_jj(
+        repo.path(),
+        &["log", "-r", "main", "--no-graph", "-T", "commit_id"],
+    );
+    assert_eq!(trunk, feature);
+    // Nothing the check ran in is left on top of the new trunk
+    let children = run_jj(
+        repo.path(),
+        &["log", "-r", "main+", "--no-graph", "-T", "description"],
+    );
+    assert_eq!(children.trim(), "", "{}", children);
+
+    // A sibling of the new trunk is refused
+    run_jj(repo.path(), &["new", "-m", "add b", &old_trunk]);
+    fs::write(repo.path().join("b.txt"), "b").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "stale"]);
+
+    repo.jjq_success(&["push", "stale"]);
+    let output = repo.jjq_failure(&["run"]);
+    assert!(
+        output.contains("merge 2 is not a fast-forward of main"),
+        "{}",
+        output
+    );
+    assert!(output.contains("To resolve:"), "{}", output);
+    assert!(output.contains("jj rebase -r"), "{}", output);
+
+    let desc = run_jj(
+        repo.path(),
+        &[
+            "log",
+            "-r",
+            "jjq/failed/000002",
+            "--no-graph",
+            "-T",
+            "description",
+        ],
+    );
+    assert!(desc.contains("jjq-failure: not-fast-forward"), "{}", desc);
+    let trunk_after = run_jj(
+        repo.path(),
+        &["log", "-r", "main", "--no-graph", "-T", "commit_id"],
+    );
+    assert_eq!(trunk_after, feature);
+
+    // The unchanged commit is still listed as landed
+    let json: serde_json::Value =
+        serde_json::from_str(&repo.jjq_success(&["status", "--json"])).unwrap();
+    assert_eq!(json["landed"][0]["id"], 1);
+}
+
+#[test]
+fn test_push_strategy_override() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq();
+
+    run_jj(repo.path(), &["new", "-m", "integration", "main"]);
+    fs::write(repo.path().join("integration.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "integration"]);
+
+    let output = repo.jjq_failure(&["push", "integration", "--strategy", "octopus"]);
+    assert!(output.contains("invalid strategy: octopus"), "{}", output);
+
+    repo.jjq_success(&["push", "integration", "--strategy", "merge"]);
+    let status = repo.jjq_success(&["status"]);
+    assert!(
+        status.contains("integration (strategy: merge)"),
+        "{}",
+        status
+    );
+    let json: serde_json::Value =
+        serde_json::from_str(&repo.jjq_success(&["status", "--json"])).unwrap();
+    assert_eq!(json["queue"][0]["strategy"], "merge");
+
+    // The item lands as a merge commit although the repo rebases
+    let output = repo.jjq_success(&["run"]);
+    assert!(
+        output.contains("processing queue item 1 (merge strategy)"),
+        "{}",
+        output
+    );
+    assert!(output.contains("merged 1 to main"), "{}", output);
+    let parents = run_jj(
+        repo.path(),
+        &["log", "-r", "main", "--no-graph", "-T", "parents.len()"],
+    );
+    assert_eq!(parents.trim(), "2");
+
+    // The override and pusher are forgotten once the item has landed
+    assert!(!repo.jj_file_exists("strategy/000001", "jjq/_/_"));
+    assert!(!repo.jj_file_exists("pusher/000001", "jjq/_/_"));
+
+    // Items pushed without --strategy still use the configured one
+    run_jj(repo.path(), &["new", "-m", "everyday", "main"]);
+    fs::write(repo.path().join("everyday.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "everyday"]);
+    repo.jjq_success(&["push", "everyday"]);
+    let json: serde_json::Value =
+        serde_json::from_str(&repo.jjq_success(&["status", "--json"])).unwrap();
+    assert!(json["queue"][0].get("strategy").is_none());
+    let output = repo.jjq_success(&["run"]);
+    assert!(output.contains("rebased 2 to main"), "{}", output);
+}
+
+#[test]
+fn test_landed_message_template_without_trailers() {
+    let repo = TestRepo::with_go_project();
+    repo.init_jjq();
+    repo.jjq_success(&[
+        "config",
+        "landed_message",
+        "{title} (#{id})\n\n{body}\n\nChecked in {duration}; trunk {trunk_before} -> {trunk_after}",
+    ]);
+    repo.jjq_success(&["config", "landed_trailers", "false"]);
+    repo.jjq_failure(&["config", "landed_trailers", "maybe"]);
+
+    run_jj(
+        repo.path(),
+        &["new", "-m", "add feature\n\nSome details.", "main"],
+    );
+    fs::write(repo.path().join("feature.txt"), "content").unwrap();
+    run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    let old_trunk = run_jj(
+        repo.path(),
+        &["log", "-r", "main", "--no-graph", "-T", "change_id.short()"],
+    );
+
+    repo.jjq_success(&["push", "feature"]);
+    let output = repo.jjq_success(&["run"]);
+    assert!(output.contains("rebased 1 to main"), "{}", output);
+
+    let trunk = run_jj(
+        repo.path(),
+        &["log", "-r", "main", "--no-graph", "-T", "change_id.short()"],
+    );
+    let desc = run_jj(
+        repo.path(),
+        &["log", "-r", "main", "--no-graph", "-T", "description"],
+    );
+    assert!(
+        desc.starts_with("add feature (#1)\n\nSome details.\n\nChecked in "),
+        "{}",
+        desc
+    );
+    assert!(
+        desc.contains(&format!("trunk {} -> {}", old_trunk.trim(), trunk.trim())),
+        "{}",
+        desc
+    );
+    assert!(!desc.contains("jjq-"), "{}", desc);
+
+    // Status still finds the landing without trailers
+    let json: serde_json::Value =
+        serde_json::from_str(&repo.jjq_success(&["status", "--json"])).unwrap();
+    assert_eq!(json["landed"][0]["id"], 1);
+    assert_eq!(json["landed"][0]["description"], "add feature (#1)");
+}
+
+#[test]
+fn test_library_opens_queues_on_two_repositories() {
+    let first = TestRepo::with_go_project();
+    let second = TestRepo::with_go_project();
+    first.init_jjq();
+    second.init_jjq();
+    for repo in [&first, &second] {
+        run_jj(repo.path(), &["new", "-m", "add file", "main"]);
+        fs::write(repo.path().join("newfile.txt"), "content").unwrap();
+        run_jj(repo.path(), &["bookmark", "create", "feature"]);
+    }
+
+    let first_queue = jjq::Queue::open(first.path()).unwrap();
+    let second_queue = jjq::Queue::open(second.path()).unwrap();
+    assert_eq!(first_queue.push("feature").unwrap(), 1);
+    assert_eq!(second_queue.push("feature").unwrap(), 1);
+    assert!(matches!(
+        first_queue.run_one().unwrap(),
+        jjq::RunResult::Success
+    ));
+
+    // Each queue only saw its own repository's items
+    assert!(first_queue.status().unwrap().queue.is_empty());
+    assert_eq!(second_queue.status().unwrap().queue.len(), 1);
+}